@@ -6,10 +6,18 @@ module Cbor =
 
     // === Writer ===
 
-    type Writer() =
+    type Writer(deterministic: bool) =
         let mutable buf: byte array = Array.zeroCreate 256
         let mutable pos: int = 0
 
+        new() = Writer(false)
+
+        /// A writer that follows the deterministic encoding profile in SPEC.md,
+        /// so equal values always produce identical bytes. Generated encoders
+        /// already meet the rest of the profile; the writer additionally
+        /// replaces every NaN with the canonical quiet NaN.
+        static member Deterministic() = Writer(true)
+
         member private _.Grow(n: int) =
             if pos + n > buf.Length then
                 let mutable cap = buf.Length
@@ -139,26 +147,30 @@ module Cbor =
 
         member this.WriteF16(v: Half) =
             this.WriteByte(0xF9uy)
-            let bytes = BitConverter.GetBytes(v)
-            if not BitConverter.IsLittleEndian then
-                this.Grow(2)
-                buf.[pos] <- bytes.[0]
-                buf.[pos + 1] <- bytes.[1]
-                pos <- pos + 2
+            if deterministic && Half.IsNaN(v) then
+                this.W16(0x7E00us)
             else
-                this.Grow(2)
-                buf.[pos] <- bytes.[1]
-                buf.[pos + 1] <- bytes.[0]
-                pos <- pos + 2
+                let bytes = BitConverter.GetBytes(v)
+                if not BitConverter.IsLittleEndian then
+                    this.Grow(2)
+                    buf.[pos] <- bytes.[0]
+                    buf.[pos + 1] <- bytes.[1]
+                    pos <- pos + 2
+                else
+                    this.Grow(2)
+                    buf.[pos] <- bytes.[1]
+                    buf.[pos + 1] <- bytes.[0]
+                    pos <- pos + 2
 
         member this.WriteF32(v: float32) =
             this.WriteByte(0xFAuy)
-            let bits = BitConverter.SingleToUInt32Bits(v)
+            // .NET's NaN constants have the sign bit set, so write the canonical bits directly
+            let bits = if deterministic && Single.IsNaN(v) then 0x7FC00000u else BitConverter.SingleToUInt32Bits(v)
             this.W32(bits)
 
         member this.WriteF64(v: float) =
             this.WriteByte(0xFBuy)
-            let bits = BitConverter.DoubleToUInt64Bits(v)
+            let bits = if deterministic && Double.IsNaN(v) then 0x7FF8000000000000UL else BitConverter.DoubleToUInt64Bits(v)
             this.W64(bits)
 
         member this.WriteString(v: string) =
@@ -5,6 +5,7 @@ use cboragen_bench::minicbor_types as mini;
 
 // --- Test data constructors ---
 
+#[allow(clippy::approx_constant)]
 fn cbg_primitives() -> cbg::Primitives {
     cbg::Primitives {
         b: true,
@@ -25,6 +26,7 @@ fn cbg_primitives() -> cbg::Primitives {
     }
 }
 
+#[allow(clippy::approx_constant)]
 fn mini_primitives() -> mini::Primitives {
     mini::Primitives {
         b: true,
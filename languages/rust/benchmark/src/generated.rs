@@ -9,8 +9,8 @@ pub struct ShapeRect {
     pub w: f64,
     pub h: f64,
 }
-/// All primitive types in one struct
 
+/// All primitive types in one struct
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Primitives {
     pub b: bool,
@@ -29,75 +29,75 @@ pub struct Primitives {
     pub str_: String,
     pub bin: Vec<u8>,
 }
-/// Struct with optional fields
 
+/// Struct with optional fields
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct WithOptionals {
     pub required: String,
     pub maybe: Option<u32>,
     pub maybe_str: Option<String>,
 }
-/// Simple enum
 
+/// Simple enum
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Color {
     Red,
     Green,
     Blue,
 }
-/// Union with unit and payload variants
 
+/// Union with unit and payload variants
 #[derive(Debug, Clone, PartialEq)]
 pub enum Shape {
     Circle(f64),
     Rect(ShapeRect),
     Point,
 }
-/// Variable-length array wrapper
 
+/// Variable-length array wrapper
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Numbers {
     pub values: Vec<i32>,
 }
-/// Fixed-length array wrapper
 
+/// Fixed-length array wrapper
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Vec3 {
     pub xyz: Vec<f64>,
 }
-/// External-length array wrapper
 
+/// External-length array wrapper
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct TimeSeries {
     pub count: u32,
     pub timestamps: Vec<i64>,
     pub values: Vec<f64>,
 }
-/// Named type references
 
+/// Named type references
 #[derive(Debug, Clone, PartialEq)]
 pub struct ColoredShape {
     pub color: Color,
     pub shape: Shape,
 }
-/// Type alias
 
+/// Type alias
 pub type Id = u64;
-/// Struct referencing an alias
 
+/// Struct referencing an alias
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Entity {
     pub id: Id,
     pub name: String,
 }
-/// Nested arrays
 
+/// Nested arrays
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Matrix {
     pub rows: Vec<Vec<f64>>,
 }
-/// Struct with gaps in ranks
 
+/// Struct with gaps in ranks
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Sparse {
     pub first: u32,
@@ -182,12 +182,12 @@ fn _enc_vec3(w: &mut Writer, v: &Vec3) {
 fn _enc_time_series(w: &mut Writer, v: &TimeSeries) {
     w.write_array_header(3);
     w.write_u32(v.count);
-    w.write_byte(0x9f);
+    w.write_indefinite_array_start();
     for _item0 in &v.timestamps { w.write_i64(*_item0); }
-    w.write_byte(0xff);
-    w.write_byte(0x9f);
+    w.write_break();
+    w.write_indefinite_array_start();
     for _item0 in &v.values { w.write_f64(*_item0); }
-    w.write_byte(0xff);
+    w.write_break();
 }
 
 fn _enc_colored_shape(w: &mut Writer, v: &ColoredShape) {
@@ -237,7 +237,7 @@ fn _enc_sparse(w: &mut Writer, v: &Sparse) {
 // === Decoders ===
 
 fn _dec_shape_rect(r: &mut Reader) -> Result<ShapeRect, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(2)?;
     let mut _w: f64 = 0.0;
     let mut _h: f64 = 0.0;
     if _len > 0 { _w = r.read_f64()?; }
@@ -250,7 +250,7 @@ fn _dec_shape_rect(r: &mut Reader) -> Result<ShapeRect, DecodeError> {
 }
 
 fn _dec_primitives(r: &mut Reader) -> Result<Primitives, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(15)?;
     let mut _b: bool = false;
     let mut _u8v: u8 = 0;
     let mut _u16v: u16 = 0;
@@ -302,13 +302,13 @@ fn _dec_primitives(r: &mut Reader) -> Result<Primitives, DecodeError> {
 }
 
 fn _dec_with_optionals(r: &mut Reader) -> Result<WithOptionals, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(3)?;
     let mut _required: String = String::new();
     let mut _maybe: Option<u32> = None;
     let mut _maybe_str: Option<String> = None;
     if _len > 0 { _required = r.read_string()?; }
-    if _len > 1 { _maybe = if r.peek_byte()? == 0x00 { r.read_byte()?; None } else { r.read_byte()?; Some(r.read_u32()?) }; }
-    if _len > 2 { _maybe_str = if r.peek_byte()? == 0x00 { r.read_byte()?; None } else { r.read_byte()?; Some(r.read_string()?) }; }
+    if _len > 1 { _maybe = if r.read_option_tag()? { Some(r.read_u32()?) } else { None }; }
+    if _len > 2 { _maybe_str = if r.read_option_tag()? { Some(r.read_string()?) } else { None }; }
     for _ in 3.._len { r.skip()?; }
     Ok(WithOptionals {
         required: _required,
@@ -327,18 +327,9 @@ fn _dec_color(r: &mut Reader) -> Result<Color, DecodeError> {
 }
 
 fn _dec_shape(r: &mut Reader) -> Result<Shape, DecodeError> {
-    let _b = r.peek_byte()?;
-    let _maj = _b >> 5;
+    let _maj = r.peek_major_type()?;
     if _maj == 6 {
-        let _b = r.read_byte()?;
-        let _ai = _b & 0x1f;
-        let _tag: u64 = match _ai {
-            0..=23 => _ai as u64,
-            24 => r.read_byte()? as u64,
-            25 => { let b = [r.read_byte()?, r.read_byte()?]; u16::from_be_bytes(b) as u64 }
-            26 => { let b = [r.read_byte()?, r.read_byte()?, r.read_byte()?, r.read_byte()?]; u32::from_be_bytes(b) as u64 }
-            _ => return Err(DecodeError::InvalidData("unsupported tag size".into())),
-        };
+        let _tag = r.read_tag_header()?;
         match _tag {
             0 => Ok(Shape::Circle(r.read_f64()?)),
             1 => Ok(Shape::Rect(_dec_shape_rect(r)?)),
@@ -356,7 +347,7 @@ fn _dec_shape(r: &mut Reader) -> Result<Shape, DecodeError> {
 }
 
 fn _dec_numbers(r: &mut Reader) -> Result<Numbers, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(1)?;
     let mut _values: Vec<i32> = Vec::new();
     if _len > 0 { _values = { let _n = r.read_array_header()?; let mut _a = Vec::with_capacity(_n); for _ in 0.._n { _a.push(r.read_i32()?); } _a }; }
     for _ in 1.._len { r.skip()?; }
@@ -366,7 +357,7 @@ fn _dec_numbers(r: &mut Reader) -> Result<Numbers, DecodeError> {
 }
 
 fn _dec_vec3(r: &mut Reader) -> Result<Vec3, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(1)?;
     let mut _xyz: Vec<f64> = Vec::new();
     if _len > 0 { _xyz = { let _ = r.read_array_header()?; let mut _a = Vec::with_capacity(3); for _ in 0..3usize { _a.push(r.read_f64()?); } _a }; }
     for _ in 1.._len { r.skip()?; }
@@ -376,13 +367,13 @@ fn _dec_vec3(r: &mut Reader) -> Result<Vec3, DecodeError> {
 }
 
 fn _dec_time_series(r: &mut Reader) -> Result<TimeSeries, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(3)?;
     let mut _count: u32 = 0;
     let mut _timestamps: Vec<i64> = Vec::new();
     let mut _values: Vec<f64> = Vec::new();
     if _len > 0 { _count = r.read_u32()?; }
-    if _len > 1 { _timestamps = { if r.read_byte()? != 0x9f { return Err(DecodeError::InvalidData("expected indefinite array".into())); } let mut _a = Vec::new(); while r.peek_byte()? != 0xff { _a.push(r.read_i64()?); } r.read_byte()?; _a }; }
-    if _len > 2 { _values = { if r.read_byte()? != 0x9f { return Err(DecodeError::InvalidData("expected indefinite array".into())); } let mut _a = Vec::new(); while r.peek_byte()? != 0xff { _a.push(r.read_f64()?); } r.read_byte()?; _a }; }
+    if _len > 1 { _timestamps = { r.read_indefinite_array_start()?; let mut _a = Vec::new(); while !r.try_read_break()? { _a.push(r.read_i64()?); } _a }; }
+    if _len > 2 { _values = { r.read_indefinite_array_start()?; let mut _a = Vec::new(); while !r.try_read_break()? { _a.push(r.read_f64()?); } _a }; }
     for _ in 3.._len { r.skip()?; }
     Ok(TimeSeries {
         count: _count,
@@ -392,7 +383,7 @@ fn _dec_time_series(r: &mut Reader) -> Result<TimeSeries, DecodeError> {
 }
 
 fn _dec_colored_shape(r: &mut Reader) -> Result<ColoredShape, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(2)?;
     let mut _color: Color = Color::Red;
     let mut _shape: Shape = Shape::Circle(0.0);
     if _len > 0 { _color = _dec_color(r)?; }
@@ -405,7 +396,8 @@ fn _dec_colored_shape(r: &mut Reader) -> Result<ColoredShape, DecodeError> {
 }
 
 fn _dec_id(r: &mut Reader) -> Result<Id, DecodeError> {
-    Ok(r.read_u64()?)
+    let v = r.read_u64()?;
+    Ok(v)
 }
 
 pub fn decode_id(data: &[u8]) -> Result<Id, DecodeError> {
@@ -414,7 +406,7 @@ pub fn decode_id(data: &[u8]) -> Result<Id, DecodeError> {
 }
 
 fn _dec_entity(r: &mut Reader) -> Result<Entity, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(2)?;
     let mut _id: Id = 0;
     let mut _name: String = String::new();
     if _len > 0 { _id = _dec_id(r)?; }
@@ -427,7 +419,7 @@ fn _dec_entity(r: &mut Reader) -> Result<Entity, DecodeError> {
 }
 
 fn _dec_matrix(r: &mut Reader) -> Result<Matrix, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(1)?;
     let mut _rows: Vec<Vec<f64>> = Vec::new();
     if _len > 0 { _rows = { let _n = r.read_array_header()?; let mut _a = Vec::with_capacity(_n); for _ in 0.._n { _a.push({ let _n = r.read_array_header()?; let mut _a = Vec::with_capacity(_n); for _ in 0.._n { _a.push(r.read_f64()?); } _a }); } _a }; }
     for _ in 1.._len { r.skip()?; }
@@ -437,20 +429,20 @@ fn _dec_matrix(r: &mut Reader) -> Result<Matrix, DecodeError> {
 }
 
 fn _dec_sparse(r: &mut Reader) -> Result<Sparse, DecodeError> {
-    let _len = r.read_array_header()?;
+    let _len = r.read_struct_header(11)?;
     let mut _first: u32 = 0;
     let mut _second: String = String::new();
     let mut _third: bool = false;
     if _len > 0 { _first = r.read_u32()?; }
-    if _len > 1 { r.skip()?; }
-    if _len > 2 { r.skip()?; }
-    if _len > 3 { r.skip()?; }
-    if _len > 4 { r.skip()?; }
+    if _len > 1 { r.skip_gap()?; }
+    if _len > 2 { r.skip_gap()?; }
+    if _len > 3 { r.skip_gap()?; }
+    if _len > 4 { r.skip_gap()?; }
     if _len > 5 { _second = r.read_string()?; }
-    if _len > 6 { r.skip()?; }
-    if _len > 7 { r.skip()?; }
-    if _len > 8 { r.skip()?; }
-    if _len > 9 { r.skip()?; }
+    if _len > 6 { r.skip_gap()?; }
+    if _len > 7 { r.skip_gap()?; }
+    if _len > 8 { r.skip_gap()?; }
+    if _len > 9 { r.skip_gap()?; }
     if _len > 10 { _third = r.read_bool()?; }
     for _ in 11.._len { r.skip()?; }
     Ok(Sparse {
@@ -478,11 +470,35 @@ impl ShapeRect {
         _dec_shape_rect(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_shape_rect(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_shape_rect(r)
     }
 }
 
+impl cboragen_runtime::Encode for ShapeRect {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_shape_rect(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for ShapeRect {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_shape_rect(r)
+    }
+
+    fn default_value() -> Self {
+        ShapeRect { w: 0.0, h: 0.0 }
+    }
+}
+
 impl Primitives {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -499,11 +515,35 @@ impl Primitives {
         _dec_primitives(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_primitives(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_primitives(r)
     }
 }
 
+impl cboragen_runtime::Encode for Primitives {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_primitives(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Primitives {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_primitives(r)
+    }
+
+    fn default_value() -> Self {
+        Primitives { b: false, u8v: 0, u16v: 0, u32v: 0, u64v: 0, i8v: 0, i16v: 0, i32v: 0, i64v: 0, f32v: 0.0, f64v: 0.0, uvar: 0, ivar: 0, str_: String::new(), bin: Vec::new() }
+    }
+}
+
 impl WithOptionals {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -520,11 +560,35 @@ impl WithOptionals {
         _dec_with_optionals(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_with_optionals(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_with_optionals(r)
     }
 }
 
+impl cboragen_runtime::Encode for WithOptionals {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_with_optionals(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for WithOptionals {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_with_optionals(r)
+    }
+
+    fn default_value() -> Self {
+        WithOptionals { required: String::new(), maybe: None, maybe_str: None }
+    }
+}
+
 impl Color {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -541,11 +605,35 @@ impl Color {
         _dec_color(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_color(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_color(r)
     }
 }
 
+impl cboragen_runtime::Encode for Color {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_color(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Color {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_color(r)
+    }
+
+    fn default_value() -> Self {
+        Color::Red
+    }
+}
+
 impl Shape {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -562,11 +650,35 @@ impl Shape {
         _dec_shape(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_shape(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_shape(r)
     }
 }
 
+impl cboragen_runtime::Encode for Shape {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_shape(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Shape {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_shape(r)
+    }
+
+    fn default_value() -> Self {
+        Shape::Circle(0.0)
+    }
+}
+
 impl Numbers {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -583,11 +695,35 @@ impl Numbers {
         _dec_numbers(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_numbers(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_numbers(r)
     }
 }
 
+impl cboragen_runtime::Encode for Numbers {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_numbers(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Numbers {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_numbers(r)
+    }
+
+    fn default_value() -> Self {
+        Numbers { values: Vec::new() }
+    }
+}
+
 impl Vec3 {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -604,11 +740,35 @@ impl Vec3 {
         _dec_vec3(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_vec3(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_vec3(r)
     }
 }
 
+impl cboragen_runtime::Encode for Vec3 {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_vec3(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Vec3 {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_vec3(r)
+    }
+
+    fn default_value() -> Self {
+        Vec3 { xyz: Vec::new() }
+    }
+}
+
 impl TimeSeries {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -625,11 +785,35 @@ impl TimeSeries {
         _dec_time_series(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_time_series(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_time_series(r)
     }
 }
 
+impl cboragen_runtime::Encode for TimeSeries {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_time_series(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for TimeSeries {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_time_series(r)
+    }
+
+    fn default_value() -> Self {
+        TimeSeries { count: 0, timestamps: Vec::new(), values: Vec::new() }
+    }
+}
+
 impl ColoredShape {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -646,11 +830,35 @@ impl ColoredShape {
         _dec_colored_shape(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_colored_shape(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_colored_shape(r)
     }
 }
 
+impl cboragen_runtime::Encode for ColoredShape {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_colored_shape(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for ColoredShape {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_colored_shape(r)
+    }
+
+    fn default_value() -> Self {
+        ColoredShape { color: Color::Red, shape: Shape::Circle(0.0) }
+    }
+}
+
 impl Entity {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -667,11 +875,35 @@ impl Entity {
         _dec_entity(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_entity(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_entity(r)
     }
 }
 
+impl cboragen_runtime::Encode for Entity {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_entity(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Entity {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_entity(r)
+    }
+
+    fn default_value() -> Self {
+        Entity { id: 0, name: String::new() }
+    }
+}
+
 impl Matrix {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -688,11 +920,35 @@ impl Matrix {
         _dec_matrix(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_matrix(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_matrix(r)
     }
 }
 
+impl cboragen_runtime::Encode for Matrix {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_matrix(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Matrix {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_matrix(r)
+    }
+
+    fn default_value() -> Self {
+        Matrix { rows: Vec::new() }
+    }
+}
+
 impl Sparse {
     pub fn encode(&self) -> Vec<u8> {
         let mut w = Writer::new();
@@ -709,7 +965,31 @@ impl Sparse {
         _dec_sparse(&mut r)
     }
 
+    /// Decode only the canonical encoding, with no trailing bytes.
+    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, cboragen_runtime::ReaderMode::Strict);
+        let v = _dec_sparse(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
     pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
         _dec_sparse(r)
     }
 }
+
+impl cboragen_runtime::Encode for Sparse {
+    fn encode_with(&self, w: &mut Writer) {
+        _enc_sparse(w, self);
+    }
+}
+
+impl cboragen_runtime::Decode for Sparse {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        _dec_sparse(r)
+    }
+
+    fn default_value() -> Self {
+        Sparse { first: 0, second: String::new(), third: false }
+    }
+}
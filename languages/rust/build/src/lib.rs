@@ -0,0 +1,355 @@
+//! Run the `cboragen-rs` code generator from a `build.rs` script.
+//!
+//! ```no_run
+//! // build.rs
+//! fn main() -> Result<(), cboragen_build::Error> {
+//!     cboragen_build::Config::new().schema("schema/api.cbg").compile()
+//! }
+//! ```
+//!
+//! Each schema is generated into `$OUT_DIR/<stem>.rs`, ready for
+//! `include!(concat!(env!("OUT_DIR"), "/api.rs"))`, so schemas must have
+//! distinct file names. Cargo is told to rerun the
+//! script when the schema or anything it transitively `@import`s changes.
+//!
+//! The generator is taken from [`Config::generator`], then the
+//! `CBORAGEN_RS_BIN` environment variable, then `cboragen-rs` on `PATH`.
+
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const GENERATOR_ENV: &str = "CBORAGEN_RS_BIN";
+
+/// Code generation settings for one or more schemas.
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    schemas: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    generator: Option<PathBuf>,
+    config: Option<PathBuf>,
+    args: Vec<OsString>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Config::default()
+    }
+
+    /// Add a schema to generate. Relative paths are resolved against the
+    /// package root, which is the working directory of `build.rs`.
+    pub fn schema(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.schemas.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Directory to write generated files to. Defaults to `OUT_DIR`.
+    pub fn out_dir(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.out_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Path to the `cboragen-rs` executable.
+    pub fn generator(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.generator = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Generator config file (derives, field visibility, runtime path, type mappings).
+    pub fn config(&mut self, path: impl AsRef<Path>) -> &mut Self {
+        self.config = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Emit `to_json`/`from_json` (needs the runtime's `json` feature).
+    pub fn json(&mut self) -> &mut Self {
+        self.arg("--json")
+    }
+
+    /// Derive serde `Serialize`/`Deserialize` (needs the runtime's `serde` feature).
+    pub fn serde(&mut self) -> &mut Self {
+        self.arg("--serde")
+    }
+
+    /// Emit schema type aliases as newtype structs.
+    pub fn newtypes(&mut self) -> &mut Self {
+        self.arg("--newtypes")
+    }
+
+    /// Emit a `{Name}Builder` for each struct.
+    pub fn builders(&mut self) -> &mut Self {
+        self.arg("--builders")
+    }
+
+    /// Mark generated types `#[non_exhaustive]` (implies [`Config::builders`]).
+    pub fn non_exhaustive(&mut self) -> &mut Self {
+        self.arg("--non-exhaustive")
+    }
+
+    /// Emit `decode_into` on structs.
+    pub fn decode_into(&mut self) -> &mut Self {
+        self.arg("--decode-into")
+    }
+
+    /// Emit a `{Name}Lazy` view for each top-level struct.
+    pub fn lazy(&mut self) -> &mut Self {
+        self.arg("--lazy")
+    }
+
+    /// Emit `digest()` on generated types (needs the runtime's `digest` feature).
+    pub fn digest(&mut self) -> &mut Self {
+        self.arg("--digest")
+    }
+
+    /// Map schema `f16` to `half::f16` (needs the runtime's `half` feature).
+    pub fn half(&mut self) -> &mut Self {
+        self.arg("--half")
+    }
+
+    /// Pass an extra command-line argument to the generator.
+    pub fn arg(&mut self, arg: impl Into<OsString>) -> &mut Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Generate every schema, stopping at the first failure.
+    pub fn compile(&self) -> Result<(), Error> {
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => std::env::var_os("OUT_DIR").map(PathBuf::from).ok_or(Error::NoOutDir)?,
+        };
+        let generator = match &self.generator {
+            Some(path) => path.clone(),
+            None => {
+                println!("cargo:rerun-if-env-changed={GENERATOR_ENV}");
+                std::env::var_os(GENERATOR_ENV).map_or_else(|| PathBuf::from("cboragen-rs"), PathBuf::from)
+            }
+        };
+
+        if let Some(config) = &self.config {
+            println!("cargo:rerun-if-changed={}", config.display());
+        }
+
+        let mut outputs: Vec<(&PathBuf, OsString)> = Vec::with_capacity(self.schemas.len());
+        for schema in &self.schemas {
+            let name = output_name(schema);
+            if let Some((first, _)) = outputs.iter().find(|(_, n)| *n == name) {
+                return Err(Error::DuplicateOutput { name, first: (*first).clone(), second: schema.clone() });
+            }
+            outputs.push((schema, name));
+        }
+
+        for (schema, name) in outputs {
+            for dep in schema_files(schema) {
+                println!("cargo:rerun-if-changed={}", dep.display());
+            }
+
+            let mut cmd = Command::new(&generator);
+            cmd.arg("--no-color");
+            if let Some(config) = &self.config {
+                cmd.arg("--config").arg(config);
+            }
+            let output = cmd
+                .args(&self.args)
+                .arg(schema)
+                .output()
+                .map_err(|source| Error::Spawn { generator: generator.clone(), source })?;
+            if !output.status.success() {
+                return Err(Error::Generate {
+                    schema: schema.clone(),
+                    diagnostics: String::from_utf8_lossy(&output.stderr).into_owned(),
+                });
+            }
+
+            let out_file = out_dir.join(name);
+            std::fs::write(&out_file, &output.stdout).map_err(|source| Error::Write { path: out_file, source })?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by [`Config::compile`].
+///
+/// `Debug` prints the same text as `Display`, so generator diagnostics stay
+/// readable when `build.rs` returns the error from `main`.
+pub enum Error {
+    /// No `out_dir` was set and `OUT_DIR` is not in the environment.
+    NoOutDir,
+    /// The generator could not be started.
+    Spawn { generator: PathBuf, source: std::io::Error },
+    /// Two schemas would be generated into the same file.
+    DuplicateOutput { name: OsString, first: PathBuf, second: PathBuf },
+    /// The generator rejected a schema; `diagnostics` is its error output.
+    Generate { schema: PathBuf, diagnostics: String },
+    /// A generated file could not be written.
+    Write { path: PathBuf, source: std::io::Error },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoOutDir => write!(f, "OUT_DIR is not set; call out_dir() outside of build.rs"),
+            Error::Spawn { generator, source } => {
+                write!(f, "cannot run '{}': {source} (set {GENERATOR_ENV} to the cboragen-rs path)", generator.display())
+            }
+            Error::DuplicateOutput { name, first, second } => write!(
+                f,
+                "'{}' and '{}' would both be generated as '{}'; rename one of them",
+                first.display(),
+                second.display(),
+                Path::new(name).display()
+            ),
+            Error::Generate { schema, diagnostics } => {
+                write!(f, "failed to generate '{}':\n{}", schema.display(), diagnostics.trim_end())
+            }
+            Error::Write { path, source } => write!(f, "cannot write '{}': {source}", path.display()),
+        }
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Spawn { source, .. } | Error::Write { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// `<stem>.rs`, the generated file name for a schema.
+fn output_name(schema: &Path) -> OsString {
+    let mut name = schema.file_stem().unwrap_or(schema.as_os_str()).to_os_string();
+    name.push(".rs");
+    name
+}
+
+/// The schema and every file it transitively imports, resolved the same way
+/// the generator resolves them (relative to the importing file). Files that
+/// cannot be read are still listed so that creating them triggers a rebuild;
+/// the generator reports them as errors.
+fn schema_files(schema: &Path) -> Vec<PathBuf> {
+    let mut files = vec![schema.to_path_buf()];
+    let mut seen: HashSet<PathBuf> = files.iter().cloned().collect();
+    let mut i = 0;
+    while i < files.len() {
+        let file = files[i].clone();
+        i += 1;
+        let Ok(source) = std::fs::read_to_string(&file) else { continue };
+        let dir = file.parent().unwrap_or(Path::new(""));
+        for import in import_paths(&source) {
+            let path = normalize(&dir.join(import));
+            if seen.insert(path.clone()) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// Paths named by `@import("...")` in a schema source, ignoring comments.
+fn import_paths(source: &str) -> Vec<&str> {
+    let mut paths = Vec::new();
+    for line in source.lines() {
+        let line = line.find("//").map_or(line, |i| &line[..i]);
+        let mut rest = line;
+        while let Some(i) = rest.find("@import") {
+            rest = rest[i + "@import".len()..].trim_start();
+            let Some(arg) = rest.strip_prefix('(').map(str::trim_start) else { continue };
+            let Some(arg) = arg.strip_prefix('"') else { continue };
+            let Some(end) = arg.find('"') else { break };
+            paths.push(&arg[..end]);
+            rest = &arg[end + 1..];
+        }
+    }
+    paths
+}
+
+/// Drop `.` components and fold `..` into its parent, without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    use std::path::Component;
+    let mut out = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => {
+                out.pop();
+            }
+            _ => out.push(comp),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_imports() {
+        let source = r#"
+common = @import("common/types.cbg")
+// old = @import("old.cbg")
+ext=@import( "../ext.cbg" ) // trailing comment
+User = struct {
+    0 status: common.Status
+}
+"#;
+        assert_eq!(import_paths(source), ["common/types.cbg", "../ext.cbg"]);
+    }
+
+    #[test]
+    fn collects_transitive_imports() {
+        let dir = std::env::temp_dir().join(format!("cboragen-build-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        std::fs::write(dir.join("api.cbg"), "common = @import(\"common/types.cbg\")\n").unwrap();
+        std::fs::write(
+            dir.join("common/types.cbg"),
+            "base = @import(\"../base.cbg\")\nmissing = @import(\"gone.cbg\")\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("base.cbg"), "api = @import(\"api.cbg\")\n").unwrap();
+
+        let files = schema_files(&dir.join("api.cbg"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            files,
+            [
+                dir.join("api.cbg"),
+                dir.join("common/types.cbg"),
+                dir.join("base.cbg"),
+                dir.join("common/gone.cbg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_missing_generator() {
+        let err = Config::new()
+            .schema("api.cbg")
+            .out_dir(std::env::temp_dir())
+            .generator("/nonexistent/cboragen-rs")
+            .compile()
+            .unwrap_err();
+        assert!(matches!(err, Error::Spawn { .. }));
+    }
+
+    #[test]
+    fn rejects_duplicate_output_names() {
+        let err = Config::new()
+            .schema("api/v1/common.cbg")
+            .schema("api/v2/common.cbg")
+            .out_dir(std::env::temp_dir())
+            .generator("/nonexistent/cboragen-rs")
+            .compile()
+            .unwrap_err();
+        assert!(matches!(&err, Error::DuplicateOutput { name, .. } if name == "common.rs"), "{err}");
+    }
+}
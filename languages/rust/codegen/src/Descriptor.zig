@@ -0,0 +1,209 @@
+//! Schema descriptor emitter.
+//!
+//! Serializes a parsed schema and its resolved imports into the CBOR
+//! descriptor format read by `cboragen_runtime::descriptor`. The descriptor
+//! is itself cboragen-encoded; its schema is documented in SPEC.md under
+//! "Schema Descriptors".
+//!
+//! Definitions from imported schemas are emitted as `namespace.Name`, and
+//! unqualified references inside them are qualified with the same namespace,
+//! so every named reference in the output resolves within the descriptor.
+
+const std = @import("std");
+const parser = @import("parser");
+const Ast = parser.Ast;
+
+const Descriptor = @This();
+
+writer: std.io.AnyWriter,
+schema: Ast.Schema,
+
+/// Namespace → Schema for imported schemas.
+imports: std.StringHashMap(Ast.Schema),
+
+const Error = std.io.AnyWriter.Error;
+
+// Major types used by the descriptor encoding.
+const maj_uint: u8 = 0;
+const maj_text: u8 = 3;
+const maj_array: u8 = 4;
+const maj_tag: u8 = 6;
+
+pub fn init(
+    writer: std.io.AnyWriter,
+    schema: Ast.Schema,
+    imports: std.StringHashMap(Ast.Schema),
+) Descriptor {
+    return .{
+        .writer = writer,
+        .schema = schema,
+        .imports = imports,
+    };
+}
+
+pub fn emit(self: *Descriptor) Error!void {
+    var count: usize = self.schema.definitions.len;
+    var count_it = self.imports.iterator();
+    while (count_it.next()) |entry| {
+        count += entry.value_ptr.definitions.len;
+    }
+
+    // Schema = struct { 0 types: []TypeDef }
+    try self.writeHead(maj_array, 1);
+    try self.writeHead(maj_array, count);
+    var import_it = self.imports.iterator();
+    while (import_it.next()) |entry| {
+        for (entry.value_ptr.definitions) |def| {
+            try self.emitTypeDef(entry.key_ptr.*, def);
+        }
+    }
+    for (self.schema.definitions) |def| {
+        try self.emitTypeDef(null, def);
+    }
+}
+
+/// TypeDef = struct { 0 name: string, 1 ty: Type }
+fn emitTypeDef(self: *Descriptor, namespace: ?[]const u8, def: Ast.TypeDef) Error!void {
+    try self.writeHead(maj_array, 2);
+    try self.writeName(namespace, def.name);
+    try self.emitType(namespace, def.ty);
+}
+
+/// Type = union { 0 boolean, 1 text, 2 integer: IntKind, ... }
+fn emitType(self: *Descriptor, namespace: ?[]const u8, ty: Ast.TypeExpr) Error!void {
+    switch (ty) {
+        .bool => try self.writeHead(maj_uint, 0),
+        .string => try self.writeHead(maj_uint, 1),
+        .int => |i| {
+            try self.writeHead(maj_tag, 2);
+            const kind: u64 = switch (i.kind) {
+                .u8 => 0,
+                .u16 => 1,
+                .u32 => 2,
+                .u64 => 3,
+                .i8 => 4,
+                .i16 => 5,
+                .i32 => 6,
+                .i64 => 7,
+                .uvar => 8,
+                .ivar => 9,
+            };
+            try self.writeHead(maj_uint, kind);
+        },
+        .float => |f| {
+            try self.writeHead(maj_tag, 3);
+            const kind: u64 = switch (f.kind) {
+                .f16 => 0,
+                .f32 => 1,
+                .f64 => 2,
+            };
+            try self.writeHead(maj_uint, kind);
+        },
+        .struct_ => |s| {
+            try self.writeHead(maj_tag, 4);
+            try self.writeHead(maj_array, s.fields.len);
+            for (s.fields) |field| {
+                // Field = struct { 0 rank: uvarint, 1 name: string, 2 ty: Type }
+                try self.writeHead(maj_array, 3);
+                try self.writeHead(maj_uint, field.rank);
+                try self.writeString(field.name);
+                try self.emitType(namespace, field.ty);
+            }
+        },
+        .enum_ => |e| {
+            try self.writeHead(maj_tag, 5);
+            try self.writeHead(maj_array, e.variants.len);
+            for (e.variants) |v| {
+                // EnumVariant = struct { 0 tag: uvarint, 1 name: string }
+                try self.writeHead(maj_array, 2);
+                try self.writeHead(maj_uint, v.tag);
+                try self.writeString(v.name);
+            }
+        },
+        .union_ => |u| {
+            try self.writeHead(maj_tag, 6);
+            try self.writeHead(maj_array, u.variants.len);
+            for (u.variants) |v| {
+                // UnionVariant = struct { 0 tag: uvarint, 1 name: string, 2 payload: ?Type }
+                try self.writeHead(maj_array, 3);
+                try self.writeHead(maj_uint, v.tag);
+                try self.writeString(v.name);
+                if (v.payload) |payload| {
+                    try self.writeHead(maj_tag, 1);
+                    try self.emitType(namespace, payload);
+                } else {
+                    try self.writeHead(maj_uint, 0);
+                }
+            }
+        },
+        .option => |o| {
+            try self.writeHead(maj_tag, 7);
+            try self.emitType(namespace, o.child);
+        },
+        .array => |a| switch (a.*) {
+            .variable => |v| {
+                try self.writeHead(maj_tag, 8);
+                try self.emitType(namespace, v.element);
+            },
+            .fixed => |f| {
+                // FixedArray = struct { 0 len: uvarint, 1 element: Type }
+                try self.writeHead(maj_tag, 9);
+                try self.writeHead(maj_array, 2);
+                try self.writeHead(maj_uint, f.len);
+                try self.emitType(namespace, f.element);
+            },
+            .external_len => |e| {
+                // ExternalArray = struct { 0 lenField: string, 1 element: Type }
+                try self.writeHead(maj_tag, 10);
+                try self.writeHead(maj_array, 2);
+                try self.writeString(e.len_field);
+                try self.emitType(namespace, e.element);
+            },
+        },
+        .named => |n| {
+            try self.writeHead(maj_tag, 11);
+            try self.writeName(namespace, n.name);
+        },
+        .qualified => |q| {
+            try self.writeHead(maj_tag, 11);
+            try self.writeName(q.namespace, q.name);
+        },
+    }
+}
+
+/// Write `name`, or `namespace.name` when a namespace is given.
+fn writeName(self: *Descriptor, namespace: ?[]const u8, name: []const u8) Error!void {
+    if (namespace) |ns| {
+        try self.writeHead(maj_text, ns.len + 1 + name.len);
+        try self.writer.writeAll(ns);
+        try self.writer.writeByte('.');
+        try self.writer.writeAll(name);
+    } else {
+        try self.writeString(name);
+    }
+}
+
+fn writeString(self: *Descriptor, s: []const u8) Error!void {
+    try self.writeHead(maj_text, s.len);
+    try self.writer.writeAll(s);
+}
+
+/// Write a CBOR initial byte with the minimal argument encoding.
+fn writeHead(self: *Descriptor, major: u8, n: u64) Error!void {
+    const base: u8 = major << 5;
+    if (n <= 23) {
+        try self.writer.writeByte(base | @as(u8, @intCast(n)));
+    } else if (n <= 0xff) {
+        try self.writer.writeByte(base | 24);
+        try self.writer.writeByte(@intCast(n));
+    } else if (n <= 0xffff) {
+        try self.writer.writeByte(base | 25);
+        try self.writer.writeInt(u16, @intCast(n), .big);
+    } else if (n <= 0xffff_ffff) {
+        try self.writer.writeByte(base | 26);
+        try self.writer.writeInt(u32, @intCast(n), .big);
+    } else {
+        try self.writer.writeByte(base | 27);
+        try self.writer.writeInt(u64, n, .big);
+    }
+}
@@ -1,17 +1,44 @@
 const std = @import("std");
 const parser = @import("parser");
 const Ast = parser.Ast;
+const Descriptor = @import("Descriptor.zig");
+const cfg = @import("config.zig");
 
 const RsGen = @This();
 
 writer: std.io.AnyWriter,
+/// Schema whose names unqualified references resolve against: the main
+/// schema, or an import while emitting (or looking into) its module.
 schema: Ast.Schema,
 arena: std.mem.Allocator,
+options: Options,
 loop_depth: u32,
 
 /// Namespace → Schema for imported schemas.
 imports: std.StringHashMap(Ast.Schema),
 
+/// Namespace of `schema` (null for the main schema).
+scope_ns: ?[]const u8,
+/// Module being emitted: null for the root, or an import's `pub mod`.
+module_ns: ?[]const u8,
+
+/// Namespace each inline type was declared in, keyed like the name maps.
+inline_owner: std.AutoHashMap(*const anyopaque, ?[]const u8),
+/// Top-level definition each inline type is nested in.
+inline_root: std.AutoHashMap(*const anyopaque, []const u8),
+
+/// The main schema (`schema` changes while emitting imports).
+main_schema: Ast.Schema,
+/// Top-level definition whose types or code are being emitted; references
+/// back into its own recursive cycle are boxed.
+current_root: ?[]const u8,
+/// Suffix for the decoder being emitted (`_body` behind a depth guard).
+dec_suffix: []const u8,
+/// Nonzero while emitting array elements, which need no box.
+box_blocked: u32,
+/// Named types whose default value is being emitted (`ns.Name` keys).
+default_stack: std.StringHashMap(void),
+
 /// Inline types discovered during pass 1, keyed by pointer identity.
 inline_struct_names: std.AutoHashMap(*const Ast.StructDef, []const u8),
 inline_enum_names: std.AutoHashMap(*const Ast.EnumDef, []const u8),
@@ -26,18 +53,62 @@ const InlineStruct = struct { name: []const u8, def: *const Ast.StructDef };
 const InlineEnum = struct { name: []const u8, def: *const Ast.EnumDef };
 const InlineUnion = struct { name: []const u8, def: *const Ast.UnionDef };
 
+pub const Options = struct {
+    /// Embed the schema descriptor and emit `to_json`/`from_json` on
+    /// top-level types (requires the runtime's `json` feature).
+    json: bool = false,
+    /// Derive serde `Serialize`/`Deserialize` on generated types, using the
+    /// schema's names (requires the runtime's `serde` feature for `[]u8`).
+    serde: bool = false,
+    /// Emit type aliases as `#[repr(transparent)]` newtype structs instead
+    /// of `pub type` aliases.
+    newtypes: bool = false,
+    /// Emit a `{Name}Builder` for each struct, with chainable setters and a
+    /// `build()` that validates array lengths.
+    builders: bool = false,
+    /// Mark structs, enums and unions `#[non_exhaustive]` so schema additions
+    /// are semver-compatible. Implies `builders`, since other crates can no
+    /// longer use struct literals.
+    non_exhaustive: bool = false,
+    /// Emit `decode_into` on structs, decoding over an existing value to
+    /// reuse its allocations.
+    decode_into: bool = false,
+    /// Emit a `{Name}Lazy<'a>` view for each top-level struct that decodes
+    /// fields on demand, with element iterators for array fields.
+    lazy: bool = false,
+    /// Emit `digest()`, the SHA-256 of the deterministic encoding, on each
+    /// struct, enum and union (requires the runtime's `digest` feature).
+    digest: bool = false,
+    /// Map schema `f16` to `half::f16` instead of `f32` (requires the
+    /// runtime's `half` feature).
+    half: bool = false,
+    /// Settings from the `--config` file.
+    config: cfg.Config = .{},
+};
+
 pub fn init(
     writer: std.io.AnyWriter,
     schema: Ast.Schema,
     imports: std.StringHashMap(Ast.Schema),
     arena: std.mem.Allocator,
+    options: Options,
 ) RsGen {
     return .{
         .writer = writer,
         .schema = schema,
         .arena = arena,
+        .options = options,
         .loop_depth = 0,
         .imports = imports,
+        .scope_ns = null,
+        .module_ns = null,
+        .inline_owner = std.AutoHashMap(*const anyopaque, ?[]const u8).init(arena),
+        .inline_root = std.AutoHashMap(*const anyopaque, []const u8).init(arena),
+        .main_schema = schema,
+        .current_root = null,
+        .dec_suffix = "",
+        .box_blocked = 0,
+        .default_stack = std.StringHashMap(void).init(arena),
         .inline_struct_names = std.AutoHashMap(*const Ast.StructDef, []const u8).init(arena),
         .inline_enum_names = std.AutoHashMap(*const Ast.EnumDef, []const u8).init(arena),
         .inline_union_names = std.AutoHashMap(*const Ast.UnionDef, []const u8).init(arena),
@@ -53,110 +124,172 @@ pub fn generate(self: *RsGen) Error!void {
     // Pass 1: collect inline types — imported schemas first, then main
     var import_it = self.imports.iterator();
     while (import_it.next()) |entry| {
+        self.scope_ns = entry.key_ptr.*;
         for (entry.value_ptr.definitions) |def| {
+            self.current_root = def.name;
             try self.collectInlineTypes(def.name, def.ty);
         }
     }
+    self.scope_ns = null;
     for (self.schema.definitions) |def| {
+        self.current_root = def.name;
         try self.collectInlineTypes(def.name, def.ty);
     }
+    self.current_root = null;
 
-    // Pass 2: emit Rust
+    // Pass 2: emit the main schema at the root, then one module per import
     try self.writer.writeAll("// Generated by cboragen — do not edit\n\n");
-    try self.writer.writeAll("use cboragen_runtime::{Writer, Reader, DecodeError};\n");
+    try self.emitModuleBody();
+
+    var namespaces: std.ArrayList([]const u8) = .{};
+    var key_it = self.imports.keyIterator();
+    while (key_it.next()) |key| {
+        try namespaces.append(self.arena, key.*);
+    }
+    std.mem.sort([]const u8, namespaces.items, {}, lessThanStr);
+
+    const root_writer = self.writer;
+    const root_schema = self.schema;
+    for (namespaces.items) |ns| {
+        var buf: std.ArrayList(u8) = .{};
+        const buf_writer = buf.writer(self.arena);
+        self.writer = buf_writer.any();
+        self.schema = self.imports.get(ns).?;
+        self.scope_ns = ns;
+        self.module_ns = ns;
+        try self.emitModuleBody();
+        self.writer = root_writer;
+        self.schema = root_schema;
+        self.scope_ns = null;
+        self.module_ns = null;
+
+        try self.writer.print("\npub mod {s} {{\n", .{try rustSafeIdent(self.arena, ns)});
+        var lines = std.mem.splitScalar(u8, std.mem.trimRight(u8, buf.items, "\n"), '\n');
+        while (lines.next()) |line| {
+            if (line.len > 0) try self.writer.print("    {s}", .{line});
+            try self.writer.writeAll("\n");
+        }
+        try self.writer.writeAll("}\n");
+    }
+}
+
+/// Emit the types, codecs and impls of the module `module_ns` for `schema`.
+fn emitModuleBody(self: *RsGen) Error!void {
+    const rt = self.options.config.runtime_crate;
+    try self.writer.print("use {s}::{{Writer, Reader, DecodeError}};\n", .{rt});
+    if (self.options.json) {
+        try self.writer.print("use {s}::json::{{JsonError, JsonOptions}};\n", .{rt});
+    }
 
     // Types
     try self.writer.writeAll("\n// === Types ===\n");
     // Inline types first
     for (self.inline_structs.items) |entry| {
-        try self.emitStructType(entry.name, entry.def, null);
+        if (self.enterInline(entry.def)) try self.emitStructType(entry.name, entry.def, null);
     }
     for (self.inline_enums.items) |entry| {
-        try self.emitEnumType(entry.name, entry.def, null);
+        if (self.enterInline(entry.def)) try self.emitEnumType(entry.name, entry.def, null);
     }
     for (self.inline_unions.items) |entry| {
-        try self.emitUnionType(entry.name, entry.def, null);
+        if (self.enterInline(entry.def)) try self.emitUnionType(entry.name, entry.def, null);
     }
-    // Imported top-level types
-    var import_it2 = self.imports.iterator();
-    while (import_it2.next()) |entry| {
-        for (entry.value_ptr.definitions) |def| {
-            try self.emitTypeDef(def);
-        }
-    }
-    // Main schema top-level types
+    // Top-level types
     for (self.schema.definitions) |def| {
+        self.current_root = def.name;
         try self.emitTypeDef(def);
     }
 
     // Encoders
     try self.writer.writeAll("\n// === Encoders ===\n");
     for (self.inline_structs.items) |entry| {
-        try self.emitStructEncoder(entry.name, entry.def);
+        if (self.enterInline(entry.def)) try self.emitStructEncoder(entry.name, entry.def);
     }
     for (self.inline_enums.items) |entry| {
-        try self.emitEnumEncoder(entry.name, entry.def);
+        if (self.enterInline(entry.def)) try self.emitEnumEncoder(entry.name, entry.def);
     }
     for (self.inline_unions.items) |entry| {
-        try self.emitUnionEncoder(entry.name, entry.def);
-    }
-    var import_it3 = self.imports.iterator();
-    while (import_it3.next()) |entry| {
-        for (entry.value_ptr.definitions) |def| {
-            try self.emitEncoderForDef(def);
-        }
+        if (self.enterInline(entry.def)) try self.emitUnionEncoder(entry.name, entry.def);
     }
     for (self.schema.definitions) |def| {
+        self.current_root = def.name;
         try self.emitEncoderForDef(def);
     }
 
     // Decoders
     try self.writer.writeAll("\n// === Decoders ===\n");
     for (self.inline_structs.items) |entry| {
-        try self.emitStructDecoder(entry.name, entry.def);
+        if (self.enterInline(entry.def)) try self.emitStructDecoder(entry.name, entry.def);
     }
     for (self.inline_enums.items) |entry| {
-        try self.emitEnumDecoder(entry.name, entry.def);
+        if (self.enterInline(entry.def)) try self.emitEnumDecoder(entry.name, entry.def);
     }
     for (self.inline_unions.items) |entry| {
-        try self.emitUnionDecoder(entry.name, entry.def);
-    }
-    var import_it4 = self.imports.iterator();
-    while (import_it4.next()) |entry| {
-        for (entry.value_ptr.definitions) |def| {
-            try self.emitDecoderForDef(def);
-        }
+        if (self.enterInline(entry.def)) try self.emitUnionDecoder(entry.name, entry.def);
     }
     for (self.schema.definitions) |def| {
+        self.current_root = def.name;
         try self.emitDecoderForDef(def);
     }
 
     // Impl blocks (encode/encode_with/decode/decode_with on each struct/enum/union)
     try self.writer.writeAll("\n// === Impl ===\n");
     for (self.inline_structs.items) |entry| {
-        try self.emitImplBlock(entry.name);
+        if (self.enterInline(entry.def)) try self.emitImplBlock(entry.name, .{ .struct_ = entry.def });
     }
     for (self.inline_enums.items) |entry| {
-        try self.emitImplBlock(entry.name);
+        if (self.enterInline(entry.def)) try self.emitImplBlock(entry.name, .{ .enum_ = entry.def });
     }
     for (self.inline_unions.items) |entry| {
-        try self.emitImplBlock(entry.name);
-    }
-    var import_it5 = self.imports.iterator();
-    while (import_it5.next()) |entry| {
-        for (entry.value_ptr.definitions) |def| {
-            switch (def.ty) {
-                .struct_, .enum_, .union_ => try self.emitImplBlock(def.name),
-                else => {},
-            }
-        }
+        if (self.enterInline(entry.def)) try self.emitImplBlock(entry.name, .{ .union_ = entry.def });
     }
     for (self.schema.definitions) |def| {
         switch (def.ty) {
-            .struct_, .enum_, .union_ => try self.emitImplBlock(def.name),
-            else => {},
+            .struct_, .enum_, .union_ => try self.emitImplBlock(def.name, null),
+            else => if (self.options.newtypes) try self.emitImplBlock(def.name, null),
+        }
+    }
+
+    if (self.options.decode_into) {
+        try self.writer.writeAll("\n// === Decode-into ===\n");
+        for (self.inline_structs.items) |entry| {
+            if (self.enterInline(entry.def)) try self.emitStructDecodeInto(entry.name, entry.def);
+        }
+        for (self.schema.definitions) |def| {
+            self.current_root = def.name;
+            try self.emitDecodeIntoForDef(def);
+        }
+        for (self.inline_structs.items) |entry| {
+            if (self.ownedHere(entry.def)) try self.emitDecodeIntoImpl(entry.name);
+        }
+        for (self.schema.definitions) |def| {
+            if (def.ty == .struct_) try self.emitDecodeIntoImpl(def.name);
+        }
+    }
+
+    if (self.options.lazy) {
+        try self.writer.writeAll("\n// === Lazy ===\n");
+        for (self.schema.definitions) |def| {
+            self.current_root = def.name;
+            if (def.ty == .struct_) try self.emitLazy(def.name, def.ty.struct_);
+        }
+    }
+
+    if (self.options.builders or self.options.non_exhaustive) {
+        try self.writer.writeAll("\n// === Builders ===\n");
+        for (self.inline_structs.items) |entry| {
+            if (self.enterInline(entry.def)) try self.emitBuilder(entry.name, entry.def);
+        }
+        for (self.schema.definitions) |def| {
+            self.current_root = def.name;
+            if (def.ty == .struct_) try self.emitBuilder(def.name, def.ty.struct_);
         }
     }
+
+    self.current_root = null;
+
+    if (self.options.json) {
+        try self.emitJsonSection();
+    }
 }
 
 // =========================================================================
@@ -194,6 +327,8 @@ fn collectInlineTypesField(self: *RsGen, parent_name: []const u8, field: Ast.Fie
             const fname = try self.sanitizeFieldName(field.name);
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, fname }));
             try self.inline_struct_names.put(s, name);
+            try self.inline_owner.put(s, self.scope_ns);
+            try self.inline_root.put(s, self.current_root.?);
             try self.inline_structs.append(self.arena, .{ .name = name, .def = s });
             for (s.fields) |f| {
                 try self.collectInlineTypesField(name, f);
@@ -203,12 +338,16 @@ fn collectInlineTypesField(self: *RsGen, parent_name: []const u8, field: Ast.Fie
             const fname = try self.sanitizeFieldName(field.name);
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, fname }));
             try self.inline_enum_names.put(e, name);
+            try self.inline_owner.put(e, self.scope_ns);
+            try self.inline_root.put(e, self.current_root.?);
             try self.inline_enums.append(self.arena, .{ .name = name, .def = e });
         },
         .union_ => |u| {
             const fname = try self.sanitizeFieldName(field.name);
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, fname }));
             try self.inline_union_names.put(u, name);
+            try self.inline_owner.put(u, self.scope_ns);
+            try self.inline_root.put(u, self.current_root.?);
             try self.inline_unions.append(self.arena, .{ .name = name, .def = u });
             for (u.variants) |v| {
                 if (v.payload) |payload| {
@@ -233,6 +372,8 @@ fn collectInlineTypesElement(self: *RsGen, parent_name: []const u8, field_name:
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, fn_name }));
             if (!self.inline_struct_names.contains(s)) {
                 try self.inline_struct_names.put(s, name);
+                try self.inline_owner.put(s, self.scope_ns);
+                try self.inline_root.put(s, self.current_root.?);
                 try self.inline_structs.append(self.arena, .{ .name = name, .def = s });
                 for (s.fields) |f| {
                     try self.collectInlineTypesField(name, f);
@@ -244,6 +385,8 @@ fn collectInlineTypesElement(self: *RsGen, parent_name: []const u8, field_name:
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, fn_name }));
             if (!self.inline_enum_names.contains(e)) {
                 try self.inline_enum_names.put(e, name);
+                try self.inline_owner.put(e, self.scope_ns);
+                try self.inline_root.put(e, self.current_root.?);
                 try self.inline_enums.append(self.arena, .{ .name = name, .def = e });
             }
         },
@@ -252,6 +395,8 @@ fn collectInlineTypesElement(self: *RsGen, parent_name: []const u8, field_name:
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, fn_name }));
             if (!self.inline_union_names.contains(u)) {
                 try self.inline_union_names.put(u, name);
+                try self.inline_owner.put(u, self.scope_ns);
+                try self.inline_root.put(u, self.current_root.?);
                 try self.inline_unions.append(self.arena, .{ .name = name, .def = u });
                 for (u.variants) |v| {
                     if (v.payload) |payload| {
@@ -282,6 +427,8 @@ fn collectInlineTypesVariant(self: *RsGen, parent_name: []const u8, variant_name
         .struct_ => |s| {
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, variant_name }));
             try self.inline_struct_names.put(s, name);
+            try self.inline_owner.put(s, self.scope_ns);
+            try self.inline_root.put(s, self.current_root.?);
             try self.inline_structs.append(self.arena, .{ .name = name, .def = s });
             for (s.fields) |f| {
                 try self.collectInlineTypesField(name, f);
@@ -290,11 +437,15 @@ fn collectInlineTypesVariant(self: *RsGen, parent_name: []const u8, variant_name
         .enum_ => |e| {
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, variant_name }));
             try self.inline_enum_names.put(e, name);
+            try self.inline_owner.put(e, self.scope_ns);
+            try self.inline_root.put(e, self.current_root.?);
             try self.inline_enums.append(self.arena, .{ .name = name, .def = e });
         },
         .union_ => |u| {
             const name = try toPascalCase(self.arena, try std.fmt.allocPrint(self.arena, "{s}_{s}", .{ parent_name, variant_name }));
             try self.inline_union_names.put(u, name);
+            try self.inline_owner.put(u, self.scope_ns);
+            try self.inline_root.put(u, self.current_root.?);
             try self.inline_unions.append(self.arena, .{ .name = name, .def = u });
             for (u.variants) |v| {
                 if (v.payload) |payload| {
@@ -322,50 +473,106 @@ fn emitTypeDef(self: *RsGen, def: Ast.TypeDef) Error!void {
         .enum_ => |e| try self.emitEnumType(def.name, e, def.doc),
         .union_ => |u| try self.emitUnionType(def.name, u, def.doc),
         else => {
+            if (self.options.newtypes) return self.emitNewtype(def);
+            try self.writer.writeAll("\n");
             try self.emitDoc(def.doc);
-            try self.writer.print("\npub type {s} = ", .{def.name});
+            try self.writer.print("pub type {s} = ", .{def.name});
             try self.emitTypeRef(def.ty);
             try self.writer.writeAll(";\n");
         },
     }
 }
 
+/// Emit an alias as a newtype with conversions to and from its inner type.
+fn emitNewtype(self: *RsGen, def: Ast.TypeDef) Error!void {
+    var base: std.ArrayList(u8) = .{};
+    try base.appendSlice(self.arena, "Debug, Clone");
+    if (isCopyType(def.ty)) try base.appendSlice(self.arena, ", Copy");
+    try base.appendSlice(self.arena, ", PartialEq");
+    switch (def.ty) {
+        .bool, .int, .string => try base.appendSlice(self.arena, ", Eq, Hash"),
+        else => {},
+    }
+    if (try self.typeHasDefault(def.ty)) try base.appendSlice(self.arena, ", Default");
+
+    try self.writer.writeAll("\n");
+    try self.emitDoc(def.doc);
+    try self.emitTypeAttrs(def.name, base.items);
+    try self.writer.print("#[repr(transparent)]\npub struct {s}(", .{def.name});
+    if (try self.serdeAttrText(null, null, def.ty)) |attrs| {
+        try self.writer.print("#[serde({s})] ", .{attrs});
+    }
+    const vis = self.options.config.field_visibility;
+    if (vis.len > 0) try self.writer.print("{s} ", .{vis});
+    try self.emitTypeRef(def.ty);
+    try self.writer.writeAll(");\n");
+
+    const name = def.name;
+    const ty = try self.renderTypeRef(def.ty);
+
+    try self.writer.print("\nimpl From<{s}> for {s} {{\n    fn from(v: {s}) -> Self {{\n        {s}(v)\n    }}\n}}\n", .{ ty, name, ty, name });
+    try self.writer.print("\nimpl From<{s}> for {s} {{\n    fn from(v: {s}) -> Self {{\n        v.0\n    }}\n}}\n", .{ name, ty, name });
+    try self.writer.print("\nimpl std::ops::Deref for {s} {{\n    type Target = {s};\n\n    fn deref(&self) -> &{s} {{\n        &self.0\n    }}\n}}\n", .{ name, ty, ty });
+}
+
 fn emitStructType(self: *RsGen, name: []const u8, def: *const Ast.StructDef, doc: ?[]const u8) Error!void {
+    try self.writer.writeAll("\n");
     try self.emitDoc(doc);
-    if (self.canDeriveDefault(def)) {
-        try self.writer.print("\n#[derive(Debug, Clone, PartialEq, Default)]\npub struct {s} {{\n", .{name});
+    if (try self.canDeriveDefault(self.module_ns, name, def)) {
+        try self.emitTypeAttrs(name, "Debug, Clone, PartialEq, Default");
     } else {
-        try self.writer.print("\n#[derive(Debug, Clone, PartialEq)]\npub struct {s} {{\n", .{name});
+        try self.emitTypeAttrs(name, "Debug, Clone, PartialEq");
     }
+    try self.emitNonExhaustive();
+    try self.writer.print("pub struct {s} {{\n", .{name});
+    const vis = self.options.config.field_visibility;
+    const vis_sep = if (vis.len > 0) " " else "";
     for (def.fields) |field| {
         try self.emitDoc(field.doc);
         const fname = try self.toSnakeCase(field.name);
         const safe_name = try rustSafeIdent(self.arena, fname);
-        try self.writer.print("    pub {s}: ", .{safe_name});
-        try self.emitTypeRef(field.ty);
+        const mapped = try self.fieldMapping(self.module_ns, name, field.name);
+        const encoded = try self.fieldEncoded(self.module_ns, name, field.name);
+        try self.emitSerdeAttrs(safe_name, field.name, if (mapped == null and !encoded) field.ty else null);
+        try self.writer.print("    {s}{s}{s}: ", .{ vis, vis_sep, safe_name });
+        if (mapped) |rust_type| {
+            try self.writer.writeAll(rust_type);
+        } else if (encoded) {
+            try self.emitEncodedType(field.ty);
+        } else {
+            try self.emitTypeRef(field.ty);
+        }
         try self.writer.writeAll(",\n");
     }
     try self.writer.writeAll("}\n");
 }
 
 fn emitEnumType(self: *RsGen, name: []const u8, def: *const Ast.EnumDef, doc: ?[]const u8) Error!void {
+    try self.writer.writeAll("\n");
     try self.emitDoc(doc);
     // Check if all variants are Copy-able (unit enum)
-    try self.writer.print("\n#[derive(Debug, Clone, Copy, PartialEq, Eq)]\npub enum {s} {{\n", .{name});
+    try self.emitTypeAttrs(name, "Debug, Clone, Copy, PartialEq, Eq");
+    try self.emitNonExhaustive();
+    try self.writer.print("pub enum {s} {{\n", .{name});
     for (def.variants) |v| {
         try self.emitDoc(v.doc);
         const vname = try toPascalCase(self.arena, v.name);
+        try self.emitSerdeAttrs(vname, v.name, null);
         try self.writer.print("    {s},\n", .{vname});
     }
     try self.writer.writeAll("}\n");
 }
 
 fn emitUnionType(self: *RsGen, name: []const u8, def: *const Ast.UnionDef, doc: ?[]const u8) Error!void {
+    try self.writer.writeAll("\n");
     try self.emitDoc(doc);
-    try self.writer.print("\n#[derive(Debug, Clone, PartialEq)]\npub enum {s} {{\n", .{name});
+    try self.emitTypeAttrs(name, "Debug, Clone, PartialEq");
+    try self.emitNonExhaustive();
+    try self.writer.print("pub enum {s} {{\n", .{name});
     for (def.variants) |v| {
         try self.emitDoc(v.doc);
         const vname = try toPascalCase(self.arena, v.name);
+        try self.emitSerdeAttrs(vname, v.name, v.payload);
         if (v.payload) |payload| {
             try self.writer.print("    {s}(", .{vname});
             try self.emitTypeRef(payload);
@@ -397,6 +604,10 @@ fn emitTypeRef(self: *RsGen, ty: Ast.TypeExpr) Error!void {
             try self.writer.writeAll(name);
         },
         .float => |f| {
+            if (f.kind == .f16 and self.options.half) {
+                try self.writer.print("{s}::half::f16", .{self.options.config.runtime_crate});
+                return;
+            }
             const name = switch (f.kind) {
                 .f16 => "f32",
                 .f32 => "f32",
@@ -406,21 +617,21 @@ fn emitTypeRef(self: *RsGen, ty: Ast.TypeExpr) Error!void {
         },
         .struct_ => |s| {
             if (self.inline_struct_names.get(s)) |name| {
-                try self.writer.writeAll(name);
+                try self.writer.print("{s}{s}", .{ try self.inlinePath(s), name });
             } else {
                 try self.writer.writeAll("() /* inline struct */");
             }
         },
         .enum_ => |e| {
             if (self.inline_enum_names.get(e)) |name| {
-                try self.writer.writeAll(name);
+                try self.writer.print("{s}{s}", .{ try self.inlinePath(e), name });
             } else {
                 try self.writer.writeAll("() /* inline enum */");
             }
         },
         .union_ => |u| {
             if (self.inline_union_names.get(u)) |name| {
-                try self.writer.writeAll(name);
+                try self.writer.print("{s}{s}", .{ try self.inlinePath(u), name });
             } else {
                 try self.writer.writeAll("() /* inline union */");
             }
@@ -434,17 +645,31 @@ fn emitTypeRef(self: *RsGen, ty: Ast.TypeExpr) Error!void {
             if (isU8Array(a.getElement())) {
                 try self.writer.writeAll("Vec<u8>");
             } else {
+                self.box_blocked += 1;
+                defer self.box_blocked -= 1;
                 try self.writer.writeAll("Vec<");
                 try self.emitTypeRef(a.getElement());
                 try self.writer.writeAll(">");
             }
         },
         .named => |n| {
-            try self.writer.writeAll(n.name);
+            if (try self.typeMapping(self.scope_ns, n.name)) |rust_type| {
+                try self.writer.writeAll(rust_type);
+            } else if (try self.needsBox(self.scope_ns, n.name)) {
+                try self.writer.print("Box<{s}{s}>", .{ try self.modulePath(self.scope_ns), n.name });
+            } else {
+                try self.writer.print("{s}{s}", .{ try self.modulePath(self.scope_ns), n.name });
+            }
         },
         .qualified => |q| {
             if (self.resolveQualified(q.namespace, q.name)) |_| {
-                try self.writer.writeAll(q.name);
+                if (try self.typeMapping(q.namespace, q.name)) |rust_type| {
+                    try self.writer.writeAll(rust_type);
+                } else if (try self.needsBox(q.namespace, q.name)) {
+                    try self.writer.print("Box<{s}{s}>", .{ try self.modulePath(q.namespace), q.name });
+                } else {
+                    try self.writer.print("{s}{s}", .{ try self.modulePath(q.namespace), q.name });
+                }
             } else {
                 try self.writer.print("() /* TODO: unresolved {s}.{s} */", .{ q.namespace, q.name });
             }
@@ -452,6 +677,158 @@ fn emitTypeRef(self: *RsGen, ty: Ast.TypeExpr) Error!void {
     }
 }
 
+/// The Rust type for `ty`, as a string.
+fn renderTypeRef(self: *RsGen, ty: Ast.TypeExpr) Error![]const u8 {
+    var buf: std.ArrayList(u8) = .{};
+    const buf_writer = buf.writer(self.arena);
+    const out = self.writer;
+    self.writer = buf_writer.any();
+    defer self.writer = out;
+    try self.emitTypeRef(ty);
+    return buf.items;
+}
+
+/// Emit `#[derive(...)]` (the generator's own derives, serde's, then any
+/// configured ones not already present) and configured attributes.
+fn emitTypeAttrs(self: *RsGen, name: []const u8, base: []const u8) Error!void {
+    const config = self.options.config;
+    const type_config = config.typeConfig(try self.configName(self.module_ns, name));
+
+    var derives: std.ArrayList(u8) = .{};
+    try derives.appendSlice(self.arena, base);
+    try derives.appendSlice(self.arena, self.serdeDerives());
+    const extra = [_][]const []const u8{ config.derives, if (type_config) |t| t.derives else &.{} };
+    for (extra) |list| {
+        for (list) |d| {
+            if (hasDerive(derives.items, d)) continue;
+            try derives.appendSlice(self.arena, ", ");
+            try derives.appendSlice(self.arena, d);
+        }
+    }
+    try self.writer.print("#[derive({s})]\n", .{derives.items});
+
+    for (config.attributes) |a| try self.writer.print("{s}\n", .{a});
+    if (type_config) |t| {
+        for (t.attributes) |a| try self.writer.print("{s}\n", .{a});
+    }
+}
+
+fn emitNonExhaustive(self: *RsGen) Error!void {
+    if (self.options.non_exhaustive) try self.writer.writeAll("#[non_exhaustive]\n");
+}
+
+fn hasDerive(list: []const u8, derive: []const u8) bool {
+    var it = std.mem.splitSequence(u8, list, ", ");
+    while (it.next()) |d| {
+        if (std.mem.eql(u8, d, derive)) return true;
+    }
+    return false;
+}
+
+/// Name a type or field is configured under: `Name`, or `ns.Name` in an import.
+fn configName(self: *RsGen, ns: ?[]const u8, name: []const u8) Error![]const u8 {
+    const n = ns orelse return name;
+    return std.fmt.allocPrint(self.arena, "{s}.{s}", .{ n, name });
+}
+
+/// The configured Rust type for a struct field, if it is mapped.
+fn fieldMapping(self: *RsGen, ns: ?[]const u8, type_name: []const u8, field_name: []const u8) Error!?[]const u8 {
+    if (self.options.config.mappings.len == 0) return null;
+    const key = try std.fmt.allocPrint(self.arena, "{s}.{s}", .{ try self.configName(ns, type_name), field_name });
+    const mapping = self.options.config.fieldMapping(key) orelse return null;
+    if (mapping.encoded) return null;
+    return mapping.rust_type;
+}
+
+/// Whether a struct field is configured to stay encoded, as `Encoded<T>`.
+fn fieldEncoded(self: *RsGen, ns: ?[]const u8, type_name: []const u8, field_name: []const u8) Error!bool {
+    if (self.options.config.mappings.len == 0) return false;
+    const key = try std.fmt.allocPrint(self.arena, "{s}.{s}", .{ try self.configName(ns, type_name), field_name });
+    const mapping = self.options.config.fieldMapping(key) orelse return false;
+    return mapping.encoded;
+}
+
+/// `Encoded<T>` for an encoded field. Named types are never boxed here,
+/// since the bytes already break any recursion.
+fn emitEncodedType(self: *RsGen, ty: Ast.TypeExpr) Error!void {
+    try self.writer.print("{s}::Encoded<", .{self.options.config.runtime_crate});
+    switch (ty) {
+        .named => |n| try self.writer.print("{s}{s}", .{ try self.modulePath(self.scope_ns), n.name }),
+        .qualified => |q| try self.writer.print("{s}{s}", .{ try self.modulePath(q.namespace), q.name }),
+        else => try self.emitTypeRef(ty),
+    }
+    try self.writer.writeAll(">");
+}
+
+/// Default for an encoded field: the encoding of the type's default.
+fn emitEncodedDefault(self: *RsGen, ty: Ast.TypeExpr) Error!void {
+    try self.writer.writeAll("<");
+    try self.emitEncodedType(ty);
+    try self.writer.print(" as {s}::Decode>::default_value()", .{self.options.config.runtime_crate});
+}
+
+/// The configured Rust type for references to a named type, if it is mapped.
+fn typeMapping(self: *RsGen, ns: ?[]const u8, name: []const u8) Error!?[]const u8 {
+    if (self.options.config.mappings.len == 0) return null;
+    const mapping = self.options.config.typeMapping(try self.configName(ns, name)) orelse return null;
+    return mapping.rust_type;
+}
+
+/// Default for a mapped type, from its `CbgConvert` impl.
+fn emitMappedDefault(self: *RsGen, rust_type: []const u8) Error!void {
+    try self.writer.print("<{s} as {s}::CbgConvert>::default_value()", .{ rust_type, self.options.config.runtime_crate });
+}
+
+/// Encode a mapped value through its wire type.
+fn emitMappedEncode(self: *RsGen, enc_fn: []const u8, access: []const u8, is_ref: bool) Error!void {
+    const amp = if (is_ref) "" else "&";
+    try self.writer.print("{s}(w, &{s}::CbgConvert::to_wire({s}{s}))", .{ enc_fn, self.options.config.runtime_crate, amp, access });
+}
+
+/// Decode a mapped value from its wire type.
+fn emitMappedDecode(self: *RsGen, rust_type: []const u8, dec_fn: []const u8) Error!void {
+    try self.writer.print("<{s} as {s}::CbgConvert>::from_wire({s}(r)?)?", .{ rust_type, self.options.config.runtime_crate, dec_fn });
+}
+
+fn serdeDerives(self: *RsGen) []const u8 {
+    return if (self.options.serde) ", serde::Serialize, serde::Deserialize" else "";
+}
+
+/// Emit `#[serde(...)]` for a field or variant: `rename` back to the schema
+/// name when the Rust identifier differs, and the runtime's byte helpers for
+/// `[]u8` (and `?[]u8`) so bytes aren't serialized as a list of numbers.
+fn emitSerdeAttrs(self: *RsGen, rust_name: []const u8, schema_name: []const u8, ty: ?Ast.TypeExpr) Error!void {
+    if (try self.serdeAttrText(rust_name, schema_name, ty)) |attrs| {
+        try self.writer.print("    #[serde({s})]\n", .{attrs});
+    }
+}
+
+/// The contents of a `#[serde(...)]` attribute, if one is needed.
+fn serdeAttrText(self: *RsGen, rust_name: ?[]const u8, schema_name: ?[]const u8, ty: ?Ast.TypeExpr) Error!?[]const u8 {
+    if (!self.options.serde) return null;
+
+    var attrs: std.ArrayList(u8) = .{};
+    if (rust_name != null and !std.mem.eql(u8, rust_name.?, schema_name.?)) {
+        try attrs.appendSlice(self.arena, try std.fmt.allocPrint(self.arena, "rename = \"{s}\"", .{schema_name}));
+    }
+    if (ty) |t| {
+        const helper: ?[]const u8 = switch (t) {
+            .array => |a| if (isU8Array(a.getElement())) "with = \"{s}::serde::bytes\"" else null,
+            .option => |o| switch (o.child) {
+                .array => |a| if (isU8Array(a.getElement())) "with = \"{s}::serde::option_bytes\", default" else null,
+                else => null,
+            },
+            else => null,
+        };
+        if (helper) |h| {
+            if (attrs.items.len > 0) try attrs.appendSlice(self.arena, ", ");
+            const rt = self.options.config.runtime_crate;
+            try attrs.appendSlice(self.arena, try std.mem.replaceOwned(u8, self.arena, h, "{s}", rt));
+        }
+    }
+    return if (attrs.items.len > 0) attrs.items else null;
+}
+
 fn emitDoc(self: *RsGen, doc: ?[]const u8) Error!void {
     const text = doc orelse return;
     if (std.mem.indexOfScalar(u8, text, '\n')) |_| {
@@ -476,8 +853,12 @@ fn emitEncoderForDef(self: *RsGen, def: Ast.TypeDef) Error!void {
         else => {
             // Type alias
             const fn_name = try toSnakeCaseAlloc(self.arena, def.name);
-            try self.writer.print("\nfn _enc_{s}(w: &mut Writer, v: &{s}) {{\n    ", .{ fn_name, def.name });
-            try self.emitEncodeExpr(def.ty, "v", true);
+            try self.writer.print("\n{s}fn _enc_{s}(w: &mut Writer, v: &{s}) {{\n    ", .{ self.helperVis(), fn_name, def.name });
+            if (self.options.newtypes) {
+                try self.emitEncodeExpr(def.ty, "v.0", false);
+            } else {
+                try self.emitEncodeExpr(def.ty, "v", true);
+            }
             try self.writer.writeAll(";\n}\n");
             try self.writer.print("\npub fn encode_{s}(value: &{s}) -> Vec<u8> {{\n", .{ fn_name, def.name });
             try self.writer.print("    let mut w = Writer::new();\n    _enc_{s}(&mut w, value);\n    w.finish()\n}}\n", .{fn_name});
@@ -487,7 +868,7 @@ fn emitEncoderForDef(self: *RsGen, def: Ast.TypeDef) Error!void {
 
 fn emitStructEncoder(self: *RsGen, name: []const u8, def: *const Ast.StructDef) Error!void {
     const fn_name = try toSnakeCaseAlloc(self.arena, name);
-    try self.writer.print("\nfn _enc_{s}(w: &mut Writer, v: &{s}) {{\n", .{ fn_name, name });
+    try self.writer.print("\n{s}fn _enc_{s}(w: &mut Writer, v: &{s}) {{\n", .{ self.helperVis(), fn_name, name });
     if (def.fields.len == 0) {
         try self.writer.writeAll("    w.write_array_header(0);\n");
     } else {
@@ -499,9 +880,20 @@ fn emitStructEncoder(self: *RsGen, name: []const u8, def: *const Ast.StructDef)
                 const fname = try self.toSnakeCase(field.name);
                 const safe_name = try rustSafeIdent(self.arena, fname);
                 const access = try std.fmt.allocPrint(self.arena, "v.{s}", .{safe_name});
-                try self.writer.writeAll("    ");
-                try self.emitEncodeExpr(field.ty, access, false);
-                try self.writer.writeAll(";\n");
+                if (try self.fieldMapping(self.module_ns, name, field.name) != null) {
+                    // Convert the mapped value back to its wire type first
+                    try self.writer.writeAll("    { let _m: ");
+                    try self.emitTypeRef(field.ty);
+                    try self.writer.print(" = {s}::CbgConvert::to_wire(&{s}); ", .{ self.options.config.runtime_crate, access });
+                    try self.emitEncodeExpr(field.ty, "_m", false);
+                    try self.writer.writeAll("; }\n");
+                } else if (try self.fieldEncoded(self.module_ns, name, field.name)) {
+                    try self.writer.print("    w.write_raw_item({s}.as_bytes());\n", .{access});
+                } else {
+                    try self.writer.writeAll("    ");
+                    try self.emitEncodeExpr(field.ty, access, false);
+                    try self.writer.writeAll(";\n");
+                }
             } else {
                 try self.writer.writeAll("    w.write_null();\n");
             }
@@ -512,7 +904,7 @@ fn emitStructEncoder(self: *RsGen, name: []const u8, def: *const Ast.StructDef)
 
 fn emitEnumEncoder(self: *RsGen, name: []const u8, def: *const Ast.EnumDef) Error!void {
     const fn_name = try toSnakeCaseAlloc(self.arena, name);
-    try self.writer.print("\nfn _enc_{s}(w: &mut Writer, v: &{s}) {{\n", .{ fn_name, name });
+    try self.writer.print("\n{s}fn _enc_{s}(w: &mut Writer, v: &{s}) {{\n", .{ self.helperVis(), fn_name, name });
     try self.writer.writeAll("    match v {\n");
     for (def.variants) |v| {
         const vname = try toPascalCase(self.arena, v.name);
@@ -523,7 +915,7 @@ fn emitEnumEncoder(self: *RsGen, name: []const u8, def: *const Ast.EnumDef) Erro
 
 fn emitUnionEncoder(self: *RsGen, name: []const u8, def: *const Ast.UnionDef) Error!void {
     const fn_name = try toSnakeCaseAlloc(self.arena, name);
-    try self.writer.print("\nfn _enc_{s}(w: &mut Writer, v: &{s}) {{\n", .{ fn_name, name });
+    try self.writer.print("\n{s}fn _enc_{s}(w: &mut Writer, v: &{s}) {{\n", .{ self.helperVis(), fn_name, name });
     try self.writer.writeAll("    match v {\n");
     for (def.variants) |v| {
         const vname = try toPascalCase(self.arena, v.name);
@@ -584,7 +976,7 @@ fn emitEncodeExpr(self: *RsGen, ty: Ast.TypeExpr, access: []const u8, is_ref: bo
         .float => |f| {
             const deref = if (is_ref) "*" else "";
             const fn_name = switch (f.kind) {
-                .f16 => "write_f16",
+                .f16 => if (self.options.half) "write_half" else "write_f16",
                 .f32 => "write_f32",
                 .f64 => "write_f64",
             };
@@ -646,7 +1038,7 @@ fn emitEncodeExpr(self: *RsGen, ty: Ast.TypeExpr, access: []const u8, is_ref: bo
                         try self.writer.writeAll("; }");
                     },
                     .external_len => |e| {
-                        try self.writer.writeAll("w.write_byte(0x9f);\n    for ");
+                        try self.writer.writeAll("w.write_indefinite_array_start();\n    for ");
                         try self.writer.print("{s} in ", .{lv});
                         if (is_ref) {
                             try self.writer.print("{s}", .{access});
@@ -655,7 +1047,7 @@ fn emitEncodeExpr(self: *RsGen, ty: Ast.TypeExpr, access: []const u8, is_ref: bo
                         }
                         try self.writer.writeAll(" { ");
                         try self.emitEncodeExpr(e.element, lv, true);
-                        try self.writer.writeAll("; }\n    w.write_byte(0xff)");
+                        try self.writer.writeAll("; }\n    w.write_break()");
                     },
                 }
             }
@@ -663,48 +1055,57 @@ fn emitEncodeExpr(self: *RsGen, ty: Ast.TypeExpr, access: []const u8, is_ref: bo
         .struct_ => |s| {
             if (self.inline_struct_names.get(s)) |sname| {
                 const sfn = try toSnakeCaseAlloc(self.arena, sname);
+                const path = try self.inlinePath(s);
                 if (is_ref) {
-                    try self.writer.print("_enc_{s}(w, {s})", .{ sfn, access });
+                    try self.writer.print("{s}_enc_{s}(w, {s})", .{ path, sfn, access });
                 } else {
-                    try self.writer.print("_enc_{s}(w, &{s})", .{ sfn, access });
+                    try self.writer.print("{s}_enc_{s}(w, &{s})", .{ path, sfn, access });
                 }
             }
         },
         .enum_ => |e| {
             if (self.inline_enum_names.get(e)) |ename| {
                 const efn = try toSnakeCaseAlloc(self.arena, ename);
+                const path = try self.inlinePath(e);
                 if (is_ref) {
-                    try self.writer.print("_enc_{s}(w, {s})", .{ efn, access });
+                    try self.writer.print("{s}_enc_{s}(w, {s})", .{ path, efn, access });
                 } else {
-                    try self.writer.print("_enc_{s}(w, &{s})", .{ efn, access });
+                    try self.writer.print("{s}_enc_{s}(w, &{s})", .{ path, efn, access });
                 }
             }
         },
         .union_ => |u| {
             if (self.inline_union_names.get(u)) |uname| {
                 const ufn = try toSnakeCaseAlloc(self.arena, uname);
+                const path = try self.inlinePath(u);
                 if (is_ref) {
-                    try self.writer.print("_enc_{s}(w, {s})", .{ ufn, access });
+                    try self.writer.print("{s}_enc_{s}(w, {s})", .{ path, ufn, access });
                 } else {
-                    try self.writer.print("_enc_{s}(w, &{s})", .{ ufn, access });
+                    try self.writer.print("{s}_enc_{s}(w, &{s})", .{ path, ufn, access });
                 }
             }
         },
         .named => |n| {
             const nfn = try toSnakeCaseAlloc(self.arena, n.name);
-            if (is_ref) {
-                try self.writer.print("_enc_{s}(w, {s})", .{ nfn, access });
+            const path = try self.modulePath(self.scope_ns);
+            if (try self.typeMapping(self.scope_ns, n.name) != null) {
+                try self.emitMappedEncode(try std.fmt.allocPrint(self.arena, "{s}_enc_{s}", .{ path, nfn }), access, is_ref);
+            } else if (is_ref) {
+                try self.writer.print("{s}_enc_{s}(w, {s})", .{ path, nfn, access });
             } else {
-                try self.writer.print("_enc_{s}(w, &{s})", .{ nfn, access });
+                try self.writer.print("{s}_enc_{s}(w, &{s})", .{ path, nfn, access });
             }
         },
         .qualified => |q| {
             if (self.resolveQualified(q.namespace, q.name)) |_| {
                 const qfn = try toSnakeCaseAlloc(self.arena, q.name);
-                if (is_ref) {
-                    try self.writer.print("_enc_{s}(w, {s})", .{ qfn, access });
+                const path = try self.modulePath(q.namespace);
+                if (try self.typeMapping(q.namespace, q.name) != null) {
+                    try self.emitMappedEncode(try std.fmt.allocPrint(self.arena, "{s}_enc_{s}", .{ path, qfn }), access, is_ref);
+                } else if (is_ref) {
+                    try self.writer.print("{s}_enc_{s}(w, {s})", .{ path, qfn, access });
                 } else {
-                    try self.writer.print("_enc_{s}(w, &{s})", .{ qfn, access });
+                    try self.writer.print("{s}_enc_{s}(w, &{s})", .{ path, qfn, access });
                 }
             }
         },
@@ -716,6 +1117,15 @@ fn emitEncodeExpr(self: *RsGen, ty: Ast.TypeExpr, access: []const u8, is_ref: bo
 // =========================================================================
 
 fn emitDecoderForDef(self: *RsGen, def: Ast.TypeDef) Error!void {
+    // Recursive types decode behind a guard that bounds nesting depth
+    if (try self.isRecursive(def)) {
+        const fn_name = try toSnakeCaseAlloc(self.arena, def.name);
+        try self.emitDecoderHeader(fn_name, def.name);
+        try self.writer.print("    r.enter()?;\n    let v = _dec_{s}_body(r);\n    r.leave();\n    v\n}}\n", .{fn_name});
+        self.dec_suffix = "_body";
+    }
+    defer self.dec_suffix = "";
+
     switch (def.ty) {
         .struct_ => |s| try self.emitStructDecoder(def.name, s),
         .enum_ => |e| try self.emitEnumDecoder(def.name, e),
@@ -723,19 +1133,36 @@ fn emitDecoderForDef(self: *RsGen, def: Ast.TypeDef) Error!void {
         else => {
             // Type alias
             const fn_name = try toSnakeCaseAlloc(self.arena, def.name);
-            try self.writer.print("\nfn _dec_{s}(r: &mut Reader) -> Result<{s}, DecodeError> {{\n    Ok(", .{ fn_name, def.name });
+            try self.emitDecoderHeader(fn_name, def.name);
+            try self.writer.writeAll("    let v = ");
             try self.emitDecodeExpr(def.ty);
-            try self.writer.writeAll(")\n}\n");
+            if (self.options.newtypes) {
+                try self.writer.print(";\n    Ok({s}(v))\n}}\n", .{def.name});
+            } else {
+                try self.writer.writeAll(";\n    Ok(v)\n}\n");
+            }
             try self.writer.print("\npub fn decode_{s}(data: &[u8]) -> Result<{s}, DecodeError> {{\n", .{ fn_name, def.name });
             try self.writer.print("    let mut r = Reader::new(data);\n    _dec_{s}(&mut r)\n}}\n", .{fn_name});
         },
     }
 }
 
+fn emitDecoderHeader(self: *RsGen, fn_name: []const u8, type_name: []const u8) Error!void {
+    // The guarded body is only called by its wrapper
+    const vis = if (self.dec_suffix.len > 0) "" else self.helperVis();
+    try self.writer.print("\n{s}fn _dec_{s}{s}(r: &mut Reader) -> Result<{s}, DecodeError> {{\n", .{ vis, fn_name, self.dec_suffix, type_name });
+}
+
+/// Strict readers reject struct arrays whose length differs from the schema's.
+fn emitStructHeader(self: *RsGen, def: *const Ast.StructDef) Error!void {
+    const fields = if (def.fields.len == 0) 0 else maxRank(def) + 1;
+    try self.writer.print("    let _len = r.read_struct_header({d})?;\n", .{fields});
+}
+
 fn emitStructDecoder(self: *RsGen, name: []const u8, def: *const Ast.StructDef) Error!void {
     const fn_name = try toSnakeCaseAlloc(self.arena, name);
-    try self.writer.print("\nfn _dec_{s}(r: &mut Reader) -> Result<{s}, DecodeError> {{\n", .{ fn_name, name });
-    try self.writer.writeAll("    let _len = r.read_array_header()?;\n");
+    try self.emitDecoderHeader(fn_name, name);
+    try self.emitStructHeader(def);
 
     if (def.fields.len == 0) {
         try self.writer.writeAll("    for _ in 0.._len { r.skip()?; }\n");
@@ -748,6 +1175,19 @@ fn emitStructDecoder(self: *RsGen, name: []const u8, def: *const Ast.StructDef)
             const fname = try self.toSnakeCase(field.name);
             const safe_name = try rustSafeIdent(self.arena, fname);
             try self.writer.print("    let mut _{s}: ", .{safe_name});
+            if (try self.fieldMapping(self.module_ns, name, field.name)) |rust_type| {
+                try self.writer.print("{s} = ", .{rust_type});
+                try self.emitMappedDefault(rust_type);
+                try self.writer.writeAll(";\n");
+                continue;
+            }
+            if (try self.fieldEncoded(self.module_ns, name, field.name)) {
+                try self.emitEncodedType(field.ty);
+                try self.writer.writeAll(" = ");
+                try self.emitEncodedDefault(field.ty);
+                try self.writer.writeAll(";\n");
+                continue;
+            }
             try self.emitTypeRef(field.ty);
             if (field.ty == .option) {
                 try self.writer.writeAll(" = None;\n");
@@ -766,10 +1206,18 @@ fn emitStructDecoder(self: *RsGen, name: []const u8, def: *const Ast.StructDef)
                 const fname = try self.toSnakeCase(field.name);
                 const safe_name = try rustSafeIdent(self.arena, fname);
                 try self.writer.print("_{s} = ", .{safe_name});
-                try self.emitDecodeExpr(field.ty);
+                if (try self.fieldMapping(self.module_ns, name, field.name)) |rust_type| {
+                    try self.writer.print("<{s} as {s}::CbgConvert>::from_wire(", .{ rust_type, self.options.config.runtime_crate });
+                    try self.emitDecodeExpr(field.ty);
+                    try self.writer.writeAll(")?");
+                } else if (try self.fieldEncoded(self.module_ns, name, field.name)) {
+                    try self.writer.print("{s}::Encoded::read(r)?", .{self.options.config.runtime_crate});
+                } else {
+                    try self.emitDecodeExpr(field.ty);
+                }
                 try self.writer.writeAll("; }\n");
             } else {
-                try self.writer.writeAll("r.skip()?; }\n");
+                try self.writer.writeAll("r.skip_gap()?; }\n");
             }
         }
 
@@ -789,7 +1237,7 @@ fn emitStructDecoder(self: *RsGen, name: []const u8, def: *const Ast.StructDef)
 
 fn emitEnumDecoder(self: *RsGen, name: []const u8, def: *const Ast.EnumDef) Error!void {
     const fn_name = try toSnakeCaseAlloc(self.arena, name);
-    try self.writer.print("\nfn _dec_{s}(r: &mut Reader) -> Result<{s}, DecodeError> {{\n", .{ fn_name, name });
+    try self.emitDecoderHeader(fn_name, name);
     try self.writer.writeAll("    match r.read_uvarint()? {\n");
     for (def.variants) |v| {
         const vname = try toPascalCase(self.arena, v.name);
@@ -801,19 +1249,10 @@ fn emitEnumDecoder(self: *RsGen, name: []const u8, def: *const Ast.EnumDef) Erro
 
 fn emitUnionDecoder(self: *RsGen, name: []const u8, def: *const Ast.UnionDef) Error!void {
     const fn_name = try toSnakeCaseAlloc(self.arena, name);
-    try self.writer.print("\nfn _dec_{s}(r: &mut Reader) -> Result<{s}, DecodeError> {{\n", .{ fn_name, name });
-    try self.writer.writeAll("    let _b = r.peek_byte()?;\n");
-    try self.writer.writeAll("    let _maj = _b >> 5;\n");
+    try self.emitDecoderHeader(fn_name, name);
+    try self.writer.writeAll("    let _maj = r.peek_major_type()?;\n");
     try self.writer.writeAll("    if _maj == 6 {\n");
-    try self.writer.writeAll("        let _b = r.read_byte()?;\n");
-    try self.writer.writeAll("        let _ai = _b & 0x1f;\n");
-    try self.writer.writeAll("        let _tag: u64 = match _ai {\n");
-    try self.writer.writeAll("            0..=23 => _ai as u64,\n");
-    try self.writer.writeAll("            24 => r.read_byte()? as u64,\n");
-    try self.writer.writeAll("            25 => { let b = [r.read_byte()?, r.read_byte()?]; u16::from_be_bytes(b) as u64 }\n");
-    try self.writer.writeAll("            26 => { let b = [r.read_byte()?, r.read_byte()?, r.read_byte()?, r.read_byte()?]; u32::from_be_bytes(b) as u64 }\n");
-    try self.writer.writeAll("            _ => return Err(DecodeError::InvalidData(\"unsupported tag size\".into())),\n");
-    try self.writer.writeAll("        };\n");
+    try self.writer.writeAll("        let _tag = r.read_tag_header()?;\n");
     try self.writer.writeAll("        match _tag {\n");
     for (def.variants) |v| {
         if (v.payload) |payload| {
@@ -860,64 +1299,82 @@ fn emitDecodeExpr(self: *RsGen, ty: Ast.TypeExpr) Error!void {
         },
         .float => |f| {
             const fn_name = switch (f.kind) {
-                .f16 => "r.read_f16()?",
+                .f16 => if (self.options.half) "r.read_half()?" else "r.read_f16()?",
                 .f32 => "r.read_f32()?",
                 .f64 => "r.read_f64()?",
             };
             try self.writer.writeAll(fn_name);
         },
         .option => |o| {
-            try self.writer.writeAll("if r.peek_byte()? == 0x00 { r.read_byte()?; None } else { r.read_byte()?; Some(");
+            try self.writer.writeAll("if r.read_option_tag()? { Some(");
             try self.emitDecodeExpr(o.child);
-            try self.writer.writeAll(") }");
+            try self.writer.writeAll(") } else { None }");
         },
         .array => |a| {
             if (isU8Array(a.getElement())) {
                 try self.writer.writeAll("r.read_bytes()?");
-            } else switch (a.*) {
-                .variable => |v| {
-                    try self.writer.writeAll("{ let _n = r.read_array_header()?; let mut _a = Vec::with_capacity(_n); for _ in 0.._n { _a.push(");
-                    try self.emitDecodeExpr(v.element);
-                    try self.writer.writeAll("); } _a }");
-                },
-                .fixed => |f| {
-                    try self.writer.print("{{ let _ = r.read_array_header()?; let mut _a = Vec::with_capacity({d}); for _ in 0..{d}usize {{ _a.push(", .{ f.len, f.len });
-                    try self.emitDecodeExpr(f.element);
-                    try self.writer.writeAll("); } _a }");
-                },
-                .external_len => |e| {
-                    try self.writer.writeAll("{ if r.read_byte()? != 0x9f { return Err(DecodeError::InvalidData(\"expected indefinite array\".into())); } let mut _a = Vec::new(); while r.peek_byte()? != 0xff { _a.push(");
-                    try self.emitDecodeExpr(e.element);
-                    try self.writer.writeAll("); } r.read_byte()?; _a }");
-                },
+            } else {
+                self.box_blocked += 1;
+                defer self.box_blocked -= 1;
+                switch (a.*) {
+                    .variable => |v| {
+                        try self.writer.writeAll("{ let _n = r.read_array_header()?; let mut _a = Vec::with_capacity(_n); for _ in 0.._n { _a.push(");
+                        try self.emitDecodeExpr(v.element);
+                        try self.writer.writeAll("); } _a }");
+                    },
+                    .fixed => |f| {
+                        try self.writer.print("{{ let _ = r.read_array_header()?; let mut _a = Vec::with_capacity({d}); for _ in 0..{d}usize {{ _a.push(", .{ f.len, f.len });
+                        try self.emitDecodeExpr(f.element);
+                        try self.writer.writeAll("); } _a }");
+                    },
+                    .external_len => |e| {
+                        try self.writer.writeAll("{ r.read_indefinite_array_start()?; let mut _a = Vec::new(); while !r.try_read_break()? { _a.push(");
+                        try self.emitDecodeExpr(e.element);
+                        try self.writer.writeAll("); } _a }");
+                    },
+                }
             }
         },
         .struct_ => |s| {
             if (self.inline_struct_names.get(s)) |sname| {
                 const sfn = try toSnakeCaseAlloc(self.arena, sname);
-                try self.writer.print("_dec_{s}(r)?", .{sfn});
+                try self.writer.print("{s}_dec_{s}(r)?", .{ try self.inlinePath(s), sfn });
             }
         },
         .enum_ => |e| {
             if (self.inline_enum_names.get(e)) |ename| {
                 const efn = try toSnakeCaseAlloc(self.arena, ename);
-                try self.writer.print("_dec_{s}(r)?", .{efn});
+                try self.writer.print("{s}_dec_{s}(r)?", .{ try self.inlinePath(e), efn });
             }
         },
         .union_ => |u| {
             if (self.inline_union_names.get(u)) |uname| {
                 const ufn = try toSnakeCaseAlloc(self.arena, uname);
-                try self.writer.print("_dec_{s}(r)?", .{ufn});
+                try self.writer.print("{s}_dec_{s}(r)?", .{ try self.inlinePath(u), ufn });
             }
         },
         .named => |n| {
             const nfn = try toSnakeCaseAlloc(self.arena, n.name);
-            try self.writer.print("_dec_{s}(r)?", .{nfn});
+            const dec_fn = try std.fmt.allocPrint(self.arena, "{s}_dec_{s}", .{ try self.modulePath(self.scope_ns), nfn });
+            if (try self.typeMapping(self.scope_ns, n.name)) |rust_type| {
+                try self.emitMappedDecode(rust_type, dec_fn);
+            } else if (try self.needsBox(self.scope_ns, n.name)) {
+                try self.writer.print("Box::new({s}(r)?)", .{dec_fn});
+            } else {
+                try self.writer.print("{s}(r)?", .{dec_fn});
+            }
         },
         .qualified => |q| {
             if (self.resolveQualified(q.namespace, q.name)) |_| {
                 const qfn = try toSnakeCaseAlloc(self.arena, q.name);
-                try self.writer.print("_dec_{s}(r)?", .{qfn});
+                const dec_fn = try std.fmt.allocPrint(self.arena, "{s}_dec_{s}", .{ try self.modulePath(q.namespace), qfn });
+                if (try self.typeMapping(q.namespace, q.name)) |rust_type| {
+                    try self.emitMappedDecode(rust_type, dec_fn);
+                } else if (try self.needsBox(q.namespace, q.name)) {
+                    try self.writer.print("Box::new({s}(r)?)", .{dec_fn});
+                } else {
+                    try self.writer.print("{s}(r)?", .{dec_fn});
+                }
             } else {
                 try self.writer.print("return Err(DecodeError::InvalidData(\"unresolved {s}.{s}\".into()))", .{ q.namespace, q.name });
             }
@@ -930,17 +1387,33 @@ fn emitDefaultValue(self: *RsGen, ty: Ast.TypeExpr) Error!void {
         .bool => try self.writer.writeAll("false"),
         .string => try self.writer.writeAll("String::new()"),
         .int => try self.writer.writeAll("0"),
-        .float => try self.writer.writeAll("0.0"),
+        .float => |f| {
+            if (f.kind == .f16 and self.options.half) {
+                try self.writer.print("{s}::half::f16::ZERO", .{self.options.config.runtime_crate});
+            } else {
+                try self.writer.writeAll("0.0");
+            }
+        },
         .option => try self.writer.writeAll("None"),
         .array => try self.writer.writeAll("Vec::new()"),
         .struct_ => |s| {
             if (self.inline_struct_names.get(s)) |sname| {
-                try self.writer.print("{s} {{ ", .{sname});
+                try self.writer.print("{s}{s} {{ ", .{ try self.inlinePath(s), sname });
+                const owner = self.inline_owner.get(s) orelse null;
+                const saved_root = self.current_root;
+                defer self.current_root = saved_root;
+                self.current_root = self.inline_root.get(s);
                 for (s.fields, 0..) |field, idx| {
                     const fname = try self.toSnakeCase(field.name);
                     const safe_name = try rustSafeIdent(self.arena, fname);
                     try self.writer.print("{s}: ", .{safe_name});
-                    try self.emitDefaultValue(field.ty);
+                    if (try self.fieldMapping(owner, sname, field.name)) |rust_type| {
+                        try self.emitMappedDefault(rust_type);
+                    } else if (try self.fieldEncoded(owner, sname, field.name)) {
+                        try self.emitEncodedDefault(field.ty);
+                    } else {
+                        try self.emitDefaultValue(field.ty);
+                    }
                     if (idx < s.fields.len - 1) try self.writer.writeAll(", ");
                 }
                 try self.writer.writeAll(" }");
@@ -952,7 +1425,7 @@ fn emitDefaultValue(self: *RsGen, ty: Ast.TypeExpr) Error!void {
             if (self.inline_enum_names.get(e)) |ename| {
                 if (e.variants.len > 0) {
                     const vname = try toPascalCase(self.arena, e.variants[0].name);
-                    try self.writer.print("{s}::{s}", .{ ename, vname });
+                    try self.writer.print("{s}{s}::{s}", .{ try self.inlinePath(e), ename, vname });
                 } else {
                     try self.writer.writeAll("Default::default()");
                 }
@@ -963,14 +1436,18 @@ fn emitDefaultValue(self: *RsGen, ty: Ast.TypeExpr) Error!void {
         .union_ => |u| {
             if (self.inline_union_names.get(u)) |uname| {
                 if (u.variants.len > 0) {
-                    const v = u.variants[0];
+                    const saved_root = self.current_root;
+                    defer self.current_root = saved_root;
+                    self.current_root = self.inline_root.get(u);
+                    const v = try self.defaultVariant(u);
                     const vname = try toPascalCase(self.arena, v.name);
+                    const path = try self.inlinePath(u);
                     if (v.payload) |payload| {
-                        try self.writer.print("{s}::{s}(", .{ uname, vname });
+                        try self.writer.print("{s}{s}::{s}(", .{ path, uname, vname });
                         try self.emitDefaultValue(payload);
                         try self.writer.writeAll(")");
                     } else {
-                        try self.writer.print("{s}::{s}", .{ uname, vname });
+                        try self.writer.print("{s}{s}::{s}", .{ path, uname, vname });
                     }
                 }
             } else {
@@ -978,11 +1455,27 @@ fn emitDefaultValue(self: *RsGen, ty: Ast.TypeExpr) Error!void {
             }
         },
         .named => |n| {
-            try self.emitDefaultForNamedType(n.name);
+            if (try self.typeMapping(self.scope_ns, n.name)) |rust_type| {
+                try self.emitMappedDefault(rust_type);
+            } else if (try self.needsBox(self.scope_ns, n.name)) {
+                try self.writer.writeAll("Box::new(");
+                try self.emitDefaultForNamedType(n.name);
+                try self.writer.writeAll(")");
+            } else {
+                try self.emitDefaultForNamedType(n.name);
+            }
         },
         .qualified => |q| {
-            if (self.resolveQualified(q.namespace, q.name)) |resolved| {
-                try self.emitDefaultForTypeDef(q.name, resolved);
+            if (try self.typeMapping(q.namespace, q.name)) |rust_type| {
+                try self.emitMappedDefault(rust_type);
+            } else if (self.resolveQualified(q.namespace, q.name)) |resolved| {
+                const name = try std.fmt.allocPrint(self.arena, "{s}{s}", .{ try self.modulePath(q.namespace), q.name });
+                const boxed = try self.needsBox(q.namespace, q.name);
+                if (boxed) try self.writer.writeAll("Box::new(");
+                const saved = self.enterScope(q.namespace);
+                try self.emitDefaultForTypeDef(name, resolved);
+                self.leaveScope(saved);
+                if (boxed) try self.writer.writeAll(")");
             } else {
                 try self.writer.writeAll("unsafe { std::mem::zeroed() }");
             }
@@ -990,11 +1483,416 @@ fn emitDefaultValue(self: *RsGen, ty: Ast.TypeExpr) Error!void {
     }
 }
 
+// =========================================================================
+// Decode-into
+// =========================================================================
+
+fn emitDecodeIntoForDef(self: *RsGen, def: Ast.TypeDef) Error!void {
+    const s = switch (def.ty) {
+        .struct_ => |s| s,
+        else => return,
+    };
+    if (try self.isRecursive(def)) {
+        const fn_name = try toSnakeCaseAlloc(self.arena, def.name);
+        try self.writer.print("\n{s}fn _dec_into_{s}(r: &mut Reader, v: &mut {s}) -> Result<(), DecodeError> {{\n", .{ self.helperVis(), fn_name, def.name });
+        try self.writer.print("    r.enter()?;\n    let res = _dec_into_{s}_body(r, v);\n    r.leave();\n    res\n}}\n", .{fn_name});
+        self.dec_suffix = "_body";
+    }
+    defer self.dec_suffix = "";
+    try self.emitStructDecodeInto(def.name, s);
+}
+
+/// Emit `_dec_into_{name}`, which decodes over an existing value: strings
+/// and vectors are cleared and refilled, and nested structs are decoded in
+/// place, so their allocations are reused.
+fn emitStructDecodeInto(self: *RsGen, name: []const u8, def: *const Ast.StructDef) Error!void {
+    const fn_name = try toSnakeCaseAlloc(self.arena, name);
+    const vis = if (self.dec_suffix.len > 0) "" else self.helperVis();
+    const param = if (def.fields.len == 0) "_v" else "v";
+    try self.writer.print("\n{s}fn _dec_into_{s}{s}(r: &mut Reader, {s}: &mut {s}) -> Result<(), DecodeError> {{\n", .{ vis, fn_name, self.dec_suffix, param, name });
+    try self.emitStructHeader(def);
+    if (def.fields.len == 0) {
+        try self.writer.writeAll("    for _ in 0.._len { r.skip()?; }\n    Ok(())\n}\n");
+        return;
+    }
+
+    const max_rank = maxRank(def);
+    var rank: u64 = 0;
+    while (rank <= max_rank) : (rank += 1) {
+        try self.writer.print("    if _len > {d} {{ ", .{rank});
+        const field = findFieldByRank(def, rank) orelse {
+            try self.writer.writeAll("r.skip_gap()?; }\n");
+            continue;
+        };
+        const fname = try self.toSnakeCase(field.name);
+        const place = try std.fmt.allocPrint(self.arena, "v.{s}", .{try rustSafeIdent(self.arena, fname)});
+        if (try self.fieldMapping(self.module_ns, name, field.name)) |rust_type| {
+            try self.writer.print("{s} = <{s} as {s}::CbgConvert>::from_wire(", .{ place, rust_type, self.options.config.runtime_crate });
+            try self.emitDecodeExpr(field.ty);
+            try self.writer.writeAll(")?; } else { ");
+            try self.writer.print("{s} = ", .{place});
+            try self.emitMappedDefault(rust_type);
+            try self.writer.writeAll("; }\n");
+            continue;
+        }
+        if (try self.fieldEncoded(self.module_ns, name, field.name)) {
+            try self.writer.print("{s} = {s}::Encoded::read(r)?; }} else {{ {s} = ", .{ place, self.options.config.runtime_crate, place });
+            try self.emitEncodedDefault(field.ty);
+            try self.writer.writeAll("; }\n");
+            continue;
+        }
+        try self.emitDecodeInto(field.ty, place, false);
+        // Absent fields take the same default a fresh decode gives them
+        try self.writer.writeAll(" } else { ");
+        switch (field.ty) {
+            .string, .array => try self.writer.print("{s}.clear();", .{place}),
+            else => {
+                try self.writer.print("{s} = ", .{place});
+                try self.emitDefaultValue(field.ty);
+                try self.writer.writeAll(";");
+            },
+        }
+        try self.writer.writeAll(" }\n");
+    }
+    try self.writer.print("    for _ in {d}.._len {{ r.skip()?; }}\n    Ok(())\n}}\n", .{max_rank + 1});
+}
+
+/// Emit a statement decoding `ty` over `place`. `is_ref` means `place` is a
+/// `&mut T` binding rather than a place expression.
+fn emitDecodeInto(self: *RsGen, ty: Ast.TypeExpr, place: []const u8, is_ref: bool) Error!void {
+    const ref = if (is_ref) place else try std.fmt.allocPrint(self.arena, "&mut {s}", .{place});
+    switch (ty) {
+        .string => return self.writer.print("r.read_string_into({s})?;", .{ref}),
+        .array => |a| {
+            if (isU8Array(a.getElement())) return self.writer.print("r.read_bytes_into({s})?;", .{ref});
+            const d = self.loop_depth;
+            self.loop_depth += 1;
+            defer self.loop_depth -= 1;
+            self.box_blocked += 1;
+            defer self.box_blocked -= 1;
+            const elem = try std.fmt.allocPrint(self.arena, "{s}[_i{d}]", .{ place, d });
+            switch (a.*) {
+                .variable, .fixed => {
+                    switch (a.*) {
+                        .fixed => |f| try self.writer.print("{{ let _ = r.read_array_header()?; let _n{d} = {d}usize; ", .{ d, f.len }),
+                        else => try self.writer.print("{{ let _n{d} = r.read_array_header()?; ", .{d}),
+                    }
+                    try self.writer.print("{s}.truncate(_n{d}); for _i{d} in 0.._n{d} {{ if _i{d} < {s}.len() {{ ", .{ place, d, d, d, d, place });
+                    try self.emitDecodeInto(a.getElement(), elem, false);
+                    try self.writer.print(" }} else {{ {s}.push(", .{place});
+                    try self.emitDecodeExpr(a.getElement());
+                    try self.writer.writeAll("); } } }");
+                },
+                .external_len => {
+                    try self.writer.print("{{ r.read_indefinite_array_start()?; let mut _i{d} = 0; while !r.try_read_break()? {{ if _i{d} < {s}.len() {{ ", .{ d, d, place });
+                    try self.emitDecodeInto(a.getElement(), elem, false);
+                    try self.writer.print(" }} else {{ {s}.push(", .{place});
+                    try self.emitDecodeExpr(a.getElement());
+                    try self.writer.print("); }} _i{d} += 1; }} {s}.truncate(_i{d}); }}", .{ d, place, d });
+                },
+            }
+            return;
+        },
+        .option => |o| {
+            const d = self.loop_depth;
+            self.loop_depth += 1;
+            defer self.loop_depth -= 1;
+            const deref = if (is_ref) "*" else "";
+            try self.writer.print("if !r.read_option_tag()? {{ {s}{s} = None; }} else if let Some(_o{d}) = {s}.as_mut() {{ ", .{ deref, place, d, place });
+            try self.emitDecodeInto(o.child, try std.fmt.allocPrint(self.arena, "_o{d}", .{d}), true);
+            try self.writer.print(" }} else {{ {s}{s} = Some(", .{ deref, place });
+            try self.emitDecodeExpr(o.child);
+            try self.writer.writeAll("); }");
+            return;
+        },
+        .struct_ => |s| {
+            if (self.inline_struct_names.get(s)) |sname| {
+                const sfn = try toSnakeCaseAlloc(self.arena, sname);
+                return self.writer.print("{s}_dec_into_{s}(r, {s})?;", .{ try self.inlinePath(s), sfn, ref });
+            }
+        },
+        .named => |n| {
+            if (try self.decodesInto(self.scope_ns, n.name)) {
+                const nfn = try toSnakeCaseAlloc(self.arena, n.name);
+                return self.writer.print("{s}_dec_into_{s}(r, {s})?;", .{ try self.modulePath(self.scope_ns), nfn, ref });
+            }
+        },
+        .qualified => |q| {
+            if (try self.decodesInto(q.namespace, q.name)) {
+                const qfn = try toSnakeCaseAlloc(self.arena, q.name);
+                return self.writer.print("{s}_dec_into_{s}(r, {s})?;", .{ try self.modulePath(q.namespace), qfn, ref });
+            }
+        },
+        else => {},
+    }
+    // Everything else is small or replaced wholesale
+    try self.writer.print("{s}{s} = ", .{ if (is_ref) "*" else "", place });
+    try self.emitDecodeExpr(ty);
+    try self.writer.writeAll(";");
+}
+
+/// Whether a named type has a `_dec_into_` function (unmapped structs).
+fn decodesInto(self: *RsGen, ns: ?[]const u8, name: []const u8) Error!bool {
+    if (try self.typeMapping(ns, name) != null) return false;
+    const def = self.lookupDef(ns, name) orelse return false;
+    return def.ty == .struct_;
+}
+
+// =========================================================================
+// Lazy views
+// =========================================================================
+
+/// Emit `{Name}Lazy<'a>`, which records where each field of an encoded
+/// struct starts (skipping over the contents) and decodes fields on demand.
+fn emitLazy(self: *RsGen, name: []const u8, def: *const Ast.StructDef) Error!void {
+    if (def.fields.len == 0) return;
+    const n = maxRank(def) + 1;
+    try self.writer.print("\n/// View of an encoded [`{s}`] that decodes fields on demand.\n", .{name});
+    try self.writer.print("#[derive(Debug, Clone, Copy)]\npub struct {s}Lazy<'a> {{\n    data: &'a [u8],\n    len: usize,\n    offsets: [usize; {d}],\n}}\n", .{ name, n + 1 });
+
+    try self.writer.print("\nimpl<'a> {s}Lazy<'a> {{\n", .{name});
+    try self.writer.print("    /// Index the fields of an encoded `{s}`, skipping over their contents.\n", .{name});
+    try self.writer.writeAll("    pub fn new(data: &'a [u8]) -> Result<Self, DecodeError> {\n");
+    try self.writer.writeAll("        let mut r = Reader::new(data);\n");
+    try self.writer.print("        let len = r.read_array_header()?.min({d});\n", .{n});
+    try self.writer.print("        let mut offsets = [0; {d}];\n", .{n + 1});
+    try self.writer.writeAll("        offsets[0] = r.position();\n");
+    try self.writer.writeAll("        for slot in &mut offsets[1..=len] {\n            r.skip()?;\n            *slot = r.position();\n        }\n");
+    try self.writer.print("        Ok({s}Lazy {{ data, len, offsets }})\n    }}\n", .{name});
+
+    try self.writer.print("\n    /// Decode the whole value.\n    pub fn decode(&self) -> Result<{s}, DecodeError> {{\n        {s}::decode(self.data)\n    }}\n", .{ name, name });
+
+    try self.writer.writeAll("\n    fn field(&self, rank: usize) -> Option<Reader<'a>> {\n");
+    try self.writer.writeAll("        (rank < self.len).then(|| Reader::new(&self.data[self.offsets[rank]..self.offsets[rank + 1]]))\n    }\n");
+
+    var rank: u64 = 0;
+    while (rank < n) : (rank += 1) {
+        const field = findFieldByRank(def, rank) orelse continue;
+        const safe_name = try rustSafeIdent(self.arena, try self.toSnakeCase(field.name));
+        try self.writer.writeAll("\n");
+        try self.emitDoc(field.doc);
+        try self.writer.print("    pub fn {s}(&self) -> Result<", .{try self.lazyAccessorName(name, def, safe_name)});
+        if (try self.fieldMapping(self.module_ns, name, field.name)) |rust_type| {
+            try self.writer.print("{s}, DecodeError> {{\n        let Some(mut r) = self.field({d}) else {{ return Ok(", .{ rust_type, rank });
+            try self.emitMappedDefault(rust_type);
+            try self.writer.print(") }};\n        let v = <{s} as {s}::CbgConvert>::from_wire(", .{ rust_type, self.options.config.runtime_crate });
+            try self.emitDecodeExpr(field.ty);
+            try self.writer.writeAll(")?;\n        Ok(v)\n    }\n");
+            continue;
+        }
+        if (try self.fieldEncoded(self.module_ns, name, field.name)) {
+            try self.emitEncodedType(field.ty);
+            try self.writer.print(", DecodeError> {{\n        let Some(mut r) = self.field({d}) else {{ return Ok(", .{rank});
+            try self.emitEncodedDefault(field.ty);
+            try self.writer.print(") }};\n        let v = {s}::Encoded::read(&mut r)?;\n        Ok(v)\n    }}\n", .{self.options.config.runtime_crate});
+            continue;
+        }
+        // Strings and bytes borrow from the input
+        const borrowed: ?[3][]const u8 = switch (field.ty) {
+            .string => .{ "&'a str", "\"\"", "r.read_str()?" },
+            .array => |a| if (isU8Array(a.getElement())) .{ "&'a [u8]", "&[]", "r.read_byte_slice()?" } else null,
+            else => null,
+        };
+        if (borrowed) |b| {
+            try self.writer.print("{s}, DecodeError> {{\n        let Some(mut r) = self.field({d}) else {{ return Ok({s}) }};\n        let v = {s};\n        Ok(v)\n    }}\n", .{ b[0], rank, b[1], b[2] });
+            continue;
+        }
+        try self.emitTypeRef(field.ty);
+        try self.writer.print(", DecodeError> {{\n        let Some(mut r) = self.field({d}) else {{ return Ok(", .{rank});
+        try self.emitDefaultValue(field.ty);
+        try self.writer.writeAll(") };\n        let v = ");
+        try self.emitDecodeExpr(field.ty);
+        try self.writer.writeAll(";\n        Ok(v)\n    }\n");
+        if (field.ty == .array) try self.emitLazyIter(safe_name, rank, field.ty.array.getElement());
+    }
+    try self.writer.writeAll("}\n");
+}
+
+/// Name of a field's accessor in a Lazy view: the field name, or `get_{name}`
+/// when that is taken by a method of the view (`new`, `decode`, the private
+/// `field`, or another field's `{name}_iter`).
+fn lazyAccessorName(self: *RsGen, name: []const u8, def: *const Ast.StructDef, safe_name: []const u8) Error![]const u8 {
+    const methods = [_][]const u8{ "new", "decode", "field" };
+    for (methods) |m| {
+        if (std.mem.eql(u8, safe_name, m)) return std.fmt.allocPrint(self.arena, "get_{s}", .{safe_name});
+    }
+    for (def.fields) |other| {
+        if (!try self.hasLazyIter(name, other)) continue;
+        const other_name = try rustSafeIdent(self.arena, try self.toSnakeCase(other.name));
+        const iter_name = try std.fmt.allocPrint(self.arena, "{s}_iter", .{lazyIterBase(other_name)});
+        if (std.mem.eql(u8, safe_name, iter_name)) return std.fmt.allocPrint(self.arena, "get_{s}", .{safe_name});
+    }
+    return safe_name;
+}
+
+/// Whether `emitLazy` gives a field an `{name}_iter()` method: arrays other
+/// than bytes, unless the field is mapped or kept encoded.
+fn hasLazyIter(self: *RsGen, name: []const u8, field: Ast.FieldDef) Error!bool {
+    if (field.ty != .array or isU8Array(field.ty.array.getElement())) return false;
+    if (try self.fieldMapping(self.module_ns, name, field.name) != null) return false;
+    return !try self.fieldEncoded(self.module_ns, name, field.name);
+}
+
+/// `type_` iterates as `type_iter`, not `type__iter`.
+fn lazyIterBase(safe_name: []const u8) []const u8 {
+    return if (std.mem.endsWith(u8, safe_name, "_")) safe_name[0 .. safe_name.len - 1] else safe_name;
+}
+
+/// Emit `{field}_iter()`, which decodes an array field's elements one at a
+/// time instead of collecting them.
+fn emitLazyIter(self: *RsGen, safe_name: []const u8, rank: u64, element: Ast.TypeExpr) Error!void {
+    const rt = self.options.config.runtime_crate;
+    const base = lazyIterBase(safe_name);
+    try self.writer.print("\n    /// Decode the elements of `{s}` one at a time.\n", .{safe_name});
+    try self.writer.print("    pub fn {s}_iter(&self) -> Result<{s}::ArrayIter<'a, ", .{ base, rt });
+    try self.emitTypeRef(element);
+    try self.writer.print(">, DecodeError> {{\n        let Some(r) = self.field({d}) else {{ return Ok({s}::ArrayIter::empty()) }};\n", .{ rank, rt });
+    try self.writer.print("        {s}::ArrayIter::with(r, |r| {{\n            let v = ", .{rt});
+    try self.emitDecodeExpr(element);
+    try self.writer.writeAll(";\n            Ok(v)\n        })\n    }\n");
+}
+
+// =========================================================================
+// Builders
+// =========================================================================
+
+/// Emit `{Name}Builder`: chainable setters over a value whose fields start
+/// at the defaults they decode to when absent, and a `build()` that checks
+/// array lengths.
+fn emitBuilder(self: *RsGen, name: []const u8, def: *const Ast.StructDef) Error!void {
+    const rt = self.options.config.runtime_crate;
+    try self.writer.print("\n/// Builder for [`{s}`]. Fields that are not set keep the value they decode to when absent.\n", .{name});
+    try self.writer.print("#[derive(Debug, Clone)]\npub struct {s}Builder {{\n    value: {s},\n}}\n", .{ name, name });
+
+    try self.writer.print("\nimpl Default for {s}Builder {{\n    fn default() -> Self {{\n        {s}Builder {{\n            value: {s} {{\n", .{ name, name, name });
+    for (def.fields) |field| {
+        const safe_name = try rustSafeIdent(self.arena, try self.toSnakeCase(field.name));
+        try self.writer.print("                {s}: ", .{safe_name});
+        if (try self.fieldMapping(self.module_ns, name, field.name)) |rust_type| {
+            try self.emitMappedDefault(rust_type);
+        } else if (try self.fieldEncoded(self.module_ns, name, field.name)) {
+            try self.emitEncodedDefault(field.ty);
+        } else {
+            try self.emitDefaultValue(field.ty);
+        }
+        try self.writer.writeAll(",\n");
+    }
+    try self.writer.writeAll("            },\n        }\n    }\n}\n");
+
+    try self.writer.print("\nimpl {s}Builder {{\n", .{name});
+    for (def.fields) |field| {
+        const safe_name = try rustSafeIdent(self.arena, try self.toSnakeCase(field.name));
+        // `build` is taken, and `default` would shadow `Default::default`
+        const collides = std.mem.eql(u8, safe_name, "build") or std.mem.eql(u8, safe_name, "default");
+        const setter = if (collides) try std.fmt.allocPrint(self.arena, "set_{s}", .{safe_name}) else safe_name;
+        try self.writer.print("    pub fn {s}(mut self, value: ", .{setter});
+        if (try self.fieldMapping(self.module_ns, name, field.name)) |rust_type| {
+            try self.writer.print("{s}) -> Self {{\n        self.value.{s} = value;\n", .{ rust_type, safe_name });
+        } else if (try self.fieldEncoded(self.module_ns, name, field.name)) {
+            try self.emitEncodedType(field.ty);
+            try self.writer.print(") -> Self {{\n        self.value.{s} = value;\n", .{safe_name});
+        } else {
+            // Optional fields take the inner value
+            const inner = if (field.ty == .option) field.ty.option.child else field.ty;
+            const ty = try self.renderTypeRef(inner);
+            const convert = !isCopyType(inner);
+            if (convert) {
+                try self.writer.print("impl Into<{s}>) -> Self {{\n", .{ty});
+            } else {
+                try self.writer.print("{s}) -> Self {{\n", .{ty});
+            }
+            const value = if (convert) "value.into()" else "value";
+            if (field.ty == .option) {
+                try self.writer.print("        self.value.{s} = Some({s});\n", .{ safe_name, value });
+            } else {
+                try self.writer.print("        self.value.{s} = {s};\n", .{ safe_name, value });
+            }
+        }
+        try self.writer.writeAll("        self\n    }\n\n");
+    }
+
+    try self.writer.print("    pub fn build(self) -> Result<{s}, {s}::BuildError> {{\n", .{ name, rt });
+    for (def.fields) |field| {
+        if (try self.fieldMapping(self.module_ns, name, field.name) != null) continue;
+        if (try self.fieldEncoded(self.module_ns, name, field.name)) continue;
+        const safe_name = try rustSafeIdent(self.arena, try self.toSnakeCase(field.name));
+        const access = try std.fmt.allocPrint(self.arena, "self.value.{s}", .{safe_name});
+        if (field.ty == .option and field.ty.option.child == .array) {
+            try self.writer.print("        if let Some(_a) = &{s} {{\n", .{access});
+            try self.emitLengthCheck(name, def, field, field.ty.option.child.array, "_a", "    ");
+            try self.writer.writeAll("        }\n");
+        } else if (field.ty == .array) {
+            try self.emitLengthCheck(name, def, field, field.ty.array, access, "");
+        }
+    }
+    try self.writer.writeAll("        Ok(self.value)\n    }\n}\n");
+
+    try self.writer.print("\nimpl {s} {{\n    pub fn builder() -> {s}Builder {{\n        {s}Builder::default()\n    }}\n}}\n", .{ name, name, name });
+}
+
+/// Emit the `build()` check for a fixed-length or external-length array.
+fn emitLengthCheck(
+    self: *RsGen,
+    name: []const u8,
+    def: *const Ast.StructDef,
+    field: Ast.FieldDef,
+    array: *const Ast.ArrayDef,
+    access: []const u8,
+    indent: []const u8,
+) Error!void {
+    const rt = self.options.config.runtime_crate;
+    switch (array.*) {
+        .fixed => |f| {
+            try self.writer.print("{s}        if {s}.len() != {d} {{\n", .{ indent, access, f.len });
+            try self.writer.print("{s}            return Err({s}::BuildError::FixedLength {{ field: \"{s}.{s}\", expected: {d}, actual: {s}.len() }});\n", .{ indent, rt, name, field.name, f.len, access });
+            try self.writer.print("{s}        }}\n", .{indent});
+        },
+        .external_len => |e| {
+            const len_field = findLenField(def, e.len_field) orelse return;
+            if (len_field.ty != .int) return;
+            const len_name = try rustSafeIdent(self.arena, try self.toSnakeCase(len_field.name));
+            const cast = switch (len_field.ty.int.kind) {
+                .u64, .uvar => "",
+                else => " as u64",
+            };
+            const len = try std.fmt.allocPrint(self.arena, "self.value.{s}{s}", .{ len_name, cast });
+            try self.writer.print("{s}        if {s}.len() as u64 != {s} {{\n", .{ indent, access, len });
+            try self.writer.print("{s}            return Err({s}::BuildError::LengthMismatch {{ field: \"{s}.{s}\", len_field: \"{s}.{s}\", len: {s}, actual: {s}.len() }});\n", .{ indent, rt, name, field.name, name, len_field.name, len, access });
+            try self.writer.print("{s}        }}\n", .{indent});
+        },
+        .variable => {},
+    }
+}
+
+/// The field an external-length array takes its length from, named either
+/// by field name or by rank.
+fn findLenField(def: *const Ast.StructDef, len_field: []const u8) ?Ast.FieldDef {
+    for (def.fields) |field| {
+        if (std.mem.eql(u8, field.name, len_field)) return field;
+    }
+    const rank = std.fmt.parseInt(u64, len_field, 10) catch return null;
+    return findFieldByRank(def, rank);
+}
+
 // =========================================================================
 // Impl blocks
 // =========================================================================
 
-fn emitImplBlock(self: *RsGen, name: []const u8) Error!void {
+fn emitDecodeIntoImpl(self: *RsGen, name: []const u8) Error!void {
+    const fn_name = try toSnakeCaseAlloc(self.arena, name);
+    try self.writer.print("\nimpl {s} {{\n", .{name});
+    try self.writer.writeAll("    /// Decode over this value, reusing its strings, vectors and nested structs.\n");
+    try self.writer.writeAll("    /// On error the value is left partly overwritten.\n");
+    try self.writer.writeAll("    pub fn decode_into(&mut self, r: &mut Reader) -> Result<(), DecodeError> {\n");
+    try self.writer.print("        _dec_into_{s}(r, self)\n", .{fn_name});
+    try self.writer.writeAll("    }\n}\n");
+}
+
+/// Inherent methods plus the runtime's `Encode`/`Decode` traits, so generated
+/// types can be used as `Encoded<T>` or inside `#[derive(Cbg)]` types.
+/// `inline_ty` is the inline type expression, or null for a definition.
+fn emitImplBlock(self: *RsGen, name: []const u8, inline_ty: ?Ast.TypeExpr) Error!void {
+    const rt = self.options.config.runtime_crate;
     const fn_name = try toSnakeCaseAlloc(self.arena, name);
     try self.writer.print("\nimpl {s} {{\n", .{name});
     try self.writer.writeAll("    pub fn encode(&self) -> Vec<u8> {\n");
@@ -1005,14 +1903,106 @@ fn emitImplBlock(self: *RsGen, name: []const u8) Error!void {
     try self.writer.writeAll("    pub fn encode_with(&self, w: &mut Writer) {\n");
     try self.writer.print("        _enc_{s}(w, self);\n", .{fn_name});
     try self.writer.writeAll("    }\n\n");
+    if (self.options.digest) {
+        try self.writer.writeAll("    /// SHA-256 of the deterministic encoding.\n");
+        try self.writer.writeAll("    pub fn digest(&self) -> [u8; 32] {\n");
+        try self.writer.writeAll("        let mut w = Writer::deterministic();\n");
+        try self.writer.print("        _enc_{s}(&mut w, self);\n", .{fn_name});
+        try self.writer.print("        {s}::digest::sha256(&w.finish())\n", .{rt});
+        try self.writer.writeAll("    }\n\n");
+    }
     try self.writer.print("    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {{\n", .{});
     try self.writer.writeAll("        let mut r = Reader::new(data);\n");
     try self.writer.print("        _dec_{s}(&mut r)\n", .{fn_name});
     try self.writer.writeAll("    }\n\n");
+    try self.writer.writeAll("    /// Decode only the canonical encoding, with no trailing bytes.\n");
+    try self.writer.print("    pub fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {{\n", .{});
+    try self.writer.print("        let mut r = Reader::with_mode(data, {s}::ReaderMode::Strict);\n", .{rt});
+    try self.writer.print("        let v = _dec_{s}(&mut r)?;\n", .{fn_name});
+    try self.writer.writeAll("        r.finish()?;\n        Ok(v)\n");
+    try self.writer.writeAll("    }\n\n");
     try self.writer.print("    pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {{\n", .{});
     try self.writer.print("        _dec_{s}(r)\n", .{fn_name});
     try self.writer.writeAll("    }\n");
     try self.writer.writeAll("}\n");
+
+    try self.writer.print("\nimpl {s}::Encode for {s} {{\n", .{ rt, name });
+    try self.writer.print("    fn encode_with(&self, w: &mut Writer) {{\n        _enc_{s}(w, self);\n    }}\n}}\n", .{fn_name});
+    try self.writer.print("\nimpl {s}::Decode for {s} {{\n", .{ rt, name });
+    try self.writer.print("    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {{\n        _dec_{s}(r)\n    }}\n\n", .{fn_name});
+    try self.writer.writeAll("    fn default_value() -> Self {\n        ");
+    if (inline_ty) |ty| {
+        try self.emitDefaultValue(ty);
+    } else {
+        try self.emitDefaultForNamedType(name);
+    }
+    try self.writer.writeAll("\n    }\n}\n");
+}
+
+// =========================================================================
+// JSON (embedded descriptor + to_json/from_json)
+// =========================================================================
+
+fn emitJsonSection(self: *RsGen) Error!void {
+    try self.writer.writeAll("\n// === JSON ===\n");
+    // The descriptor lives at the root and covers every import
+    if (self.module_ns == null) try self.emitDescriptor();
+
+    for (self.schema.definitions) |def| {
+        switch (def.ty) {
+            .struct_, .enum_, .union_ => {
+                // Imported definitions are named `namespace.Name` in the descriptor
+                const schema_name = if (self.module_ns) |ns|
+                    try std.fmt.allocPrint(self.arena, "{s}.{s}", .{ ns, def.name })
+                else
+                    def.name;
+                try self.emitJsonImpl(def.name, schema_name);
+            },
+            else => {},
+        }
+    }
+}
+
+fn emitDescriptor(self: *RsGen) Error!void {
+    var buf: std.ArrayList(u8) = .{};
+    const buf_writer = buf.writer(self.arena);
+    var desc = Descriptor.init(buf_writer.any(), self.schema, self.imports);
+    try desc.emit();
+
+    try self.writer.writeAll("\nstatic _DESCRIPTOR: &[u8] = &[");
+    for (buf.items, 0..) |b, i| {
+        if (i % 16 == 0) {
+            try self.writer.writeAll("\n    ");
+        } else {
+            try self.writer.writeAll(" ");
+        }
+        try self.writer.print("0x{x:0>2},", .{b});
+    }
+    try self.writer.writeAll("\n];\n");
+    const rt = self.options.config.runtime_crate;
+    try self.writer.writeAll("\n/// The schema descriptor these types were generated from.\n");
+    try self.writer.print("pub fn descriptor() -> &'static {s}::descriptor::Schema {{\n", .{rt});
+    try self.writer.print("    static SCHEMA: std::sync::OnceLock<{s}::descriptor::Schema> = std::sync::OnceLock::new();\n", .{rt});
+    try self.writer.print("    SCHEMA.get_or_init(|| {s}::descriptor::Schema::decode(_DESCRIPTOR).expect(\"embedded descriptor is valid\"))\n", .{rt});
+    try self.writer.writeAll("}\n");
+}
+
+fn emitJsonImpl(self: *RsGen, name: []const u8, schema_name: []const u8) Error!void {
+    try self.writer.print("\nimpl {s} {{\n", .{name});
+    try self.writer.writeAll("    pub fn to_json(&self) -> Result<String, JsonError> {\n");
+    try self.writer.writeAll("        self.to_json_with(&JsonOptions::default())\n");
+    try self.writer.writeAll("    }\n\n");
+    // Fallible, since encoded fields carry bytes that may not match the descriptor
+    try self.writer.writeAll("    pub fn to_json_with(&self, opts: &JsonOptions) -> Result<String, JsonError> {\n");
+    const desc = try self.modulePath(null);
+    const rt = self.options.config.runtime_crate;
+    try self.writer.print("        {s}::json::to_json({s}descriptor(), \"{s}\", &self.encode(), opts)\n", .{ rt, desc, schema_name });
+    try self.writer.writeAll("    }\n\n");
+    try self.writer.writeAll("    pub fn from_json(json: &str) -> Result<Self, JsonError> {\n");
+    try self.writer.print("        let data = {s}::json::from_json({s}descriptor(), \"{s}\", json)?;\n", .{ rt, desc, schema_name });
+    try self.writer.writeAll("        Ok(Self::decode(&data)?)\n");
+    try self.writer.writeAll("    }\n");
+    try self.writer.writeAll("}\n");
 }
 
 // =========================================================================
@@ -1051,24 +2041,19 @@ fn isU8Array(element: Ast.TypeExpr) bool {
     return element == .int and element.int.kind == .u8;
 }
 
-/// Look up a named type in the main schema or imported schemas.
+/// Look up a named type in the schema currently in scope.
 fn resolveNamedTypeDef(self: *RsGen, name: []const u8) ?Ast.TypeDef {
     for (self.schema.definitions) |def| {
         if (std.mem.eql(u8, def.name, name)) return def;
     }
-    var import_it = self.imports.iterator();
-    while (import_it.next()) |entry| {
-        for (entry.value_ptr.definitions) |def| {
-            if (std.mem.eql(u8, def.name, name)) return def;
-        }
-    }
     return null;
 }
 
 /// Emit a default value for a named type reference.
 fn emitDefaultForNamedType(self: *RsGen, name: []const u8) Error!void {
     if (self.resolveNamedTypeDef(name)) |def| {
-        try self.emitDefaultForTypeDef(name, def);
+        const path = try self.modulePath(self.scope_ns);
+        try self.emitDefaultForTypeDef(try std.fmt.allocPrint(self.arena, "{s}{s}", .{ path, name }), def);
     } else {
         try self.writer.writeAll("unsafe { std::mem::zeroed() }");
     }
@@ -1076,6 +2061,13 @@ fn emitDefaultForNamedType(self: *RsGen, name: []const u8) Error!void {
 
 /// Emit a default value given a type name and its definition.
 fn emitDefaultForTypeDef(self: *RsGen, name: []const u8, def: Ast.TypeDef) Error!void {
+    const key = try self.configName(self.scope_ns, def.name);
+    try self.default_stack.put(key, {});
+    defer _ = self.default_stack.remove(key);
+    const saved_root = self.current_root;
+    defer self.current_root = saved_root;
+    self.current_root = def.name;
+
     switch (def.ty) {
         .enum_ => |e| {
             if (e.variants.len > 0) {
@@ -1087,7 +2079,7 @@ fn emitDefaultForTypeDef(self: *RsGen, name: []const u8, def: Ast.TypeDef) Error
         },
         .union_ => |u| {
             if (u.variants.len > 0) {
-                const v = u.variants[0];
+                const v = try self.defaultVariant(u);
                 const vname = try toPascalCase(self.arena, v.name);
                 if (v.payload) |payload| {
                     try self.writer.print("{s}::{s}(", .{ name, vname });
@@ -1106,14 +2098,26 @@ fn emitDefaultForTypeDef(self: *RsGen, name: []const u8, def: Ast.TypeDef) Error
                 const fname = try self.toSnakeCase(field.name);
                 const safe_name = try rustSafeIdent(self.arena, fname);
                 try self.writer.print("{s}: ", .{safe_name});
-                try self.emitDefaultValue(field.ty);
+                if (try self.fieldMapping(self.scope_ns, def.name, field.name)) |rust_type| {
+                    try self.emitMappedDefault(rust_type);
+                } else if (try self.fieldEncoded(self.scope_ns, def.name, field.name)) {
+                    try self.emitEncodedDefault(field.ty);
+                } else {
+                    try self.emitDefaultValue(field.ty);
+                }
                 if (idx < s.fields.len - 1) try self.writer.writeAll(", ");
             }
             try self.writer.writeAll(" }");
         },
         else => {
             // Alias to a primitive type — delegate
-            try self.emitDefaultValue(def.ty);
+            if (self.options.newtypes) {
+                try self.writer.print("{s}(", .{name});
+                try self.emitDefaultValue(def.ty);
+                try self.writer.writeAll(")");
+            } else {
+                try self.emitDefaultValue(def.ty);
+            }
         },
     }
 }
@@ -1127,53 +2131,295 @@ fn resolveQualified(self: *RsGen, namespace: []const u8, name: []const u8) ?Ast.
     return null;
 }
 
+/// Path prefix from the module being emitted to the module for `ns` (null
+/// for the root): "", "ns::", "super::" or "super::ns::". Imports are all
+/// emitted as siblings under the root.
+fn modulePath(self: *RsGen, ns: ?[]const u8) Error![]const u8 {
+    if (sameNamespace(ns, self.module_ns)) return "";
+    const up = if (self.module_ns != null) "super::" else "";
+    const target = ns orelse return up;
+    return try std.fmt.allocPrint(self.arena, "{s}{s}::", .{ up, try rustSafeIdent(self.arena, target) });
+}
+
+/// Path to an inline type's module, from the module being emitted.
+fn inlinePath(self: *RsGen, def: *const anyopaque) Error![]const u8 {
+    return self.modulePath(self.inline_owner.get(def) orelse null);
+}
+
+/// Whether an inline type belongs in the module being emitted.
+fn ownedHere(self: *RsGen, def: *const anyopaque) bool {
+    return sameNamespace(self.inline_owner.get(def) orelse null, self.module_ns);
+}
+
+/// Like `ownedHere`, also making the inline type's top-level definition
+/// the current root when it is emitted here.
+fn enterInline(self: *RsGen, def: *const anyopaque) bool {
+    if (!self.ownedHere(def)) return false;
+    self.current_root = self.inline_root.get(def);
+    return true;
+}
+
+/// Visibility for `_enc_`/`_dec_` helpers: imported modules expose them
+/// to the root and to sibling modules that reference their types.
+fn helperVis(self: *RsGen) []const u8 {
+    return if (self.module_ns != null) "pub(super) " else "";
+}
+
+const Scope = struct { schema: Ast.Schema, ns: ?[]const u8 };
+
+/// Resolve unqualified names against the import `ns` until `leaveScope`,
+/// for looking into an imported definition's fields.
+fn enterScope(self: *RsGen, ns: []const u8) Scope {
+    const saved: Scope = .{ .schema = self.schema, .ns = self.scope_ns };
+    self.schema = self.imports.get(ns).?;
+    self.scope_ns = ns;
+    return saved;
+}
+
+fn leaveScope(self: *RsGen, saved: Scope) void {
+    self.schema = saved.schema;
+    self.scope_ns = saved.ns;
+}
+
+fn sameNamespace(a: ?[]const u8, b: ?[]const u8) bool {
+    if (a == null or b == null) return a == null and b == null;
+    return std.mem.eql(u8, a.?, b.?);
+}
+
+fn lessThanStr(_: void, a: []const u8, b: []const u8) bool {
+    return std.mem.lessThan(u8, a, b);
+}
+
+// =========================================================================
+// Recursive types
+// =========================================================================
+
+/// A named type that reference walks look for.
+const Target = struct { ns: ?[]const u8, name: []const u8, through_arrays: bool };
+
+/// Look up a top-level definition in the main schema (null `ns`) or an import.
+fn lookupDef(self: *RsGen, ns: ?[]const u8, name: []const u8) ?Ast.TypeDef {
+    const schema = if (ns) |n| self.imports.get(n) orelse return null else self.main_schema;
+    for (schema.definitions) |def| {
+        if (std.mem.eql(u8, def.name, name)) return def;
+    }
+    return null;
+}
+
+/// Whether a reference from the type being emitted to `ns.name` closes a
+/// cycle that would give the Rust type infinite size. Arrays are already
+/// heap-allocated, so cycles through them need no box.
+fn needsBox(self: *RsGen, ns: ?[]const u8, name: []const u8) Error!bool {
+    const root = self.current_root orelse return false;
+    if (self.box_blocked > 0) return false;
+    if (sameNamespace(ns, self.scope_ns) and std.mem.eql(u8, name, root)) return true;
+    var visited = std.StringHashMap(void).init(self.arena);
+    return self.reachesDef(ns, name, .{ .ns = self.scope_ns, .name = root, .through_arrays = false }, &visited);
+}
+
+/// Whether decoding `def` can recurse into itself, including through arrays.
+fn isRecursive(self: *RsGen, def: Ast.TypeDef) Error!bool {
+    var visited = std.StringHashMap(void).init(self.arena);
+    return self.reaches(def.ty, self.scope_ns, .{ .ns = self.scope_ns, .name = def.name, .through_arrays = true }, &visited);
+}
+
+/// Whether `ty`, with unqualified names resolved in `ns`, contains `target`.
+fn reaches(self: *RsGen, ty: Ast.TypeExpr, ns: ?[]const u8, target: Target, visited: *std.StringHashMap(void)) Error!bool {
+    switch (ty) {
+        .struct_ => |s| {
+            for (s.fields) |field| {
+                if (try self.reaches(field.ty, ns, target, visited)) return true;
+            }
+            return false;
+        },
+        .union_ => |u| {
+            for (u.variants) |v| {
+                if (v.payload) |payload| {
+                    if (try self.reaches(payload, ns, target, visited)) return true;
+                }
+            }
+            return false;
+        },
+        .option => |o| return self.reaches(o.child, ns, target, visited),
+        .array => |a| {
+            if (!target.through_arrays) return false;
+            return self.reaches(a.getElement(), ns, target, visited);
+        },
+        .named => |n| return self.reachesDef(ns, n.name, target, visited),
+        .qualified => |q| return self.reachesDef(q.namespace, q.name, target, visited),
+        else => return false,
+    }
+}
+
+fn reachesDef(self: *RsGen, ns: ?[]const u8, name: []const u8, target: Target, visited: *std.StringHashMap(void)) Error!bool {
+    // A mapped type replaces the schema type, ending any cycle through it
+    if (try self.typeMapping(ns, name) != null) return false;
+    if (sameNamespace(ns, target.ns) and std.mem.eql(u8, name, target.name)) return true;
+    const key = try self.configName(ns, name);
+    if (visited.contains(key)) return false;
+    try visited.put(key, {});
+    const def = self.lookupDef(ns, name) orelse return false;
+    return self.reaches(def.ty, ns, target, visited);
+}
+
+/// Whether `ty` has a finite value without going through a named type in
+/// `visiting`. Options and arrays can always be empty; unions need one
+/// finite variant.
+fn hasFiniteValue(self: *RsGen, ty: Ast.TypeExpr, ns: ?[]const u8, visiting: *std.StringHashMap(void)) Error!bool {
+    switch (ty) {
+        .struct_ => |s| {
+            for (s.fields) |field| {
+                if (!try self.hasFiniteValue(field.ty, ns, visiting)) return false;
+            }
+            return true;
+        },
+        .union_ => |u| {
+            for (u.variants) |v| {
+                const payload = v.payload orelse return true;
+                if (try self.hasFiniteValue(payload, ns, visiting)) return true;
+            }
+            return false;
+        },
+        .named => |n| return self.defHasFiniteValue(ns, n.name, visiting),
+        .qualified => |q| return self.defHasFiniteValue(q.namespace, q.name, visiting),
+        else => return true,
+    }
+}
+
+fn defHasFiniteValue(self: *RsGen, ns: ?[]const u8, name: []const u8, visiting: *std.StringHashMap(void)) Error!bool {
+    if (try self.typeMapping(ns, name) != null) return true;
+    const key = try self.configName(ns, name);
+    if (visiting.contains(key)) return false;
+    const def = self.lookupDef(ns, name) orelse return true;
+    try visiting.put(key, {});
+    defer _ = visiting.remove(key);
+    return self.hasFiniteValue(def.ty, ns, visiting);
+}
+
+/// The variant a union's default value uses: the first one that doesn't
+/// lead back into a type whose default is already being emitted.
+fn defaultVariant(self: *RsGen, u: *const Ast.UnionDef) Error!Ast.UnionVariant {
+    for (u.variants) |v| {
+        const payload = v.payload orelse return v;
+        if (try self.hasFiniteValue(payload, self.scope_ns, &self.default_stack)) return v;
+    }
+    return u.variants[0];
+}
+
+/// Name of a type (`Name`, or `ns.Name` in an import) that can never hold
+/// a value because it contains itself with no option, array or union
+/// variant to end the recursion, or null if there is none.
+pub fn findInfiniteType(self: *RsGen) Error!?[]const u8 {
+    var visiting = std.StringHashMap(void).init(self.arena);
+    for (self.main_schema.definitions) |def| {
+        if (!try self.defHasFiniteValue(null, def.name, &visiting)) return def.name;
+    }
+    var it = self.imports.iterator();
+    while (it.next()) |entry| {
+        for (entry.value_ptr.definitions) |def| {
+            if (!try self.defHasFiniteValue(entry.key_ptr.*, def.name, &visiting)) {
+                return try self.configName(entry.key_ptr.*, def.name);
+            }
+        }
+    }
+    return null;
+}
+
+/// Name of an alias (`Name`, or `ns.Name` in an import) that refers back
+/// to itself through other aliases, options and arrays, such as `T = []T`,
+/// or null if there is none. Rust can't resolve such a `pub type`; structs,
+/// enums and unions (and newtypes) are nominal and end the cycle.
+pub fn findCyclicAlias(self: *RsGen) Error!?[]const u8 {
+    for (self.main_schema.definitions) |def| {
+        if (try self.aliasReachesSelf(null, def)) return def.name;
+    }
+    var it = self.imports.iterator();
+    while (it.next()) |entry| {
+        for (entry.value_ptr.definitions) |def| {
+            if (try self.aliasReachesSelf(entry.key_ptr.*, def)) {
+                return try self.configName(entry.key_ptr.*, def.name);
+            }
+        }
+    }
+    return null;
+}
+
+fn aliasReachesSelf(self: *RsGen, ns: ?[]const u8, def: Ast.TypeDef) Error!bool {
+    switch (def.ty) {
+        .struct_, .enum_, .union_ => return false,
+        else => {},
+    }
+    if (try self.typeMapping(ns, def.name) != null) return false;
+    var visited = std.StringHashMap(void).init(self.arena);
+    return self.aliasReaches(def.ty, ns, .{ .ns = ns, .name = def.name, .through_arrays = true }, &visited);
+}
+
+/// Like `reaches`, but stops at structs, enums and unions.
+fn aliasReaches(self: *RsGen, ty: Ast.TypeExpr, ns: ?[]const u8, target: Target, visited: *std.StringHashMap(void)) Error!bool {
+    switch (ty) {
+        .option => |o| return self.aliasReaches(o.child, ns, target, visited),
+        .array => |a| return self.aliasReaches(a.getElement(), ns, target, visited),
+        .named => |n| return self.aliasReachesDef(ns, n.name, target, visited),
+        .qualified => |q| return self.aliasReachesDef(q.namespace, q.name, target, visited),
+        else => return false,
+    }
+}
+
+fn aliasReachesDef(self: *RsGen, ns: ?[]const u8, name: []const u8, target: Target, visited: *std.StringHashMap(void)) Error!bool {
+    if (try self.typeMapping(ns, name) != null) return false;
+    if (sameNamespace(ns, target.ns) and std.mem.eql(u8, name, target.name)) return true;
+    const key = try self.configName(ns, name);
+    if (visited.contains(key)) return false;
+    try visited.put(key, {});
+    const def = self.lookupDef(ns, name) orelse return false;
+    switch (def.ty) {
+        .struct_, .enum_, .union_ => return false,
+        else => return self.aliasReaches(def.ty, ns, target, visited),
+    }
+}
+
 /// Check if all fields of a struct can derive Default.
 /// Primitives, strings, options, and arrays all have Default.
-/// Enums, unions, and named types (which may be enums/unions) do not.
-fn canDeriveDefault(self: *RsGen, def: *const Ast.StructDef) bool {
+/// Enums, unions, and named types (which may be enums/unions) do not, and
+/// neither do fields mapped to a configured Rust type.
+fn canDeriveDefault(self: *RsGen, ns: ?[]const u8, name: []const u8, def: *const Ast.StructDef) Error!bool {
     for (def.fields) |field| {
-        if (!self.typeHasDefault(field.ty)) return false;
+        if (try self.fieldMapping(ns, name, field.name) != null) return false;
+        if (try self.fieldEncoded(ns, name, field.name)) return false;
+        if (!try self.typeHasDefault(field.ty)) return false;
     }
     return true;
 }
 
-fn typeHasDefault(self: *RsGen, ty: Ast.TypeExpr) bool {
+fn typeHasDefault(self: *RsGen, ty: Ast.TypeExpr) Error!bool {
     return switch (ty) {
         .bool, .int, .float, .string, .option, .array => true,
         .struct_ => |s| {
             // Inline structs derive Default if all their fields do
-            for (s.fields) |f| {
-                if (!self.typeHasDefault(f.ty)) return false;
-            }
-            return true;
+            const name = self.inline_struct_names.get(s) orelse return false;
+            return self.canDeriveDefault(self.inline_owner.get(s) orelse null, name, s);
         },
         .enum_, .union_ => false,
         .named => |n| {
+            if (try self.typeMapping(self.scope_ns, n.name) != null) return false;
             if (self.resolveNamedTypeDef(n.name)) |def| {
                 return switch (def.ty) {
-                    .struct_ => |s| {
-                        for (s.fields) |f| {
-                            if (!self.typeHasDefault(f.ty)) return false;
-                        }
-                        return true;
-                    },
+                    .struct_ => |s| self.canDeriveDefault(self.scope_ns, def.name, s),
                     .enum_, .union_ => false,
-                    else => true, // aliases to primitives
+                    else => self.typeHasDefault(def.ty), // aliases
                 };
             }
             return false;
         },
         .qualified => |q| {
+            if (try self.typeMapping(q.namespace, q.name) != null) return false;
             if (self.resolveQualified(q.namespace, q.name)) |def| {
+                const saved = self.enterScope(q.namespace);
+                defer self.leaveScope(saved);
                 return switch (def.ty) {
-                    .struct_ => |s| {
-                        for (s.fields) |f| {
-                            if (!self.typeHasDefault(f.ty)) return false;
-                        }
-                        return true;
-                    },
+                    .struct_ => |s| self.canDeriveDefault(q.namespace, def.name, s),
                     .enum_, .union_ => false,
-                    else => true,
+                    else => self.typeHasDefault(def.ty),
                 };
             }
             return false;
@@ -1341,3 +2587,48 @@ fn rustSafeIdent(arena: std.mem.Allocator, name: []const u8) Error![]const u8 {
     }
     return name;
 }
+
+// =========================================================================
+// Tests
+// =========================================================================
+
+fn expectCyclicAlias(source: []const u8, expected: ?[]const u8) !void {
+    var result = parser.parse(std.testing.allocator, source);
+    defer result.deinit();
+    try std.testing.expect(!result.hasErrors());
+
+    var arena = std.heap.ArenaAllocator.init(std.testing.allocator);
+    defer arena.deinit();
+    const imports = std.StringHashMap(Ast.Schema).init(arena.allocator());
+    var gen = RsGen.init(std.io.null_writer.any(), result.schema.?, imports, arena.allocator(), .{});
+    const found = try gen.findCyclicAlias();
+    if (expected) |name| {
+        try std.testing.expectEqualStrings(name, found orelse return error.TestExpectedCycle);
+    } else {
+        try std.testing.expect(found == null);
+    }
+}
+
+test "findCyclicAlias - array of itself" {
+    try expectCyclicAlias("T = []T", "T");
+}
+
+test "findCyclicAlias - optional of itself" {
+    try expectCyclicAlias("T = ?T", "T");
+}
+
+test "findCyclicAlias - through another alias" {
+    try expectCyclicAlias(
+        \\A = []B
+        \\B = ?A
+    , "A");
+}
+
+test "findCyclicAlias - struct ends the cycle" {
+    try expectCyclicAlias(
+        \\Tree = []Node
+        \\Node = struct {
+        \\    0 children: Tree
+        \\}
+    , null);
+}
@@ -0,0 +1,250 @@
+const std = @import("std");
+
+/// Extra derives and attributes for one generated type.
+pub const TypeConfig = struct {
+    /// Schema name of the type (`User`, an inline type's generated name such
+    /// as `UserAddress`, or `common.Status` for a type in an import).
+    name: []const u8,
+    derives: []const []const u8 = &.{},
+    attributes: []const []const u8 = &.{},
+};
+
+/// Replaces the Rust type of a struct field, or of every reference to a
+/// named type, with a user-provided one. Exactly one of `field` and
+/// `schema_type` is set, and exactly one of `rust_type` and `encoded`
+/// (which only applies to fields).
+pub const Mapping = struct {
+    /// `Type.field` using schema names (`common.Type.field` inside an import).
+    field: []const u8 = "",
+    /// A top-level type name (`Timestamp`, or `common.Id` for an import).
+    schema_type: []const u8 = "",
+    /// Rust type to use instead. It must implement the runtime's
+    /// `CbgConvert` with `Wire` set to the Rust type generated otherwise.
+    rust_type: []const u8 = "",
+    /// Keep the field as the runtime's `Encoded<T>`: its encoded bytes,
+    /// copied through without decoding. Not allowed with `--digest`, since
+    /// the bytes bypass the deterministic profile.
+    encoded: bool = false,
+};
+
+pub const Config = struct {
+    /// Path generated code uses for the runtime crate.
+    runtime_crate: []const u8 = "cboragen_runtime",
+    /// Visibility of struct fields (`pub`, `pub(crate)`, or empty for private).
+    field_visibility: []const u8 = "pub",
+    /// Derives and attributes added to every generated type.
+    derives: []const []const u8 = &.{},
+    attributes: []const []const u8 = &.{},
+    types: []const TypeConfig = &.{},
+    mappings: []const Mapping = &.{},
+
+    pub fn typeConfig(self: Config, name: []const u8) ?TypeConfig {
+        for (self.types) |t| {
+            if (std.mem.eql(u8, t.name, name)) return t;
+        }
+        return null;
+    }
+
+    pub fn fieldMapping(self: Config, field: []const u8) ?Mapping {
+        for (self.mappings) |m| {
+            if (std.mem.eql(u8, m.field, field)) return m;
+        }
+        return null;
+    }
+
+    pub fn typeMapping(self: Config, schema_type: []const u8) ?Mapping {
+        for (self.mappings) |m| {
+            if (std.mem.eql(u8, m.schema_type, schema_type)) return m;
+        }
+        return null;
+    }
+};
+
+pub const ParseError = error{
+    InvalidFormat,
+    OutOfMemory,
+};
+
+const Section = enum { root, types, mappings };
+
+/// Parse a minimal TOML config file. `err_line` is set to the 1-based line
+/// of the first problem when parsing fails.
+///
+/// Supported format:
+/// ```toml
+/// runtime_crate = "cboragen_runtime"
+/// field_visibility = "pub(crate)"
+/// derives = ["Hash", "Eq"]
+/// attributes = ['#[allow(clippy::large_enum_variant)]']
+///
+/// [[types]]
+/// name = "User"
+/// derives = ["PartialOrd"]
+/// attributes = ['#[serde(deny_unknown_fields)]']
+///
+/// [[mappings]]
+/// field = "User.createdAt"
+/// rust_type = "std::time::SystemTime"
+///
+/// [[mappings]]
+/// schema_type = "common.Id"
+/// rust_type = "crate::UserId"
+///
+/// [[mappings]]
+/// field = "Message.author"
+/// encoded = true
+/// ```
+pub fn parse(allocator: std.mem.Allocator, source: []const u8, err_line: *usize) ParseError!Config {
+    var cfg: Config = .{};
+    var types: std.ArrayList(TypeConfig) = .{};
+    var mappings: std.ArrayList(Mapping) = .{};
+    var section: Section = .root;
+
+    var line_no: usize = 0;
+    var line_iter = std.mem.splitScalar(u8, source, '\n');
+    while (line_iter.next()) |raw_line| {
+        line_no += 1;
+        err_line.* = line_no;
+        const line = std.mem.trim(u8, raw_line, &.{ ' ', '\t', '\r' });
+
+        // Skip empty lines and comments
+        if (line.len == 0) continue;
+        if (line[0] == '#') continue;
+
+        if (std.mem.eql(u8, line, "[[types]]")) {
+            try types.append(allocator, .{ .name = "" });
+            section = .types;
+            continue;
+        }
+        if (std.mem.eql(u8, line, "[[mappings]]")) {
+            try mappings.append(allocator, .{});
+            section = .mappings;
+            continue;
+        }
+
+        // key = value
+        const eq_idx = std.mem.indexOfScalar(u8, line, '=') orelse return error.InvalidFormat;
+        const key = std.mem.trim(u8, line[0..eq_idx], &.{ ' ', '\t' });
+        const val = std.mem.trim(u8, line[eq_idx + 1 ..], &.{ ' ', '\t' });
+
+        switch (section) {
+            .root => {
+                if (std.mem.eql(u8, key, "runtime_crate")) {
+                    cfg.runtime_crate = try parseString(allocator, val);
+                } else if (std.mem.eql(u8, key, "field_visibility")) {
+                    cfg.field_visibility = try parseString(allocator, val);
+                } else if (std.mem.eql(u8, key, "derives")) {
+                    cfg.derives = try parseStringArray(allocator, val);
+                } else if (std.mem.eql(u8, key, "attributes")) {
+                    cfg.attributes = try parseStringArray(allocator, val);
+                } else {
+                    return error.InvalidFormat;
+                }
+            },
+            .types => {
+                const entry = &types.items[types.items.len - 1];
+                if (std.mem.eql(u8, key, "name")) {
+                    entry.name = try parseString(allocator, val);
+                } else if (std.mem.eql(u8, key, "derives")) {
+                    entry.derives = try parseStringArray(allocator, val);
+                } else if (std.mem.eql(u8, key, "attributes")) {
+                    entry.attributes = try parseStringArray(allocator, val);
+                } else {
+                    return error.InvalidFormat;
+                }
+            },
+            .mappings => {
+                const entry = &mappings.items[mappings.items.len - 1];
+                if (std.mem.eql(u8, key, "field")) {
+                    entry.field = try parseString(allocator, val);
+                } else if (std.mem.eql(u8, key, "schema_type")) {
+                    entry.schema_type = try parseString(allocator, val);
+                } else if (std.mem.eql(u8, key, "rust_type")) {
+                    entry.rust_type = try parseString(allocator, val);
+                } else if (std.mem.eql(u8, key, "encoded")) {
+                    entry.encoded = try parseBool(val);
+                } else {
+                    return error.InvalidFormat;
+                }
+            },
+        }
+    }
+
+    err_line.* = 0;
+    for (types.items) |t| {
+        if (t.name.len == 0) return error.InvalidFormat;
+    }
+    for (mappings.items) |m| {
+        if ((m.field.len == 0) == (m.schema_type.len == 0) or (m.rust_type.len == 0) == !m.encoded) return error.InvalidFormat;
+        if (m.encoded and m.field.len == 0) return error.InvalidFormat;
+    }
+
+    cfg.types = try types.toOwnedSlice(allocator);
+    cfg.mappings = try mappings.toOwnedSlice(allocator);
+    return cfg;
+}
+
+/// Parse a whole value as one string.
+fn parseString(allocator: std.mem.Allocator, text: []const u8) ParseError![]const u8 {
+    var pos: usize = 0;
+    const s = try parseStringAt(allocator, text, &pos);
+    if (pos != text.len) return error.InvalidFormat;
+    return s;
+}
+
+fn parseBool(text: []const u8) ParseError!bool {
+    if (std.mem.eql(u8, text, "true")) return true;
+    if (std.mem.eql(u8, text, "false")) return false;
+    return error.InvalidFormat;
+}
+
+/// Parse `["a", 'b']`.
+fn parseStringArray(allocator: std.mem.Allocator, text: []const u8) ParseError![]const []const u8 {
+    if (text.len < 2 or text[0] != '[' or text[text.len - 1] != ']') return error.InvalidFormat;
+    const inner = text[1 .. text.len - 1];
+    var items: std.ArrayList([]const u8) = .{};
+    var pos: usize = 0;
+    while (true) {
+        skipSpaces(inner, &pos);
+        if (pos == inner.len) break;
+        try items.append(allocator, try parseStringAt(allocator, inner, &pos));
+        skipSpaces(inner, &pos);
+        if (pos == inner.len) break;
+        if (inner[pos] != ',') return error.InvalidFormat;
+        pos += 1;
+    }
+    return items.toOwnedSlice(allocator);
+}
+
+/// Parse a basic (`"..."`, with `\"` and `\\` escapes) or literal (`'...'`)
+/// string starting at `pos`, leaving `pos` after the closing quote.
+fn parseStringAt(allocator: std.mem.Allocator, text: []const u8, pos: *usize) ParseError![]const u8 {
+    if (pos.* >= text.len) return error.InvalidFormat;
+    const quote = text[pos.*];
+    if (quote != '"' and quote != '\'') return error.InvalidFormat;
+    pos.* += 1;
+
+    var out: std.ArrayList(u8) = .{};
+    while (pos.* < text.len) : (pos.* += 1) {
+        const c = text[pos.*];
+        if (c == quote) {
+            pos.* += 1;
+            return out.toOwnedSlice(allocator);
+        }
+        if (c == '\\' and quote == '"') {
+            pos.* += 1;
+            if (pos.* >= text.len) return error.InvalidFormat;
+            switch (text[pos.*]) {
+                '"', '\\' => try out.append(allocator, text[pos.*]),
+                else => return error.InvalidFormat,
+            }
+        } else {
+            try out.append(allocator, c);
+        }
+    }
+    return error.InvalidFormat;
+}
+
+fn skipSpaces(text: []const u8, pos: *usize) void {
+    while (pos.* < text.len and (text[pos.*] == ' ' or text[pos.*] == '\t')) pos.* += 1;
+}
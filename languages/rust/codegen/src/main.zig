@@ -1,6 +1,8 @@
 const std = @import("std");
 const parser = @import("parser");
 const RsGen = @import("RsGen.zig");
+const Descriptor = @import("Descriptor.zig");
+const config = @import("config.zig");
 
 pub fn main() !void {
     var gpa = std.heap.GeneralPurposeAllocator(.{}){};
@@ -11,11 +13,47 @@ pub fn main() !void {
     defer std.process.argsFree(allocator, args);
 
     var filename: ?[]const u8 = null;
-
-    for (args[1..]) |arg| {
+    var emit_descriptor = false;
+    var use_color = true;
+    var config_path: ?[]const u8 = null;
+    var options: RsGen.Options = .{};
+
+    var i: usize = 1;
+    while (i < args.len) : (i += 1) {
+        const arg = args[i];
         if (std.mem.eql(u8, arg, "--help") or std.mem.eql(u8, arg, "-h")) {
             try printUsage();
             return;
+        } else if (std.mem.eql(u8, arg, "--emit-descriptor")) {
+            emit_descriptor = true;
+        } else if (std.mem.eql(u8, arg, "--json")) {
+            options.json = true;
+        } else if (std.mem.eql(u8, arg, "--serde")) {
+            options.serde = true;
+        } else if (std.mem.eql(u8, arg, "--newtypes")) {
+            options.newtypes = true;
+        } else if (std.mem.eql(u8, arg, "--builders")) {
+            options.builders = true;
+        } else if (std.mem.eql(u8, arg, "--non-exhaustive")) {
+            options.non_exhaustive = true;
+        } else if (std.mem.eql(u8, arg, "--decode-into")) {
+            options.decode_into = true;
+        } else if (std.mem.eql(u8, arg, "--lazy")) {
+            options.lazy = true;
+        } else if (std.mem.eql(u8, arg, "--digest")) {
+            options.digest = true;
+        } else if (std.mem.eql(u8, arg, "--half")) {
+            options.half = true;
+        } else if (std.mem.eql(u8, arg, "--no-color")) {
+            use_color = false;
+        } else if (std.mem.eql(u8, arg, "--config") or std.mem.eql(u8, arg, "-c")) {
+            i += 1;
+            if (i >= args.len) {
+                const stderr = std.fs.File.stderr().deprecatedWriter();
+                try stderr.writeAll("error: --config requires a value\n");
+                std.process.exit(1);
+            }
+            config_path = args[i];
         } else if (arg.len > 0 and arg[0] == '-') {
             const stderr = std.fs.File.stderr().deprecatedWriter();
             try stderr.print("unknown option: {s}\n", .{arg});
@@ -42,7 +80,7 @@ pub fn main() !void {
 
     if (result.hasErrors()) {
         const stderr = std.fs.File.stderr().deprecatedWriter();
-        try parser.renderDiagnostics(stderr.any(), source, file_path, result.diagnostics.slice(), true);
+        try parser.renderDiagnostics(stderr.any(), source, file_path, result.diagnostics.slice(), use_color);
         std.process.exit(1);
     }
 
@@ -69,10 +107,66 @@ pub fn main() !void {
     }
 
     const base_dir = std.fs.path.dirname(file_path) orelse ".";
-    try resolveImports(allocator, &imports, &import_results, &import_sources, base_dir, schema.imports);
+    var import_failed = false;
+    try resolveImports(allocator, &imports, &import_results, &import_sources, base_dir, schema.imports, use_color, &import_failed);
+    if (import_failed) std.process.exit(1);
+
+    // Load config file if provided
+    if (config_path) |cp| {
+        // Allocate on arena so string slices in parsed config stay valid
+        const config_source = std.fs.cwd().readFileAlloc(gen_arena.allocator(), cp, 1 * 1024 * 1024) catch |err| {
+            const stderr = std.fs.File.stderr().deprecatedWriter();
+            try stderr.print("error: cannot read config '{s}': {s}\n", .{ cp, @errorName(err) });
+            std.process.exit(1);
+        };
+
+        var err_line: usize = 0;
+        options.config = config.parse(gen_arena.allocator(), config_source, &err_line) catch {
+            const stderr = std.fs.File.stderr().deprecatedWriter();
+            if (err_line > 0) {
+                try stderr.print("error: invalid config file '{s}' at line {d}\n", .{ cp, err_line });
+            } else {
+                try stderr.print("error: invalid config file '{s}': [[types]] needs a name, [[mappings]] one of field or schema_type and one of rust_type or encoded (fields only)\n", .{cp});
+            }
+            std.process.exit(1);
+        };
+    }
+
+    // Encoded fields are copied as is, so they would bypass the deterministic profile
+    if (options.digest) {
+        for (options.config.mappings) |m| {
+            if (!m.encoded) continue;
+            const stderr = std.fs.File.stderr().deprecatedWriter();
+            try stderr.print("error: --digest cannot be used with encoded field '{s}': its bytes are not re-encoded deterministically\n", .{m.field});
+            std.process.exit(1);
+        }
+    }
 
     const stdout = std.fs.File.stdout().deprecatedWriter();
-    var gen = RsGen.init(stdout.any(), schema, imports, gen_arena.allocator());
+    if (emit_descriptor) {
+        var desc = Descriptor.init(stdout.any(), schema, imports);
+        desc.emit() catch |err| {
+            const stderr = std.fs.File.stderr().deprecatedWriter();
+            try stderr.print("error: descriptor emission failed: {s}\n", .{@errorName(err)});
+            std.process.exit(1);
+        };
+        return;
+    }
+
+    var gen = RsGen.init(stdout.any(), schema, imports, gen_arena.allocator(), options);
+    if (try gen.findInfiniteType()) |name| {
+        const stderr = std.fs.File.stderr().deprecatedWriter();
+        try stderr.print("error: type '{s}' contains itself with no optional, array or union variant to end the recursion\n", .{name});
+        std.process.exit(1);
+    }
+    // Newtypes are structs, so only plain `pub type` aliases can't recurse
+    if (!options.newtypes) {
+        if (try gen.findCyclicAlias()) |name| {
+            const stderr = std.fs.File.stderr().deprecatedWriter();
+            try stderr.print("error: alias '{s}' refers to itself, which a Rust type alias cannot do; use --newtypes\n", .{name});
+            std.process.exit(1);
+        }
+    }
     gen.generate() catch |err| {
         const stderr = std.fs.File.stderr().deprecatedWriter();
         try stderr.print("error: code generation failed: {s}\n", .{@errorName(err)});
@@ -87,6 +181,8 @@ fn resolveImports(
     import_sources: *std.ArrayList([]const u8),
     base_dir: []const u8,
     schema_imports: []const parser.Ast.Import,
+    use_color: bool,
+    failed: *bool,
 ) !void {
     for (schema_imports) |imp| {
         if (imports.contains(imp.namespace)) continue;
@@ -96,7 +192,8 @@ fn resolveImports(
 
         const imp_source = std.fs.cwd().readFileAlloc(allocator, import_path, 10 * 1024 * 1024) catch |err| {
             const stderr = std.fs.File.stderr().deprecatedWriter();
-            stderr.print("warning: cannot read import '{s}': {s}\n", .{ imp.path, @errorName(err) }) catch {};
+            stderr.print("error: cannot read import '{s}': {s}\n", .{ imp.path, @errorName(err) }) catch {};
+            failed.* = true;
             continue;
         };
         try import_sources.append(allocator, imp_source);
@@ -104,8 +201,9 @@ fn resolveImports(
         var imp_result = parser.parse(allocator, imp_source);
         if (imp_result.hasErrors()) {
             const stderr = std.fs.File.stderr().deprecatedWriter();
-            parser.renderDiagnostics(stderr.any(), imp_source, imp.path, imp_result.diagnostics.slice(), true) catch {};
+            parser.renderDiagnostics(stderr.any(), imp_source, imp.path, imp_result.diagnostics.slice(), use_color) catch {};
             imp_result.deinit();
+            failed.* = true;
             continue;
         }
 
@@ -114,7 +212,7 @@ fn resolveImports(
             try import_results.append(allocator, imp_result);
 
             const imp_base_dir = std.fs.path.dirname(import_path) orelse ".";
-            try resolveImports(allocator, imports, import_results, import_sources, imp_base_dir, imp_schema.imports);
+            try resolveImports(allocator, imports, import_results, import_sources, imp_base_dir, imp_schema.imports, use_color, failed);
         } else {
             imp_result.deinit();
         }
@@ -130,6 +228,18 @@ fn printUsage() !void {
         \\cboragen schema file. Output is written to stdout.
         \\
         \\Options:
+        \\  --emit-descriptor    Write the binary schema descriptor instead of Rust code
+        \\  --json               Emit to_json/from_json (needs the runtime's json feature)
+        \\  --serde              Derive serde Serialize/Deserialize (needs the runtime's serde feature)
+        \\  --newtypes           Emit type aliases as newtype structs
+        \\  --builders           Emit a builder with validated build() for each struct
+        \\  --non-exhaustive     Mark generated types #[non_exhaustive] (implies --builders)
+        \\  --decode-into        Emit decode_into on structs to reuse allocations when decoding
+        \\  --lazy               Emit a lazy view per struct that decodes fields on demand
+        \\  --digest             Emit digest() hashing the deterministic encoding (needs the runtime's digest feature)
+        \\  --half               Map f16 to half::f16 instead of f32 (needs the runtime's half feature)
+        \\  --config, -c <file>  Path to a TOML config with derives, visibility, and type mappings
+        \\  --no-color           Disable ANSI colors in diagnostics
         \\  --help, -h           Show this help
         \\
     );
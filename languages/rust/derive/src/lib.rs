@@ -0,0 +1,604 @@
+//! `#[derive(Encode, Decode)]` for cboragen-compatible encoders.
+//!
+//! The generated code follows what `cboragen-rs` emits for the equivalent
+//! schema, on top of `cboragen_runtime::{Writer, Reader}`:
+//!
+//! - structs are arrays indexed by field rank, with null gaps and trailing
+//!   fields skipped or defaulted on decode;
+//! - enums whose variants are all units are varints;
+//! - enums with payloads are unions: unit variants are varints, newtype
+//!   variants are tagged payloads, struct variants are tagged inline structs.
+//!
+//! Attributes:
+//!
+//! - `#[cbg(field = 3)]` sets a field's rank (default: declaration order);
+//! - `#[cbg(tag = 2)]` sets a variant's tag (default: declaration order);
+//! - `#[cbg(varint)]` encodes a `u64`/`i64` as `uvarint`/`ivarint`;
+//! - `#[cbg(f16)]` encodes an `f32` as `f16`;
+//! - `#[cbg(external_len = "count")]` encodes a `Vec<T>` as `[.count]T`.
+//!
+//! `Vec<u8>` and `[u8; N]` are byte strings, other `Vec<T>` and `[T; N]` are
+//! arrays, `Option<T>` is an optional, and any other type is encoded through
+//! its `Encode`/`Decode` impl.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+
+use proc_macro2::{Literal, TokenStream};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Expr, Fields, GenericArgument, Ident, LitInt, LitStr, PathArguments, Type};
+
+#[proc_macro_derive(Encode, attributes(cbg))]
+pub fn derive_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input, encode_impl).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+#[proc_macro_derive(Decode, attributes(cbg))]
+pub fn derive_decode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    expand(&input, decode_impl).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+fn expand(input: &DeriveInput, gen: fn(&DeriveInput, &Shape) -> TokenStream) -> syn::Result<TokenStream> {
+    let shape = match &input.data {
+        Data::Struct(s) => Shape::Struct(parse_fields(&s.fields, |i, _| quote!(&self.#i))?),
+        Data::Enum(e) => Shape::Enum(parse_variants(input, e)?),
+        Data::Union(_) => return Err(syn::Error::new(input.span(), "cboragen cannot derive for Rust unions")),
+    };
+    Ok(gen(input, &shape))
+}
+
+// === Model ===
+
+enum Shape {
+    Struct(Vec<FieldInfo>),
+    Enum(Vec<VariantInfo>),
+}
+
+struct FieldInfo {
+    member: syn::Member,
+    /// Local the decoder reads into (and the encoder binds in enum patterns).
+    local: Ident,
+    /// Expression of type `&T` the encoder reads from.
+    access: TokenStream,
+    rank: u64,
+    rust_ty: Type,
+    ty: Ty,
+}
+
+struct VariantInfo {
+    ident: Ident,
+    tag: u64,
+    kind: VariantKind,
+}
+
+enum VariantKind {
+    Unit,
+    Newtype(Box<Ty>),
+    Struct(Vec<FieldInfo>),
+}
+
+/// A field type as the wire format sees it.
+enum Ty {
+    Bool,
+    String,
+    /// Fixed-width integer; the name selects `write_*`/`read_*`.
+    Int(&'static str),
+    UVarint,
+    IVarint,
+    /// `f16`, `f32` or `f64`
+    Float(&'static str),
+    Bytes,
+    FixedBytes(Expr),
+    Option(Box<Ty>),
+    Vec(Box<Ty>),
+    External(Box<Ty>),
+    /// `[T; N]`, with the full Rust type for the final conversion.
+    Array(Box<Ty>, Expr, Type),
+    Other(Type),
+}
+
+/// Field-level `#[cbg(...)]` options; the flags record whether they applied.
+#[derive(Default)]
+struct FieldAttrs {
+    rank: Option<u64>,
+    varint: Option<Cell<bool>>,
+    f16: Option<Cell<bool>>,
+    external_len: Option<LitStr>,
+}
+
+// === Parsing ===
+
+fn parse_field_attrs(attrs: &[syn::Attribute]) -> syn::Result<FieldAttrs> {
+    let mut out = FieldAttrs::default();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("cbg")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("field") {
+                out.rank = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            } else if meta.path.is_ident("varint") {
+                out.varint = Some(Cell::new(false));
+            } else if meta.path.is_ident("f16") {
+                out.f16 = Some(Cell::new(false));
+            } else if meta.path.is_ident("external_len") {
+                out.external_len = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected `field`, `varint`, `f16` or `external_len`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(out)
+}
+
+fn parse_tag(attrs: &[syn::Attribute]) -> syn::Result<Option<u64>> {
+    let mut tag = None;
+    for attr in attrs.iter().filter(|a| a.path().is_ident("cbg")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("tag") {
+                tag = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `tag`"))
+            }
+        })?;
+    }
+    Ok(tag)
+}
+
+/// Parse struct (or struct variant) fields. `access` builds the encoder's
+/// `&T` expression from the field member and its decoder local.
+fn parse_fields(fields: &Fields, access: impl Fn(&syn::Member, &Ident) -> TokenStream) -> syn::Result<Vec<FieldInfo>> {
+    let mut out = Vec::new();
+    let mut ranks = BTreeMap::new();
+    for (idx, field) in fields.iter().enumerate() {
+        let attrs = parse_field_attrs(&field.attrs)?;
+        let rank = attrs.rank.unwrap_or(idx as u64);
+        if let Some(other) = ranks.insert(rank, idx) {
+            return Err(syn::Error::new(field.span(), format!("field rank {rank} is already used by field {other}")));
+        }
+        let (member, local) = match &field.ident {
+            Some(ident) => (syn::Member::Named(ident.clone()), format_ident!("_f_{}", ident)),
+            None => (syn::Member::Unnamed(idx.into()), format_ident!("_f_{}", idx)),
+        };
+        let ty = parse_field_type(field, &attrs)?;
+        if let Some(len_field) = &attrs.external_len {
+            let found = fields.iter().any(|f| f.ident.as_ref().is_some_and(|i| *i == len_field.value()));
+            if !found {
+                return Err(syn::Error::new(len_field.span(), format!("no field named `{}`", len_field.value())));
+            }
+        }
+        out.push(FieldInfo { access: access(&member, &local), member, local, rank, rust_ty: field.ty.clone(), ty });
+    }
+    Ok(out)
+}
+
+fn parse_field_type(field: &syn::Field, attrs: &FieldAttrs) -> syn::Result<Ty> {
+    let mut ty = parse_ty(&field.ty, attrs)?;
+    if let Some(cell) = &attrs.varint {
+        if !cell.get() {
+            return Err(syn::Error::new(field.ty.span(), "`varint` applies to u64 and i64"));
+        }
+    }
+    if let Some(cell) = &attrs.f16 {
+        if !cell.get() {
+            return Err(syn::Error::new(field.ty.span(), "`f16` applies to f32"));
+        }
+    }
+    if let Some(len_field) = &attrs.external_len {
+        ty = match ty {
+            Ty::Vec(elem) => Ty::External(elem),
+            // `[.field]u8` is a byte string, like `[]u8`
+            Ty::Bytes => Ty::Bytes,
+            _ => return Err(syn::Error::new(len_field.span(), "`external_len` applies to Vec<T>")),
+        };
+    }
+    Ok(ty)
+}
+
+fn parse_ty(ty: &Type, attrs: &FieldAttrs) -> syn::Result<Ty> {
+    match ty {
+        Type::Group(g) => parse_ty(&g.elem, attrs),
+        Type::Paren(p) => parse_ty(&p.elem, attrs),
+        Type::Array(a) => {
+            if is_u8(&a.elem) {
+                Ok(Ty::FixedBytes(a.len.clone()))
+            } else {
+                Ok(Ty::Array(Box::new(parse_ty(&a.elem, attrs)?), a.len.clone(), ty.clone()))
+            }
+        }
+        Type::Path(p) if p.qself.is_none() => {
+            let Some(seg) = p.path.segments.last() else {
+                return Ok(Ty::Other(ty.clone()));
+            };
+            let name = seg.ident.to_string();
+            match (&seg.arguments, name.as_str()) {
+                (PathArguments::None, "bool") => Ok(Ty::Bool),
+                (PathArguments::None, "String") => Ok(Ty::String),
+                (PathArguments::None, "u64") if take(&attrs.varint) => Ok(Ty::UVarint),
+                (PathArguments::None, "i64") if take(&attrs.varint) => Ok(Ty::IVarint),
+                (PathArguments::None, "f32") if take(&attrs.f16) => Ok(Ty::Float("f16")),
+                (PathArguments::None, "u8") => Ok(Ty::Int("u8")),
+                (PathArguments::None, "u16") => Ok(Ty::Int("u16")),
+                (PathArguments::None, "u32") => Ok(Ty::Int("u32")),
+                (PathArguments::None, "u64") => Ok(Ty::Int("u64")),
+                (PathArguments::None, "i8") => Ok(Ty::Int("i8")),
+                (PathArguments::None, "i16") => Ok(Ty::Int("i16")),
+                (PathArguments::None, "i32") => Ok(Ty::Int("i32")),
+                (PathArguments::None, "i64") => Ok(Ty::Int("i64")),
+                (PathArguments::None, "f32") => Ok(Ty::Float("f32")),
+                (PathArguments::None, "f64") => Ok(Ty::Float("f64")),
+                (PathArguments::AngleBracketed(args), "Option" | "Vec") if args.args.len() == 1 => {
+                    let GenericArgument::Type(inner) = &args.args[0] else {
+                        return Ok(Ty::Other(ty.clone()));
+                    };
+                    if name == "Option" {
+                        Ok(Ty::Option(Box::new(parse_ty(inner, attrs)?)))
+                    } else if is_u8(inner) {
+                        Ok(Ty::Bytes)
+                    } else {
+                        Ok(Ty::Vec(Box::new(parse_ty(inner, attrs)?)))
+                    }
+                }
+                _ => Ok(Ty::Other(ty.clone())),
+            }
+        }
+        _ => Ok(Ty::Other(ty.clone())),
+    }
+}
+
+/// Consume a `varint`/`f16` flag, recording that it applied.
+fn take(flag: &Option<Cell<bool>>) -> bool {
+    match flag {
+        Some(cell) => {
+            cell.set(true);
+            true
+        }
+        None => false,
+    }
+}
+
+fn is_u8(ty: &Type) -> bool {
+    matches!(ty, Type::Path(p) if p.qself.is_none() && p.path.is_ident("u8"))
+}
+
+fn parse_variants(input: &DeriveInput, data: &syn::DataEnum) -> syn::Result<Vec<VariantInfo>> {
+    if data.variants.is_empty() {
+        return Err(syn::Error::new(input.span(), "cboragen cannot derive for an enum with no variants"));
+    }
+    let mut out = Vec::new();
+    let mut tags = BTreeMap::new();
+    for (idx, variant) in data.variants.iter().enumerate() {
+        let tag = parse_tag(&variant.attrs)?.unwrap_or(idx as u64);
+        if let Some(other) = tags.insert(tag, variant.ident.clone()) {
+            return Err(syn::Error::new(variant.span(), format!("tag {tag} is already used by {other}")));
+        }
+        let kind = match &variant.fields {
+            Fields::Unit => VariantKind::Unit,
+            Fields::Unnamed(f) if f.unnamed.len() == 1 => {
+                let field = &f.unnamed[0];
+                let attrs = parse_field_attrs(&field.attrs)?;
+                VariantKind::Newtype(Box::new(parse_field_type(field, &attrs)?))
+            }
+            Fields::Named(_) => VariantKind::Struct(parse_fields(&variant.fields, |_, local| quote!(#local))?),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "union variants carry one payload; use a newtype or struct variant",
+                ))
+            }
+        };
+        out.push(VariantInfo { ident: variant.ident.clone(), tag, kind });
+    }
+    Ok(out)
+}
+
+// === Encode ===
+
+fn encode_impl(input: &DeriveInput, shape: &Shape) -> TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = match shape {
+        Shape::Struct(fields) => encode_struct(fields),
+        Shape::Enum(variants) => {
+            let arms = variants.iter().map(|v| {
+                let ident = &v.ident;
+                let tag = Literal::u64_unsuffixed(v.tag);
+                match &v.kind {
+                    VariantKind::Unit => quote! { Self::#ident => w.write_uvarint(#tag), },
+                    VariantKind::Newtype(ty) => {
+                        let enc = encode_expr(ty, quote!(_v));
+                        quote! { Self::#ident(_v) => { w.write_tag_header(#tag); #enc; } }
+                    }
+                    VariantKind::Struct(fields) => {
+                        let members = fields.iter().map(|f| &f.member);
+                        let locals = fields.iter().map(|f| &f.local);
+                        let enc = encode_struct(fields);
+                        quote! { Self::#ident { #(#members: #locals),* } => { w.write_tag_header(#tag); #enc } }
+                    }
+                }
+            });
+            quote! { match self { #(#arms)* } }
+        }
+    };
+    quote! {
+        impl #impl_generics ::cboragen_runtime::Encode for #name #ty_generics #where_clause {
+            fn encode_with(&self, w: &mut ::cboragen_runtime::Writer) {
+                #body
+            }
+        }
+    }
+}
+
+fn encode_struct(fields: &[FieldInfo]) -> TokenStream {
+    let Some(max_rank) = fields.iter().map(|f| f.rank).max() else {
+        return quote! { w.write_array_header(0); };
+    };
+    let len = Literal::u64_unsuffixed(max_rank + 1);
+    let slots = (0..=max_rank).map(|rank| match fields.iter().find(|f| f.rank == rank) {
+        Some(f) => {
+            let enc = encode_expr(&f.ty, f.access.clone());
+            quote! { #enc; }
+        }
+        None => quote! { w.write_null(); },
+    });
+    quote! {
+        w.write_array_header(#len);
+        #(#slots)*
+    }
+}
+
+/// Encode the value behind `access`, an expression of type `&T`.
+fn encode_expr(ty: &Ty, access: TokenStream) -> TokenStream {
+    match ty {
+        Ty::Bool => quote! { w.write_bool(*#access) },
+        Ty::String => quote! { w.write_string(#access) },
+        Ty::Int(kind) => {
+            let write = format_ident!("write_{}", kind);
+            quote! { w.#write(*#access) }
+        }
+        Ty::UVarint => quote! { w.write_uvarint(*#access) },
+        Ty::IVarint => quote! { w.write_ivarint(*#access) },
+        Ty::Float(kind) => {
+            let write = format_ident!("write_{}", kind);
+            quote! { w.#write(*#access) }
+        }
+        Ty::Bytes | Ty::FixedBytes(_) => quote! { w.write_bytes(#access) },
+        Ty::Option(inner) => {
+            let enc = encode_expr(inner, quote!(_v));
+            quote! {
+                match #access {
+                    Some(_v) => { w.write_tag_header(1); #enc; }
+                    None => w.write_byte(0x00),
+                }
+            }
+        }
+        Ty::Vec(elem) => {
+            let enc = encode_expr(elem, quote!(_item));
+            quote! {{
+                w.write_array_header((#access).len());
+                for _item in #access { #enc; }
+            }}
+        }
+        Ty::Array(elem, len, _) => {
+            let enc = encode_expr(elem, quote!(_item));
+            quote! {{
+                w.write_array_header(#len);
+                for _item in #access { #enc; }
+            }}
+        }
+        Ty::External(elem) => {
+            let enc = encode_expr(elem, quote!(_item));
+            quote! {{
+                w.write_indefinite_array_start();
+                for _item in #access { #enc; }
+                w.write_break();
+            }}
+        }
+        Ty::Other(_) => quote! { ::cboragen_runtime::Encode::encode_with(#access, w) },
+    }
+}
+
+// === Decode ===
+
+fn decode_impl(input: &DeriveInput, shape: &Shape) -> TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let (body, default) = match shape {
+        Shape::Struct(fields) => {
+            let dec = decode_struct(fields, quote!(Self));
+            (quote! { Ok(#dec) }, default_struct(fields, quote!(Self)))
+        }
+        Shape::Enum(variants) => (decode_enum(&name.to_string(), variants), default_variant(&variants[0])),
+    };
+    quote! {
+        impl #impl_generics ::cboragen_runtime::Decode for #name #ty_generics #where_clause {
+            fn decode_with(r: &mut ::cboragen_runtime::Reader) -> Result<Self, ::cboragen_runtime::DecodeError> {
+                r.enter()?;
+                let _v = (|| -> Result<Self, ::cboragen_runtime::DecodeError> { #body })();
+                r.leave();
+                _v
+            }
+
+            fn default_value() -> Self {
+                #default
+            }
+        }
+    }
+}
+
+/// A block expression reading a struct array and building `ctor { .. }`.
+fn decode_struct(fields: &[FieldInfo], ctor: TokenStream) -> TokenStream {
+    let Some(max_rank) = fields.iter().map(|f| f.rank).max() else {
+        return quote! {{
+            let _len = r.read_struct_header(0)?;
+            for _ in 0.._len { r.skip()?; }
+            #ctor {}
+        }};
+    };
+    let locals = fields.iter().map(|f| {
+        let (local, rust_ty, default) = (&f.local, &f.rust_ty, default_expr(&f.ty));
+        quote! { let mut #local: #rust_ty = #default; }
+    });
+    let slots = (0..=max_rank).map(|rank| {
+        let lit = Literal::u64_unsuffixed(rank);
+        match fields.iter().find(|f| f.rank == rank) {
+            Some(f) => {
+                let (local, dec) = (&f.local, decode_expr(&f.ty));
+                quote! { if _len > #lit { #local = #dec; } }
+            }
+            None => quote! { if _len > #lit { r.skip_gap()?; } },
+        }
+    });
+    let next = Literal::u64_unsuffixed(max_rank + 1);
+    let members = fields.iter().map(|f| &f.member);
+    let values = fields.iter().map(|f| &f.local);
+    quote! {{
+        let _len = r.read_struct_header(#next)?;
+        #(#locals)*
+        #(#slots)*
+        for _ in #next.._len { r.skip()?; }
+        #ctor { #(#members: #values),* }
+    }}
+}
+
+fn decode_enum(name: &str, variants: &[VariantInfo]) -> TokenStream {
+    let unknown = LitStr::new(&format!("unknown {name} tag {{}}"), proc_macro2::Span::call_site());
+    let unit_arms = variants.iter().filter(|v| matches!(v.kind, VariantKind::Unit)).map(|v| {
+        let (ident, tag) = (&v.ident, Literal::u64_unsuffixed(v.tag));
+        quote! { #tag => Ok(Self::#ident), }
+    });
+    if variants.iter().all(|v| matches!(v.kind, VariantKind::Unit)) {
+        return quote! {
+            match r.read_uvarint()? {
+                #(#unit_arms)*
+                _t => Err(::cboragen_runtime::DecodeError::InvalidData(format!(#unknown, _t))),
+            }
+        };
+    }
+
+    let payload_arms = variants.iter().filter_map(|v| {
+        let (ident, tag) = (&v.ident, Literal::u64_unsuffixed(v.tag));
+        match &v.kind {
+            VariantKind::Unit => None,
+            VariantKind::Newtype(ty) => {
+                let dec = decode_expr(ty);
+                Some(quote! { #tag => Ok(Self::#ident(#dec)), })
+            }
+            VariantKind::Struct(fields) => {
+                let dec = decode_struct(fields, quote!(Self::#ident));
+                Some(quote! { #tag => Ok(#dec), })
+            }
+        }
+    });
+    let expected = LitStr::new(&format!("expected union {name}, got major type {{}}"), proc_macro2::Span::call_site());
+    quote! {
+        match r.peek_major_type()? {
+            6 => {
+                let _tag = r.read_tag_header()?;
+                match _tag {
+                    #(#payload_arms)*
+                    _t => Err(::cboragen_runtime::DecodeError::InvalidData(format!(#unknown, _t))),
+                }
+            }
+            0 => match r.read_uvarint()? {
+                #(#unit_arms)*
+                _t => Err(::cboragen_runtime::DecodeError::InvalidData(format!(#unknown, _t))),
+            },
+            _maj => Err(::cboragen_runtime::DecodeError::InvalidData(format!(#expected, _maj))),
+        }
+    }
+}
+
+fn decode_expr(ty: &Ty) -> TokenStream {
+    match ty {
+        Ty::Bool => quote! { r.read_bool()? },
+        Ty::String => quote! { r.read_string()? },
+        Ty::Int(kind) | Ty::Float(kind) => {
+            let read = format_ident!("read_{}", kind);
+            quote! { r.#read()? }
+        }
+        Ty::UVarint => quote! { r.read_uvarint()? },
+        Ty::IVarint => quote! { r.read_ivarint()? },
+        Ty::Bytes => quote! { r.read_bytes()? },
+        Ty::FixedBytes(len) => quote! {{
+            let _b = r.read_bytes()?;
+            <[u8; #len]>::try_from(_b.as_slice()).map_err(|_| {
+                ::cboragen_runtime::DecodeError::InvalidData(format!("expected {} bytes, got {}", #len, _b.len()))
+            })?
+        }},
+        Ty::Option(inner) => {
+            let dec = decode_expr(inner);
+            quote! { if r.read_option_tag()? { Some(#dec) } else { None } }
+        }
+        Ty::Vec(elem) => {
+            let dec = decode_expr(elem);
+            quote! {{
+                let _n = r.read_array_header()?;
+                let mut _a = Vec::with_capacity(_n);
+                for _ in 0.._n { _a.push(#dec); }
+                _a
+            }}
+        }
+        Ty::Array(elem, len, rust_ty) => {
+            let dec = decode_expr(elem);
+            quote! {{
+                let _n = r.read_array_header()?;
+                if _n != #len {
+                    return Err(::cboragen_runtime::DecodeError::InvalidData(format!("expected array of {}, got {}", #len, _n)));
+                }
+                let mut _a = Vec::with_capacity(_n);
+                for _ in 0.._n { _a.push(#dec); }
+                match <#rust_ty>::try_from(_a) { Ok(_a) => _a, Err(_) => unreachable!() }
+            }}
+        }
+        Ty::External(elem) => {
+            let dec = decode_expr(elem);
+            quote! {{
+                r.read_indefinite_array_start()?;
+                let mut _a = Vec::new();
+                while !r.try_read_break()? { _a.push(#dec); }
+                _a
+            }}
+        }
+        Ty::Other(rust_ty) => quote! { <#rust_ty as ::cboragen_runtime::Decode>::decode_with(r)? },
+    }
+}
+
+// === Defaults ===
+
+fn default_struct(fields: &[FieldInfo], ctor: TokenStream) -> TokenStream {
+    let members = fields.iter().map(|f| &f.member);
+    let values = fields.iter().map(|f| default_expr(&f.ty));
+    quote! { #ctor { #(#members: #values),* } }
+}
+
+fn default_variant(v: &VariantInfo) -> TokenStream {
+    let ident = &v.ident;
+    match &v.kind {
+        VariantKind::Unit => quote! { Self::#ident },
+        VariantKind::Newtype(ty) => {
+            let value = default_expr(ty);
+            quote! { Self::#ident(#value) }
+        }
+        VariantKind::Struct(fields) => default_struct(fields, quote!(Self::#ident)),
+    }
+}
+
+fn default_expr(ty: &Ty) -> TokenStream {
+    match ty {
+        Ty::Bool => quote! { false },
+        Ty::String => quote! { String::new() },
+        Ty::Int(_) | Ty::UVarint | Ty::IVarint => quote! { 0 },
+        Ty::Float(_) => quote! { 0.0 },
+        Ty::Bytes | Ty::Vec(_) | Ty::External(_) => quote! { Vec::new() },
+        Ty::FixedBytes(len) => quote! { [0u8; #len] },
+        Ty::Option(_) => quote! { None },
+        Ty::Array(elem, _, _) => {
+            let value = default_expr(elem);
+            quote! { ::std::array::from_fn(|_| #value) }
+        }
+        Ty::Other(rust_ty) => quote! { <#rust_ty as ::cboragen_runtime::Decode>::default_value() },
+    }
+}
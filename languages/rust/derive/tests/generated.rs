@@ -0,0 +1,240 @@
+//! Derived encoders must produce the same bytes as `cboragen-rs` output for
+//! the equivalent schema (`languages/typescript/codegen/test/roundtrip.cbg`,
+//! generated into the benchmark crate).
+
+use cboragen_runtime::{Decode, DecodeError, Encode};
+
+#[allow(dead_code)]
+#[path = "../../benchmark/src/generated.rs"]
+mod generated;
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+struct Primitives {
+    b: bool,
+    u8v: u8,
+    u16v: u16,
+    u32v: u32,
+    u64v: u64,
+    i8v: i8,
+    i16v: i16,
+    i32v: i32,
+    i64v: i64,
+    f32v: f32,
+    f64v: f64,
+    #[cbg(varint)]
+    uvar: u64,
+    #[cbg(varint)]
+    ivar: i64,
+    str_: String,
+    bin: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+struct WithOptionals {
+    required: String,
+    maybe: Option<u32>,
+    maybe_str: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Encode, Decode)]
+enum Color {
+    Red,
+    Green,
+    Blue,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+enum Shape {
+    Circle(f64),
+    Rect { w: f64, h: f64 },
+    Point,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+struct Vec3 {
+    xyz: [f64; 3],
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+struct TimeSeries {
+    count: u32,
+    #[cbg(external_len = "count")]
+    timestamps: Vec<i64>,
+    #[cbg(external_len = "count")]
+    values: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+struct ColoredShape {
+    color: Color,
+    shape: Shape,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+struct Matrix {
+    rows: Vec<Vec<f64>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Encode, Decode)]
+struct Sparse {
+    #[cbg(field = 0)]
+    first: u32,
+    #[cbg(field = 5)]
+    second: String,
+    #[cbg(field = 10)]
+    third: bool,
+}
+
+/// Encode both values, compare bytes, and decode each side's bytes with the other.
+fn check<D, G>(derived: &D, generated: &G, gen_encode: fn(&G) -> Vec<u8>, gen_decode: fn(&[u8]) -> Result<G, DecodeError>)
+where
+    D: Encode + Decode + PartialEq + std::fmt::Debug,
+    G: PartialEq + std::fmt::Debug,
+{
+    let bytes = derived.encode();
+    assert_eq!(bytes, gen_encode(generated));
+    assert_eq!(&D::decode(&bytes).unwrap(), derived);
+    assert_eq!(&gen_decode(&bytes).unwrap(), generated);
+}
+
+#[test]
+fn primitives() {
+    let d = Primitives {
+        b: true,
+        u8v: 200,
+        u16v: 60000,
+        u32v: 4_000_000_000,
+        u64v: u64::MAX,
+        i8v: -100,
+        i16v: -30000,
+        i32v: -2_000_000_000,
+        i64v: i64::MIN,
+        f32v: 1.25,
+        f64v: -1.0e-3,
+        uvar: 300,
+        ivar: -300,
+        str_: "hello".into(),
+        bin: vec![0xde, 0xad],
+    };
+    let g = generated::Primitives {
+        b: d.b,
+        u8v: d.u8v,
+        u16v: d.u16v,
+        u32v: d.u32v,
+        u64v: d.u64v,
+        i8v: d.i8v,
+        i16v: d.i16v,
+        i32v: d.i32v,
+        i64v: d.i64v,
+        f32v: d.f32v,
+        f64v: d.f64v,
+        uvar: d.uvar,
+        ivar: d.ivar,
+        str_: d.str_.clone(),
+        bin: d.bin.clone(),
+    };
+    check(&d, &g, generated::Primitives::encode, generated::Primitives::decode);
+}
+
+#[test]
+fn optionals() {
+    let d = WithOptionals { required: "x".into(), maybe: Some(7), maybe_str: None };
+    let g = generated::WithOptionals { required: "x".into(), maybe: Some(7), maybe_str: None };
+    check(&d, &g, generated::WithOptionals::encode, generated::WithOptionals::decode);
+}
+
+#[test]
+fn unions_and_enums() {
+    let cases = [
+        (Color::Red, Shape::Circle(2.5), generated::Color::Red, generated::Shape::Circle(2.5)),
+        (
+            Color::Blue,
+            Shape::Rect { w: 1.0, h: 2.0 },
+            generated::Color::Blue,
+            generated::Shape::Rect(generated::ShapeRect { w: 1.0, h: 2.0 }),
+        ),
+        (Color::Green, Shape::Point, generated::Color::Green, generated::Shape::Point),
+    ];
+    for (color, shape, gcolor, gshape) in cases {
+        let d = ColoredShape { color, shape };
+        let g = generated::ColoredShape { color: gcolor, shape: gshape };
+        check(&d, &g, generated::ColoredShape::encode, generated::ColoredShape::decode);
+    }
+}
+
+#[test]
+fn arrays() {
+    let d = Vec3 { xyz: [1.0, 2.0, 3.0] };
+    let g = generated::Vec3 { xyz: vec![1.0, 2.0, 3.0] };
+    check(&d, &g, generated::Vec3::encode, generated::Vec3::decode);
+
+    let d = TimeSeries { count: 2, timestamps: vec![-1, 1], values: vec![0.5, 1.5] };
+    let g = generated::TimeSeries { count: 2, timestamps: vec![-1, 1], values: vec![0.5, 1.5] };
+    check(&d, &g, generated::TimeSeries::encode, generated::TimeSeries::decode);
+
+    let d = Matrix { rows: vec![vec![1.0], vec![], vec![2.0, 3.0]] };
+    let g = generated::Matrix { rows: d.rows.clone() };
+    check(&d, &g, generated::Matrix::encode, generated::Matrix::decode);
+}
+
+#[test]
+fn sparse_ranks() {
+    let d = Sparse { first: 1, second: "two".into(), third: true };
+    let g = generated::Sparse { first: 1, second: "two".into(), third: true };
+    check(&d, &g, generated::Sparse::encode, generated::Sparse::decode);
+}
+
+#[test]
+fn missing_and_unknown_fields() -> Result<(), DecodeError> {
+    // An older peer's shorter struct decodes with defaults
+    let short = [0x81, 0x1a, 0, 0, 0, 9];
+    assert_eq!(Sparse::decode(&short)?, Sparse { first: 9, second: String::new(), third: false });
+    // but not in strict mode, and optionals need tag 1
+    assert!(Sparse::decode_strict(&short).is_err());
+    assert_eq!(Matrix::decode_strict(&[0x81, 0x80])?, Matrix { rows: vec![] });
+    assert!(Matrix::decode_strict(&[0x82, 0x80, 0xf5]).is_err());
+    assert!(Option::<Sparse>::decode(&[0xc2, 0x80]).is_err());
+    assert_eq!(ColoredShape::default_value(), ColoredShape { color: Color::Red, shape: Shape::Circle(0.0) });
+
+    // A newer peer's extra fields are skipped
+    let mut long = Matrix { rows: vec![vec![1.0]] }.encode();
+    long[0] = 0x82;
+    long.push(0xf5);
+    assert_eq!(Matrix::decode(&long)?, Matrix { rows: vec![vec![1.0]] });
+    Ok(())
+}
+
+#[test]
+fn rejects_unknown_tags() {
+    assert!(Color::decode(&[0x03]).is_err());
+    assert!(Shape::decode(&[0xc5, 0xf5]).is_err());
+    assert!(Vec3::decode(&[0x81, 0x82, 0xfb, 0, 0, 0, 0, 0, 0, 0, 0, 0xfb, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+enum Reply {
+    #[cbg(tag = 4)]
+    Ok(#[cbg(f16)] f32),
+    #[cbg(tag = 9)]
+    Err,
+}
+
+#[test]
+fn explicit_tags() -> Result<(), DecodeError> {
+    assert_eq!(Reply::Ok(1.5).encode(), [0xc4, 0xf9, 0x3e, 0x00]);
+    assert_eq!(Reply::Err.encode(), [0x09]);
+    assert_eq!(Reply::decode(&[0xc4, 0xf9, 0x3e, 0x00])?, Reply::Ok(1.5));
+    assert_eq!(Reply::decode(&[0x09])?, Reply::Err);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct Node {
+    kids: Vec<Node>,
+}
+
+#[test]
+fn deep_nesting_is_an_error() {
+    let data = [0x81; 400_000];
+    assert!(matches!(Node::decode(&data), Err(DecodeError::DepthExceeded(_))));
+}
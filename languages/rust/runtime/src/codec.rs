@@ -0,0 +1,221 @@
+//! `Encode`/`Decode` traits for types with a cboragen wire encoding.
+//!
+//! `#[derive(Encode, Decode)]` (the `derive` feature) implements these for
+//! Rust-only types, producing the same bytes generated code does. Scalars,
+//! `String` and `Option<T>` implement them with their default schema type
+//! (`u64` is fixed-width; use `#[cbg(varint)]` on a field for `uvarint`).
+//! `Vec<T>` does not, since `Vec<u8>` and `Vec<T>` have different encodings;
+//! the derive handles vectors and arrays in field position itself.
+//!
+//! [`Encoded<T>`] holds the encoding of a `T` without decoding it, for
+//! caching encoded sub-messages or forwarding them unparsed.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::{DecodeError, Reader, ReaderMode, Writer};
+
+/// A type that can be written in the cboragen wire format.
+pub trait Encode {
+    fn encode_with(&self, w: &mut Writer);
+
+    fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        self.encode_with(&mut w);
+        w.finish()
+    }
+
+    /// SHA-256 of the value's deterministic encoding.
+    #[cfg(feature = "digest")]
+    fn digest(&self) -> [u8; 32] {
+        let mut w = Writer::deterministic();
+        self.encode_with(&mut w);
+        crate::digest::sha256(&w.finish())
+    }
+}
+
+/// A type that can be read from the cboragen wire format.
+pub trait Decode: Sized {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError>;
+
+    fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data);
+        Self::decode_with(&mut r)
+    }
+
+    /// Decode only the canonical encoding of a value, with no trailing bytes.
+    fn decode_strict(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::with_mode(data, ReaderMode::Strict);
+        let v = Self::decode_with(&mut r)?;
+        r.finish()?;
+        Ok(v)
+    }
+
+    /// The value a struct field of this type takes when the encoded struct
+    /// is too short to include it (zero, empty, or the first variant).
+    fn default_value() -> Self;
+}
+
+macro_rules! scalar {
+    ($ty:ty, $write:ident, $read:ident, $default:expr) => {
+        impl Encode for $ty {
+            fn encode_with(&self, w: &mut Writer) {
+                w.$write(*self);
+            }
+        }
+
+        impl Decode for $ty {
+            fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+                r.$read()
+            }
+
+            fn default_value() -> Self {
+                $default
+            }
+        }
+    };
+}
+
+scalar!(bool, write_bool, read_bool, false);
+scalar!(u8, write_u8, read_u8, 0);
+scalar!(u16, write_u16, read_u16, 0);
+scalar!(u32, write_u32, read_u32, 0);
+scalar!(u64, write_u64, read_u64, 0);
+scalar!(i8, write_i8, read_i8, 0);
+scalar!(i16, write_i16, read_i16, 0);
+scalar!(i32, write_i32, read_i32, 0);
+scalar!(i64, write_i64, read_i64, 0);
+scalar!(f32, write_f32, read_f32, 0.0);
+scalar!(f64, write_f64, read_f64, 0.0);
+#[cfg(feature = "half")]
+scalar!(half::f16, write_half, read_half, half::f16::ZERO);
+
+impl Encode for String {
+    fn encode_with(&self, w: &mut Writer) {
+        w.write_string(self);
+    }
+}
+
+impl Decode for String {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        r.read_string()
+    }
+
+    fn default_value() -> Self {
+        String::new()
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode_with(&self, w: &mut Writer) {
+        match self {
+            Some(v) => {
+                w.write_tag_header(1);
+                v.encode_with(w);
+            }
+            None => w.write_byte(0x00),
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        if r.read_option_tag()? {
+            Ok(Some(T::decode_with(r)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn default_value() -> Self {
+        None
+    }
+}
+
+/// The encoding of one `T`, kept as bytes. Encoding copies them and decoding
+/// captures the item without parsing it into a `T`, so a value encoded once
+/// can be embedded in many messages, or a field passed through unread.
+/// Deterministic writers copy the bytes too, so they only follow the
+/// deterministic profile if they were written that way.
+pub struct Encoded<T> {
+    bytes: Vec<u8>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T> Encoded<T> {
+    /// Wrap the encoding of a `T`, checking that it is one well-formed item.
+    /// Whether it decodes as a `T` is checked only by [`Encoded::decode`].
+    pub fn from_raw(bytes: Vec<u8>) -> Result<Self, DecodeError> {
+        crate::validate_well_formed(&bytes)?;
+        Ok(Encoded { bytes, _type: PhantomData })
+    }
+
+    /// Capture the next item from `r`.
+    pub fn read(r: &mut Reader) -> Result<Self, DecodeError> {
+        Ok(Encoded { bytes: r.read_raw_item()?.to_vec(), _type: PhantomData })
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl<T: Encode> Encoded<T> {
+    pub fn new(value: &T) -> Self {
+        Encoded { bytes: value.encode(), _type: PhantomData }
+    }
+}
+
+impl<T: Decode> Encoded<T> {
+    pub fn decode(&self) -> Result<T, DecodeError> {
+        T::decode(&self.bytes)
+    }
+}
+
+impl<T> Encode for Encoded<T> {
+    fn encode_with(&self, w: &mut Writer) {
+        w.write_raw_item(&self.bytes);
+    }
+}
+
+impl<T: Encode + Decode> Decode for Encoded<T> {
+    fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        Self::read(r)
+    }
+
+    fn default_value() -> Self {
+        Self::new(&T::default_value())
+    }
+}
+
+// Manual impls, so they don't require `T` to implement the trait too
+impl<T> Clone for Encoded<T> {
+    fn clone(&self) -> Self {
+        Encoded { bytes: self.bytes.clone(), _type: PhantomData }
+    }
+}
+
+impl<T> fmt::Debug for Encoded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Encoded").field(&self.bytes).finish()
+    }
+}
+
+impl<T> PartialEq for Encoded<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.bytes == other.bytes
+    }
+}
+
+impl<T> Eq for Encoded<T> {}
+
+impl<T> Hash for Encoded<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.bytes.hash(state);
+    }
+}
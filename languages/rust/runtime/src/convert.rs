@@ -0,0 +1,86 @@
+//! `CbgConvert` for domain types stored as a schema type on the wire.
+//!
+//! The Rust generator's `[[mappings]]` config replaces a field's (or every
+//! reference to a named type's) Rust type with one implementing this trait.
+//! The bytes don't change: generated code encodes `to_wire()` and decodes
+//! through `from_wire()`.
+//!
+//! Implemented here for `SystemTime` (`u64` milliseconds since the Unix
+//! epoch) and, with the `uuid` feature, `uuid::Uuid` (`[16]u8`).
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::DecodeError;
+
+/// A Rust type stored on the wire as `Wire`, the Rust type generated code
+/// would otherwise use (`u64`, `String`, `Vec<u8>`, a generated struct, ...).
+pub trait CbgConvert: Sized {
+    type Wire;
+
+    fn to_wire(&self) -> Self::Wire;
+
+    fn from_wire(wire: Self::Wire) -> Result<Self, DecodeError>;
+
+    /// The value a struct field of this type takes when the encoded struct
+    /// is too short to include it.
+    fn default_value() -> Self;
+}
+
+/// Milliseconds since the Unix epoch. Times before the epoch encode as 0.
+impl CbgConvert for SystemTime {
+    type Wire = u64;
+
+    fn to_wire(&self) -> u64 {
+        self.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_millis().min(u64::MAX as u128) as u64)
+    }
+
+    fn from_wire(wire: u64) -> Result<Self, DecodeError> {
+        UNIX_EPOCH
+            .checked_add(Duration::from_millis(wire))
+            .ok_or_else(|| DecodeError::InvalidData(format!("timestamp {wire} out of range")))
+    }
+
+    fn default_value() -> Self {
+        UNIX_EPOCH
+    }
+}
+
+/// Stored as a `[16]u8` (generated as `Vec<u8>`).
+#[cfg(feature = "uuid")]
+impl CbgConvert for uuid::Uuid {
+    type Wire = Vec<u8>;
+
+    fn to_wire(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_wire(wire: Vec<u8>) -> Result<Self, DecodeError> {
+        uuid::Uuid::from_slice(&wire)
+            .map_err(|_| DecodeError::InvalidData(format!("expected 16 bytes for uuid, got {}", wire.len())))
+    }
+
+    fn default_value() -> Self {
+        uuid::Uuid::nil()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn system_time() {
+        let t = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        assert_eq!(t.to_wire(), 1_700_000_000_123);
+        assert_eq!(SystemTime::from_wire(1_700_000_000_123).unwrap(), t);
+        assert_eq!((UNIX_EPOCH - Duration::from_secs(1)).to_wire(), 0);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid() {
+        let id = uuid::Uuid::from_bytes([7; 16]);
+        assert_eq!(uuid::Uuid::from_wire(id.to_wire()).unwrap(), id);
+        assert!(uuid::Uuid::from_wire(vec![1, 2, 3]).is_err());
+    }
+}
@@ -0,0 +1,354 @@
+//! Schema descriptors — a runtime model of a cboragen schema.
+//!
+//! A descriptor carries the same information generated code is compiled
+//! from: every named type definition, with struct field ranks, enum and
+//! union tags, and array kinds. `cboragen-rs --emit-descriptor` serializes
+//! a schema (and its resolved imports) into the CBOR descriptor format
+//! documented in SPEC.md, which [`Schema::decode`] loads back.
+//!
+//! Definitions from imported schemas are named `namespace.Name`, and type
+//! references inside them are qualified the same way, so a descriptor is
+//! self-contained and every [`Type::Named`] resolves with [`Schema::get`].
+
+use crate::{DecodeError, Reader, Writer};
+
+/// A complete schema: all named type definitions, imports included.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Schema {
+    pub types: Vec<TypeDef>,
+}
+
+/// A named type definition: `Name = type`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeDef {
+    pub name: String,
+    pub ty: Type,
+}
+
+/// A type expression.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Bool,
+    String,
+    Int(IntKind),
+    Float(FloatKind),
+    Struct(Vec<Field>),
+    Enum(Vec<EnumVariant>),
+    Union(Vec<UnionVariant>),
+    Option(Box<Type>),
+    /// Variable-length array: `[]T`
+    Array(Box<Type>),
+    /// Fixed-length array: `[N]T`
+    FixedArray(u64, Box<Type>),
+    /// External-length array: `[.field]T`
+    ExternalArray(String, Box<Type>),
+    /// Reference to another definition in the same descriptor.
+    Named(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntKind {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    UVarint,
+    IVarint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatKind {
+    F16,
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub rank: u64,
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumVariant {
+    pub tag: u64,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnionVariant {
+    pub tag: u64,
+    pub name: String,
+    pub payload: Option<Type>,
+}
+
+impl Schema {
+    /// Look up a type definition by name (`Name` or `namespace.Name`).
+    pub fn get(&self, name: &str) -> Option<&TypeDef> {
+        self.types.iter().find(|def| def.name == name)
+    }
+
+    /// Follow `Named` references until reaching a structural type.
+    pub fn resolve<'s>(&'s self, ty: &'s Type) -> Result<&'s Type, DecodeError> {
+        let mut ty = ty;
+        // A chain longer than the number of definitions must be a cycle.
+        for _ in 0..=self.types.len() {
+            match ty {
+                Type::Named(name) => {
+                    ty = &self
+                        .get(name)
+                        .ok_or_else(|| DecodeError::InvalidData(format!("unknown type {name}")))?
+                        .ty;
+                }
+                _ => return Ok(ty),
+            }
+        }
+        Err(DecodeError::InvalidData("cyclic type alias in descriptor".into()))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        self.encode_with(&mut w);
+        w.finish()
+    }
+
+    pub fn encode_with(&self, w: &mut Writer) {
+        w.write_array_header(1);
+        w.write_array_header(self.types.len());
+        for def in &self.types {
+            w.write_array_header(2);
+            w.write_string(&def.name);
+            enc_type(w, &def.ty);
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, DecodeError> {
+        let mut r = Reader::new(data);
+        Self::decode_with(&mut r)
+    }
+
+    pub fn decode_with(r: &mut Reader) -> Result<Self, DecodeError> {
+        let len = r.read_array_header()?;
+        let mut types = Vec::new();
+        if len > 0 {
+            let n = r.read_array_header()?;
+            types.reserve(n.min(1024));
+            for _ in 0..n {
+                let def_len = r.read_array_header()?;
+                if def_len < 2 {
+                    return Err(DecodeError::InvalidData("truncated TypeDef in descriptor".into()));
+                }
+                let name = r.read_string()?;
+                let ty = dec_type(r)?;
+                for _ in 2..def_len { r.skip()?; }
+                types.push(TypeDef { name, ty });
+            }
+        }
+        for _ in 1..len { r.skip()?; }
+        Ok(Schema { types })
+    }
+}
+
+// === Descriptor wire format (see SPEC.md, "Schema Descriptors") ===
+
+fn enc_type(w: &mut Writer, ty: &Type) {
+    match ty {
+        Type::Bool => w.write_uvarint(0),
+        Type::String => w.write_uvarint(1),
+        Type::Int(kind) => {
+            w.write_tag_header(2);
+            w.write_uvarint(*kind as u64);
+        }
+        Type::Float(kind) => {
+            w.write_tag_header(3);
+            w.write_uvarint(*kind as u64);
+        }
+        Type::Struct(fields) => {
+            w.write_tag_header(4);
+            w.write_array_header(fields.len());
+            for f in fields {
+                w.write_array_header(3);
+                w.write_uvarint(f.rank);
+                w.write_string(&f.name);
+                enc_type(w, &f.ty);
+            }
+        }
+        Type::Enum(variants) => {
+            w.write_tag_header(5);
+            w.write_array_header(variants.len());
+            for v in variants {
+                w.write_array_header(2);
+                w.write_uvarint(v.tag);
+                w.write_string(&v.name);
+            }
+        }
+        Type::Union(variants) => {
+            w.write_tag_header(6);
+            w.write_array_header(variants.len());
+            for v in variants {
+                w.write_array_header(3);
+                w.write_uvarint(v.tag);
+                w.write_string(&v.name);
+                match &v.payload {
+                    Some(p) => { w.write_tag_header(1); enc_type(w, p); }
+                    None => w.write_byte(0x00),
+                }
+            }
+        }
+        Type::Option(child) => {
+            w.write_tag_header(7);
+            enc_type(w, child);
+        }
+        Type::Array(elem) => {
+            w.write_tag_header(8);
+            enc_type(w, elem);
+        }
+        Type::FixedArray(len, elem) => {
+            w.write_tag_header(9);
+            w.write_array_header(2);
+            w.write_uvarint(*len);
+            enc_type(w, elem);
+        }
+        Type::ExternalArray(len_field, elem) => {
+            w.write_tag_header(10);
+            w.write_array_header(2);
+            w.write_string(len_field);
+            enc_type(w, elem);
+        }
+        Type::Named(name) => {
+            w.write_tag_header(11);
+            w.write_string(name);
+        }
+    }
+}
+
+/// Types nest as deeply as the descriptor says, so they count against the
+/// reader's depth limit.
+fn dec_type(r: &mut Reader) -> Result<Type, DecodeError> {
+    r.enter()?;
+    let ty = dec_type_body(r);
+    r.leave();
+    ty
+}
+
+fn dec_type_body(r: &mut Reader) -> Result<Type, DecodeError> {
+    let maj = r.peek_byte()? >> 5;
+    if maj == 0 {
+        return match r.read_uvarint()? {
+            0 => Ok(Type::Bool),
+            1 => Ok(Type::String),
+            t => Err(DecodeError::InvalidData(format!("unknown Type tag {t}"))),
+        };
+    }
+    if maj != 6 {
+        return Err(DecodeError::InvalidData(format!("expected union Type, got major type {maj}")));
+    }
+    match r.read_tag_header()? {
+        2 => Ok(Type::Int(dec_int_kind(r)?)),
+        3 => Ok(Type::Float(dec_float_kind(r)?)),
+        4 => {
+            let n = r.read_array_header()?;
+            let mut fields = Vec::with_capacity(n.min(1024));
+            for _ in 0..n {
+                let len = r.read_array_header()?;
+                if len < 3 {
+                    return Err(DecodeError::InvalidData("truncated Field in descriptor".into()));
+                }
+                let rank = r.read_uvarint()?;
+                let name = r.read_string()?;
+                let ty = dec_type(r)?;
+                for _ in 3..len { r.skip()?; }
+                fields.push(Field { rank, name, ty });
+            }
+            Ok(Type::Struct(fields))
+        }
+        5 => {
+            let n = r.read_array_header()?;
+            let mut variants = Vec::with_capacity(n.min(1024));
+            for _ in 0..n {
+                let len = r.read_array_header()?;
+                if len < 2 {
+                    return Err(DecodeError::InvalidData("truncated EnumVariant in descriptor".into()));
+                }
+                let tag = r.read_uvarint()?;
+                let name = r.read_string()?;
+                for _ in 2..len { r.skip()?; }
+                variants.push(EnumVariant { tag, name });
+            }
+            Ok(Type::Enum(variants))
+        }
+        6 => {
+            let n = r.read_array_header()?;
+            let mut variants = Vec::with_capacity(n.min(1024));
+            for _ in 0..n {
+                let len = r.read_array_header()?;
+                if len < 2 {
+                    return Err(DecodeError::InvalidData("truncated UnionVariant in descriptor".into()));
+                }
+                let tag = r.read_uvarint()?;
+                let name = r.read_string()?;
+                let mut payload = None;
+                if len > 2 {
+                    payload = if r.read_option_tag()? { Some(dec_type(r)?) } else { None };
+                }
+                for _ in 3..len { r.skip()?; }
+                variants.push(UnionVariant { tag, name, payload });
+            }
+            Ok(Type::Union(variants))
+        }
+        7 => Ok(Type::Option(Box::new(dec_type(r)?))),
+        8 => Ok(Type::Array(Box::new(dec_type(r)?))),
+        9 => {
+            let len = r.read_array_header()?;
+            if len < 2 {
+                return Err(DecodeError::InvalidData("truncated FixedArray in descriptor".into()));
+            }
+            let n = r.read_uvarint()?;
+            let elem = dec_type(r)?;
+            for _ in 2..len { r.skip()?; }
+            Ok(Type::FixedArray(n, Box::new(elem)))
+        }
+        10 => {
+            let len = r.read_array_header()?;
+            if len < 2 {
+                return Err(DecodeError::InvalidData("truncated ExternalArray in descriptor".into()));
+            }
+            let len_field = r.read_string()?;
+            let elem = dec_type(r)?;
+            for _ in 2..len { r.skip()?; }
+            Ok(Type::ExternalArray(len_field, Box::new(elem)))
+        }
+        11 => Ok(Type::Named(r.read_string()?)),
+        t => Err(DecodeError::InvalidData(format!("unknown Type tag {t}"))),
+    }
+}
+
+fn dec_int_kind(r: &mut Reader) -> Result<IntKind, DecodeError> {
+    match r.read_uvarint()? {
+        0 => Ok(IntKind::U8),
+        1 => Ok(IntKind::U16),
+        2 => Ok(IntKind::U32),
+        3 => Ok(IntKind::U64),
+        4 => Ok(IntKind::I8),
+        5 => Ok(IntKind::I16),
+        6 => Ok(IntKind::I32),
+        7 => Ok(IntKind::I64),
+        8 => Ok(IntKind::UVarint),
+        9 => Ok(IntKind::IVarint),
+        t => Err(DecodeError::InvalidData(format!("unknown IntKind tag {t}"))),
+    }
+}
+
+fn dec_float_kind(r: &mut Reader) -> Result<FloatKind, DecodeError> {
+    match r.read_uvarint()? {
+        0 => Ok(FloatKind::F16),
+        1 => Ok(FloatKind::F32),
+        2 => Ok(FloatKind::F64),
+        t => Err(DecodeError::InvalidData(format!("unknown FloatKind tag {t}"))),
+    }
+}
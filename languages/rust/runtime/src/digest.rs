@@ -0,0 +1,71 @@
+//! Content hashes of deterministic encodings (the `digest` feature).
+//!
+//! A digest is the SHA-256 of a value's encoding under the deterministic
+//! profile in SPEC.md, written with `Writer::deterministic()`. Generated code
+//! built with `--digest` and types implementing `Encode` get a `digest()`
+//! method; two values have the same digest exactly when they encode to the
+//! same bytes, whichever cboragen runtime produced them.
+
+use sha2::{Digest, Sha256};
+
+/// SHA-256 of `data`.
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Encode, Writer};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn sha256_vectors() {
+        assert_eq!(hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+
+    // The vectors in SPEC.md, "Deterministic Encoding"
+    #[test]
+    fn deterministic_vectors() {
+        // struct { 0 x: f64, 2 n: uvarint, 3 name: string, 4 h: f16, 5 s: f32 }
+        // with every float NaN, the f64 one negative with a payload
+        let mut w = Writer::deterministic();
+        w.write_array_header(6);
+        w.write_f64(f64::from_bits(0xfff0_0000_0000_0001));
+        w.write_null();
+        w.write_uvarint(500);
+        w.write_string("cbor");
+        w.write_f16(-f32::NAN);
+        w.write_f32(f32::from_bits(0x7f80_0001));
+        let data = w.finish();
+        assert_eq!(hex(&data), "86fb7ff8000000000000f61901f46463626f72f97e00fa7fc00000");
+        assert_eq!(hex(&sha256(&data)), "2cd0c57e594a832b76a391294227b993d4e4ba9f2b776ba545c81bda6d4b0518");
+
+        // struct { 0 a: ?u32, 2 b: ?[]u32 } with a absent and b = [1, 2, 3]
+        let mut w = Writer::deterministic();
+        w.write_array_header(3);
+        w.write_byte(0x00);
+        w.write_null();
+        w.write_byte(0xc1);
+        w.write_array_header(3);
+        for v in 1..=3 {
+            w.write_u32(v);
+        }
+        let data = w.finish();
+        assert_eq!(hex(&data), "8300f6c1831a000000011a000000021a00000003");
+        assert_eq!(hex(&sha256(&data)), "dbcbd8185cb2b020aef2988080f3be3a4ddd2069423f9984f67c33dc87a75e6a");
+    }
+
+    #[test]
+    fn nan_payloads_share_a_digest() {
+        let quiet = f64::NAN;
+        let payload = f64::from_bits(0x7ff8_0000_dead_beef);
+        assert_ne!(quiet.encode(), payload.encode());
+        assert_eq!(quiet.digest(), payload.digest());
+        assert_ne!(0.0f64.digest(), (-0.0f64).digest());
+    }
+}
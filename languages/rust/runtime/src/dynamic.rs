@@ -0,0 +1,673 @@
+//! Descriptor-driven decoding and encoding of dynamic [`Value`]s.
+//!
+//! The interpreter follows the same wire rules as generated code: fixed-width
+//! integers at full width, structs as arrays indexed by field rank with null
+//! gaps, enums as varints, unions as tagged payloads or plain integers, and
+//! the three array kinds. Fields missing from a short struct array decode to
+//! the same defaults generated decoders use, so `decode_value` followed by
+//! `encode_value` reproduces what generated code would emit.
+
+use crate::descriptor::{FloatKind, IntKind, Schema, Type};
+use crate::{DecodeError, EncodeError, Reader, Writer};
+
+/// A decoded value whose shape is described by a schema [`Type`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    /// Unsigned integers (`u8`–`u64`, `uvarint`).
+    UInt(u64),
+    /// Signed integers (`i8`–`i64`, `ivarint`).
+    Int(i64),
+    Float(f64),
+    Text(String),
+    /// `[]u8`, `[N]u8` and `[.field]u8`.
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    /// Struct fields by schema name, in declaration order.
+    Struct(Vec<(String, Value)>),
+    /// Enum variant by schema name.
+    Enum(String),
+    /// Union variant by schema name, with its payload if the variant has one.
+    Union(String, Option<Box<Value>>),
+    Option(Option<Box<Value>>),
+}
+
+impl Value {
+    /// Look up a struct field by schema name.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+impl Schema {
+    /// Decode a value of the named type from `data`.
+    pub fn decode_value(&self, type_name: &str, data: &[u8]) -> Result<Value, DecodeError> {
+        let mut r = Reader::new(data);
+        self.read_value(&self.named(type_name)?, &mut r)
+    }
+
+    /// Encode a value of the named type.
+    pub fn encode_value(&self, type_name: &str, value: &Value) -> Result<Vec<u8>, EncodeError> {
+        let ty = self
+            .named(type_name)
+            .map_err(|e| EncodeError::InvalidValue(e.to_string()))?;
+        let mut w = Writer::new();
+        self.write_value(&ty, value, &mut w)?;
+        Ok(w.finish())
+    }
+
+    fn named(&self, type_name: &str) -> Result<Type, DecodeError> {
+        match self.get(type_name) {
+            Some(_) => Ok(Type::Named(type_name.to_string())),
+            None => Err(DecodeError::InvalidData(format!("unknown type {type_name}"))),
+        }
+    }
+
+    /// Decode a value of type `ty` from the reader.
+    pub fn read_value(&self, ty: &Type, r: &mut Reader) -> Result<Value, DecodeError> {
+        let ty = self.resolve(ty)?;
+        // Composite values count against the reader's depth limit, so a
+        // recursive schema can't be driven into a stack overflow
+        let nested = matches!(
+            ty,
+            Type::Struct(_) | Type::Union(_) | Type::Option(_) | Type::Array(_) | Type::FixedArray(..) | Type::ExternalArray(..)
+        );
+        if nested {
+            r.enter()?;
+        }
+        let v = self.read_resolved(ty, r);
+        if nested {
+            r.leave();
+        }
+        v
+    }
+
+    fn read_resolved(&self, ty: &Type, r: &mut Reader) -> Result<Value, DecodeError> {
+        match ty {
+            Type::Bool => Ok(Value::Bool(r.read_bool()?)),
+            Type::String => Ok(Value::Text(r.read_string()?)),
+            Type::Int(kind) => Ok(match kind {
+                IntKind::U8 => Value::UInt(r.read_u8()? as u64),
+                IntKind::U16 => Value::UInt(r.read_u16()? as u64),
+                IntKind::U32 => Value::UInt(r.read_u32()? as u64),
+                IntKind::U64 => Value::UInt(r.read_u64()?),
+                IntKind::I8 => Value::Int(r.read_i8()? as i64),
+                IntKind::I16 => Value::Int(r.read_i16()? as i64),
+                IntKind::I32 => Value::Int(r.read_i32()? as i64),
+                IntKind::I64 => Value::Int(r.read_i64()?),
+                IntKind::UVarint => Value::UInt(r.read_uvarint()?),
+                IntKind::IVarint => Value::Int(r.read_ivarint()?),
+            }),
+            Type::Float(kind) => Ok(Value::Float(match kind {
+                FloatKind::F16 => r.read_f16()? as f64,
+                FloatKind::F32 => r.read_f32()? as f64,
+                FloatKind::F64 => r.read_f64()?,
+            })),
+            Type::Struct(fields) => {
+                let max_rank = fields.iter().map(|f| f.rank).max();
+                let len = r.read_struct_header(max_rank.map_or(0, |m| m as usize + 1))?;
+                let mut slots: Vec<Option<Value>> = vec![None; fields.len()];
+                if let Some(max_rank) = max_rank {
+                    for rank in 0..=max_rank {
+                        if rank as usize >= len {
+                            break;
+                        }
+                        match fields.iter().position(|f| f.rank == rank) {
+                            Some(i) => slots[i] = Some(self.read_value(&fields[i].ty, r)?),
+                            None => r.skip_gap()?,
+                        }
+                    }
+                }
+                let known = max_rank.map_or(0, |m| m as usize + 1);
+                for _ in known..len { r.skip()?; }
+                let mut out = Vec::with_capacity(fields.len());
+                for (f, slot) in fields.iter().zip(slots) {
+                    let v = match slot {
+                        Some(v) => v,
+                        None => self.default_value(&f.ty)?,
+                    };
+                    out.push((f.name.clone(), v));
+                }
+                Ok(Value::Struct(out))
+            }
+            Type::Enum(variants) => {
+                let tag = r.read_uvarint()?;
+                match variants.iter().find(|v| v.tag == tag) {
+                    Some(v) => Ok(Value::Enum(v.name.clone())),
+                    None => Err(DecodeError::InvalidData(format!("unknown enum tag {tag}"))),
+                }
+            }
+            Type::Union(variants) => {
+                let maj = r.peek_major_type()?;
+                match maj {
+                    6 => {
+                        let tag = r.read_tag_header()?;
+                        match variants.iter().find(|v| v.tag == tag) {
+                            Some(v) => match &v.payload {
+                                Some(p) => Ok(Value::Union(v.name.clone(), Some(Box::new(self.read_value(p, r)?)))),
+                                None => Err(DecodeError::InvalidData(format!("union variant {} has no payload", v.name))),
+                            },
+                            None => Err(DecodeError::InvalidData(format!("unknown union tag {tag}"))),
+                        }
+                    }
+                    0 => {
+                        let tag = r.read_uvarint()?;
+                        match variants.iter().find(|v| v.tag == tag && v.payload.is_none()) {
+                            Some(v) => Ok(Value::Union(v.name.clone(), None)),
+                            None => Err(DecodeError::InvalidData(format!("unknown union tag {tag}"))),
+                        }
+                    }
+                    _ => Err(DecodeError::InvalidData(format!("expected union, got major type {maj}"))),
+                }
+            }
+            Type::Option(child) => {
+                if r.read_option_tag()? {
+                    Ok(Value::Option(Some(Box::new(self.read_value(child, r)?))))
+                } else {
+                    Ok(Value::Option(None))
+                }
+            }
+            Type::Array(elem) => {
+                if is_u8(elem) {
+                    return Ok(Value::Bytes(r.read_bytes()?));
+                }
+                let n = r.read_array_header()?;
+                let mut items = Vec::with_capacity(n.min(4096));
+                for _ in 0..n {
+                    items.push(self.read_value(elem, r)?);
+                }
+                Ok(Value::Array(items))
+            }
+            Type::FixedArray(len, elem) => {
+                if is_u8(elem) {
+                    let b = r.read_bytes()?;
+                    if b.len() as u64 != *len {
+                        return Err(DecodeError::InvalidData(format!("expected {len} bytes, got {}", b.len())));
+                    }
+                    return Ok(Value::Bytes(b));
+                }
+                let n = r.read_array_header()?;
+                if n as u64 != *len {
+                    return Err(DecodeError::InvalidData(format!("expected array of {len}, got {n}")));
+                }
+                let mut items = Vec::with_capacity(n.min(4096));
+                for _ in 0..n {
+                    items.push(self.read_value(elem, r)?);
+                }
+                Ok(Value::Array(items))
+            }
+            Type::ExternalArray(_, elem) => {
+                if is_u8(elem) {
+                    return Ok(Value::Bytes(r.read_bytes()?));
+                }
+                r.read_indefinite_array_start()?;
+                let mut items = Vec::new();
+                while !r.try_read_break()? {
+                    items.push(self.read_value(elem, r)?);
+                }
+                Ok(Value::Array(items))
+            }
+            Type::Named(_) => unreachable!("resolve() never returns Named"),
+        }
+    }
+
+    /// Encode `value` as type `ty`.
+    pub fn write_value(&self, ty: &Type, value: &Value, w: &mut Writer) -> Result<(), EncodeError> {
+        let ty = self.resolve(ty).map_err(|e| EncodeError::InvalidValue(e.to_string()))?;
+        match (ty, value) {
+            (Type::Bool, Value::Bool(b)) => w.write_bool(*b),
+            (Type::String, Value::Text(s)) => w.write_string(s),
+            (Type::Int(kind), v) => match kind {
+                IntKind::U8 => w.write_u8(int_value(v)?),
+                IntKind::U16 => w.write_u16(int_value(v)?),
+                IntKind::U32 => w.write_u32(int_value(v)?),
+                IntKind::U64 => w.write_u64(int_value(v)?),
+                IntKind::I8 => w.write_i8(int_value(v)?),
+                IntKind::I16 => w.write_i16(int_value(v)?),
+                IntKind::I32 => w.write_i32(int_value(v)?),
+                IntKind::I64 => w.write_i64(int_value(v)?),
+                IntKind::UVarint => w.write_uvarint(int_value(v)?),
+                IntKind::IVarint => w.write_ivarint(int_value(v)?),
+            },
+            (Type::Float(kind), Value::Float(f)) => match kind {
+                FloatKind::F16 => w.write_f16(*f as f32),
+                FloatKind::F32 => w.write_f32(*f as f32),
+                FloatKind::F64 => w.write_f64(*f),
+            },
+            (Type::Struct(fields), Value::Struct(values)) => {
+                if let Some((name, _)) = values.iter().find(|(n, _)| !fields.iter().any(|f| &f.name == n)) {
+                    return Err(EncodeError::InvalidValue(format!("unknown struct field {name}")));
+                }
+                match fields.iter().map(|f| f.rank).max() {
+                    None => w.write_array_header(0),
+                    Some(max_rank) => {
+                        w.write_array_header(max_rank as usize + 1);
+                        for rank in 0..=max_rank {
+                            match fields.iter().find(|f| f.rank == rank) {
+                                Some(f) => match values.iter().find(|(n, _)| n == &f.name) {
+                                    Some((_, v)) => self.write_value(&f.ty, v, w)?,
+                                    None => {
+                                        let v = self.default_value(&f.ty).map_err(|e| EncodeError::InvalidValue(e.to_string()))?;
+                                        self.write_value(&f.ty, &v, w)?;
+                                    }
+                                },
+                                None => w.write_null(),
+                            }
+                        }
+                    }
+                }
+            }
+            (Type::Enum(variants), Value::Enum(name)) => match variants.iter().find(|v| &v.name == name) {
+                Some(v) => w.write_uvarint(v.tag),
+                None => return Err(EncodeError::InvalidValue(format!("unknown enum variant {name}"))),
+            },
+            (Type::Union(variants), Value::Union(name, payload)) => {
+                let v = variants
+                    .iter()
+                    .find(|v| &v.name == name)
+                    .ok_or_else(|| EncodeError::InvalidValue(format!("unknown union variant {name}")))?;
+                match (&v.payload, payload) {
+                    (Some(ty), Some(p)) => {
+                        w.write_tag_header(v.tag);
+                        self.write_value(ty, p, w)?;
+                    }
+                    (None, None) => w.write_uvarint(v.tag),
+                    (Some(_), None) => return Err(EncodeError::InvalidValue(format!("union variant {name} requires a payload"))),
+                    (None, Some(_)) => return Err(EncodeError::InvalidValue(format!("union variant {name} has no payload"))),
+                }
+            }
+            (Type::Option(child), Value::Option(opt)) => match opt {
+                Some(v) => {
+                    w.write_tag_header(1);
+                    self.write_value(child, v, w)?;
+                }
+                None => w.write_byte(0x00),
+            },
+            (Type::Array(elem), v) => {
+                if is_u8(elem) {
+                    w.write_bytes(bytes_value(v)?);
+                } else {
+                    let items = array_value(v)?;
+                    w.write_array_header(items.len());
+                    for item in items {
+                        self.write_value(elem, item, w)?;
+                    }
+                }
+            }
+            // Empty is the default of a fixed array, written as `len` defaults
+            (Type::FixedArray(len, elem), v) if is_empty_array(v) && *len > 0 => {
+                if is_u8(elem) {
+                    w.write_bytes(&vec![0; *len as usize]);
+                } else {
+                    let item = self.default_value(elem).map_err(|e| EncodeError::InvalidValue(e.to_string()))?;
+                    w.write_array_header(*len as usize);
+                    for _ in 0..*len {
+                        self.write_value(elem, &item, w)?;
+                    }
+                }
+            }
+            (Type::FixedArray(len, elem), v) => {
+                if is_u8(elem) {
+                    let b = bytes_value(v)?;
+                    if b.len() as u64 != *len {
+                        return Err(EncodeError::InvalidValue(format!("expected {len} bytes, got {}", b.len())));
+                    }
+                    w.write_bytes(b);
+                } else {
+                    let items = array_value(v)?;
+                    if items.len() as u64 != *len {
+                        return Err(EncodeError::InvalidValue(format!("expected array of {len}, got {}", items.len())));
+                    }
+                    w.write_array_header(items.len());
+                    for item in items {
+                        self.write_value(elem, item, w)?;
+                    }
+                }
+            }
+            (Type::ExternalArray(_, elem), v) => {
+                if is_u8(elem) {
+                    w.write_bytes(bytes_value(v)?);
+                } else {
+                    w.write_indefinite_array_start();
+                    for item in array_value(v)? {
+                        self.write_value(elem, item, w)?;
+                    }
+                    w.write_break();
+                }
+            }
+            (ty, v) => {
+                return Err(EncodeError::InvalidValue(format!("value {v:?} does not match type {ty:?}")));
+            }
+        }
+        Ok(())
+    }
+
+    /// The value generated decoders assign to a field absent from the wire.
+    /// Errors for a type that contains itself with nothing to end the
+    /// recursion, which has no value at all.
+    pub fn default_value(&self, ty: &Type) -> Result<Value, DecodeError> {
+        self.default_in(ty, &mut Vec::new())
+    }
+
+    /// `default_value`, with `building` holding the named types whose
+    /// defaults are under construction.
+    fn default_in<'s>(&'s self, ty: &'s Type, building: &mut Vec<&'s str>) -> Result<Value, DecodeError> {
+        Ok(match ty {
+            Type::Named(name) => {
+                if building.contains(&name.as_str()) {
+                    return Err(DecodeError::InvalidData(format!("type {name} has no finite value")));
+                }
+                let def = self.get(name).ok_or_else(|| DecodeError::InvalidData(format!("unknown type {name}")))?;
+                building.push(name);
+                let v = self.default_in(&def.ty, building);
+                building.pop();
+                v?
+            }
+            Type::Bool => Value::Bool(false),
+            Type::String => Value::Text(String::new()),
+            Type::Int(kind) => match kind {
+                IntKind::I8 | IntKind::I16 | IntKind::I32 | IntKind::I64 | IntKind::IVarint => Value::Int(0),
+                _ => Value::UInt(0),
+            },
+            Type::Float(_) => Value::Float(0.0),
+            Type::Struct(fields) => {
+                let mut out = Vec::with_capacity(fields.len());
+                for f in fields {
+                    out.push((f.name.clone(), self.default_in(&f.ty, building)?));
+                }
+                Value::Struct(out)
+            }
+            Type::Enum(variants) => match variants.first() {
+                Some(v) => Value::Enum(v.name.clone()),
+                None => return Err(DecodeError::InvalidData("enum has no variants".into())),
+            },
+            Type::Union(variants) => {
+                if variants.is_empty() {
+                    return Err(DecodeError::InvalidData("union has no variants".into()));
+                }
+                // The first variant that doesn't lead back into a type being built
+                let v = variants
+                    .iter()
+                    .find(|v| v.payload.as_ref().is_none_or(|p| self.has_finite_value(p, building)))
+                    .unwrap_or(&variants[0]);
+                match &v.payload {
+                    Some(p) => Value::Union(v.name.clone(), Some(Box::new(self.default_in(p, building)?))),
+                    None => Value::Union(v.name.clone(), None),
+                }
+            }
+            Type::Option(_) => Value::Option(None),
+            // Fixed-length arrays too: generated decoders leave them empty
+            Type::Array(elem) | Type::FixedArray(_, elem) | Type::ExternalArray(_, elem) => {
+                if is_u8(elem) { Value::Bytes(Vec::new()) } else { Value::Array(Vec::new()) }
+            }
+        })
+    }
+
+    /// Whether `ty` has a value without going through a type in `visiting`;
+    /// options, arrays and unit union variants all end a recursion.
+    fn has_finite_value<'s>(&'s self, ty: &'s Type, visiting: &mut Vec<&'s str>) -> bool {
+        match ty {
+            Type::Struct(fields) => fields.iter().all(|f| self.has_finite_value(&f.ty, visiting)),
+            Type::Union(variants) => variants
+                .iter()
+                .any(|v| v.payload.as_ref().is_none_or(|p| self.has_finite_value(p, visiting))),
+            Type::Named(name) => {
+                if visiting.contains(&name.as_str()) {
+                    return false;
+                }
+                let Some(def) = self.get(name) else { return true };
+                visiting.push(name);
+                let finite = self.has_finite_value(&def.ty, visiting);
+                visiting.pop();
+                finite
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Arrays of `u8` encode as CBOR byte strings (aliases of `u8` do not).
+fn is_u8(elem: &Type) -> bool {
+    matches!(elem, Type::Int(IntKind::U8))
+}
+
+fn int_value<T: TryFrom<u64> + TryFrom<i64>>(v: &Value) -> Result<T, EncodeError> {
+    let converted = match v {
+        Value::UInt(n) => T::try_from(*n).ok(),
+        Value::Int(n) => T::try_from(*n).ok(),
+        _ => return Err(EncodeError::InvalidValue(format!("expected integer, got {v:?}"))),
+    };
+    converted.ok_or_else(|| EncodeError::InvalidValue(format!("integer {v:?} out of range")))
+}
+
+fn bytes_value(v: &Value) -> Result<&[u8], EncodeError> {
+    match v {
+        Value::Bytes(b) => Ok(b),
+        _ => Err(EncodeError::InvalidValue(format!("expected bytes, got {v:?}"))),
+    }
+}
+
+fn is_empty_array(v: &Value) -> bool {
+    match v {
+        Value::Bytes(b) => b.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
+
+fn array_value(v: &Value) -> Result<&[Value], EncodeError> {
+    match v {
+        Value::Array(items) => Ok(items),
+        _ => Err(EncodeError::InvalidValue(format!("expected array, got {v:?}"))),
+    }
+}
+
+/// The `Item` fixture is shared with the JSON tests.
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::descriptor::{EnumVariant, Field, TypeDef, UnionVariant};
+    use crate::ReaderMode;
+
+    fn field(rank: u64, name: &str, ty: Type) -> Field {
+        Field { rank, name: name.into(), ty }
+    }
+
+    pub(crate) fn schema() -> Schema {
+        Schema {
+            types: vec![
+                TypeDef {
+                    name: "Color".into(),
+                    ty: Type::Enum(vec![
+                        EnumVariant { tag: 0, name: "Red".into() },
+                        EnumVariant { tag: 1, name: "Green".into() },
+                    ]),
+                },
+                TypeDef {
+                    name: "Shape".into(),
+                    ty: Type::Union(vec![
+                        UnionVariant { tag: 0, name: "circle".into(), payload: Some(Type::Float(FloatKind::F64)) },
+                        UnionVariant { tag: 2, name: "point".into(), payload: None },
+                    ]),
+                },
+                TypeDef { name: "common.Id".into(), ty: Type::Int(IntKind::U64) },
+                TypeDef {
+                    name: "Item".into(),
+                    ty: Type::Struct(vec![
+                        field(0, "id", Type::Named("common.Id".into())),
+                        field(2, "color", Type::Named("Color".into())),
+                        field(3, "shape", Type::Named("Shape".into())),
+                        field(4, "note", Type::Option(Box::new(Type::String))),
+                        field(5, "count", Type::Int(IntKind::U8)),
+                        field(6, "samples", Type::ExternalArray("count".into(), Box::new(Type::Int(IntKind::I16)))),
+                        field(7, "xyz", Type::FixedArray(3, Box::new(Type::Float(FloatKind::F32)))),
+                        field(8, "blob", Type::Array(Box::new(Type::Int(IntKind::U8)))),
+                        field(9, "delta", Type::Int(IntKind::IVarint)),
+                        field(10, "maybe", Type::Option(Box::new(Type::Option(Box::new(Type::Int(IntKind::I16)))))),
+                    ]),
+                },
+            ],
+        }
+    }
+
+    pub(crate) fn item() -> Value {
+        Value::Struct(vec![
+            ("id".into(), Value::UInt(7)),
+            ("color".into(), Value::Enum("Green".into())),
+            ("shape".into(), Value::Union("circle".into(), Some(Box::new(Value::Float(1.5))))),
+            ("note".into(), Value::Option(Some(Box::new(Value::Text("hi".into()))))),
+            ("count".into(), Value::UInt(2)),
+            ("samples".into(), Value::Array(vec![Value::Int(-1), Value::Int(300)])),
+            ("xyz".into(), Value::Array(vec![Value::Float(1.0), Value::Float(2.0), Value::Float(3.0)])),
+            ("blob".into(), Value::Bytes(vec![1, 2])),
+            ("delta".into(), Value::Int(-100)),
+            ("maybe".into(), Value::Option(Some(Box::new(Value::Option(None))))),
+        ])
+    }
+
+    pub(crate) fn item_bytes() -> Vec<u8> {
+        let mut w = Writer::new();
+        w.write_array_header(11);
+        w.write_u64(7);
+        w.write_null();
+        w.write_uvarint(1);
+        w.write_tag_header(0);
+        w.write_f64(1.5);
+        w.write_tag_header(1);
+        w.write_string("hi");
+        w.write_u8(2);
+        w.write_byte(0x9f);
+        w.write_i16(-1);
+        w.write_i16(300);
+        w.write_byte(0xff);
+        w.write_array_header(3);
+        w.write_f32(1.0);
+        w.write_f32(2.0);
+        w.write_f32(3.0);
+        w.write_bytes(&[1, 2]);
+        w.write_ivarint(-100);
+        w.write_tag_header(1);
+        w.write_byte(0x00);
+        w.finish()
+    }
+
+    #[test]
+    fn encode_matches_wire_rules() -> Result<(), EncodeError> {
+        assert_eq!(schema().encode_value("Item", &item())?, item_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_roundtrip() -> Result<(), DecodeError> {
+        let s = schema();
+        let v = s.decode_value("Item", &item_bytes())?;
+        assert_eq!(v, item());
+        assert_eq!(v.field("id"), Some(&Value::UInt(7)));
+        Ok(())
+    }
+
+    #[test]
+    fn short_struct_uses_defaults() -> Result<(), DecodeError> {
+        let mut w = Writer::new();
+        w.write_array_header(1);
+        w.write_u64(9);
+        let v = schema().decode_value("Item", &w.finish())?;
+        assert_eq!(v.field("color"), Some(&Value::Enum("Red".into())));
+        assert_eq!(v.field("shape"), Some(&Value::Union("circle".into(), Some(Box::new(Value::Float(0.0))))));
+        assert_eq!(v.field("note"), Some(&Value::Option(None)));
+        assert_eq!(v.field("xyz"), Some(&Value::Array(Vec::new())));
+        // and encodes with the fixed array at its declared length
+        let bytes = schema().encode_value("Item", &v).map_err(|e| DecodeError::InvalidData(e.to_string()))?;
+        let xyz = Value::Array(vec![Value::Float(0.0); 3]);
+        assert_eq!(schema().decode_value("Item", &bytes)?.field("xyz"), Some(&xyz));
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_defaults() {
+        // U = union { 0 a: U, 1 b }, S = struct { 0 s: S }
+        let s = Schema {
+            types: vec![
+                TypeDef {
+                    name: "U".into(),
+                    ty: Type::Union(vec![
+                        UnionVariant { tag: 0, name: "a".into(), payload: Some(Type::Named("U".into())) },
+                        UnionVariant { tag: 1, name: "b".into(), payload: None },
+                    ]),
+                },
+                TypeDef { name: "S".into(), ty: Type::Struct(vec![field(0, "s", Type::Named("S".into()))]) },
+            ],
+        };
+        assert_eq!(s.default_value(&Type::Named("U".into())).ok(), Some(Value::Union("b".into(), None)));
+        assert!(s.default_value(&Type::Named("S".into())).is_err());
+    }
+
+    #[test]
+    fn unit_union_variant() -> Result<(), DecodeError> {
+        let s = schema();
+        let v = Value::Union("point".into(), None);
+        let bytes = s.encode_value("Shape", &v).map_err(|e| DecodeError::InvalidData(e.to_string()))?;
+        assert_eq!(bytes, vec![0x02]);
+        assert_eq!(s.decode_value("Shape", &bytes)?, v);
+        Ok(())
+    }
+
+    #[test]
+    fn encode_rejects_mismatches() {
+        let s = schema();
+        assert!(s.encode_value("Color", &Value::Enum("Purple".into())).is_err());
+        assert!(s.encode_value("common.Id", &Value::Int(-1)).is_err());
+        assert!(s.encode_value("Item", &Value::Struct(vec![("bogus".into(), Value::Bool(true))])).is_err());
+        assert!(s.encode_value("Missing", &Value::Bool(true)).is_err());
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        // Node = struct { 0 kids: []Node }
+        let s = Schema {
+            types: vec![TypeDef {
+                name: "Node".into(),
+                ty: Type::Struct(vec![field(0, "kids", Type::Array(Box::new(Type::Named("Node".into()))))]),
+            }],
+        };
+        let data = [0x81; 400_000];
+        assert!(matches!(s.decode_value("Node", &data), Err(DecodeError::DepthExceeded(_))));
+    }
+
+    #[test]
+    fn descriptor_roundtrip() -> Result<(), DecodeError> {
+        let s = schema();
+        assert_eq!(Schema::decode(&s.encode())?, s);
+        Ok(())
+    }
+
+    #[test]
+    fn strict_gaps_must_be_null() -> Result<(), DecodeError> {
+        let s = schema();
+        let ty = Type::Named("Item".into());
+        let mut data = item_bytes();
+        s.read_value(&ty, &mut Reader::with_mode(&data, ReaderMode::Strict))?;
+        // The gap at rank 1 follows the 9-byte u64 id
+        assert_eq!(data[10], 0xf6);
+        data[10] = 0x00;
+        assert_eq!(s.decode_value("Item", &data)?, item());
+        assert!(s.read_value(&ty, &mut Reader::with_mode(&data, ReaderMode::Strict)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn descriptor_rejects_bad_input() {
+        // [[["T", ?????...]]]
+        let mut data = vec![0x81, 0x81, 0x82, 0x61, b'T'];
+        data.resize(200_000, 0xc7);
+        assert!(matches!(Schema::decode(&data), Err(DecodeError::DepthExceeded(_))));
+        // A union variant whose optional payload isn't tagged 1
+        let mut data = vec![0x81, 0x81, 0x82, 0x61, b'U', 0xc6, 0x81, 0x83, 0x00, 0x61, b'a', 0xc2, 0x00];
+        assert!(Schema::decode(&data).is_err());
+        data[11] = 0xc1;
+        assert!(Schema::decode(&data).is_ok());
+    }
+}
@@ -0,0 +1,440 @@
+//! Schema-less pull parsing: `Reader::next_event` walks any CBOR input one
+//! head at a time without building a tree, checking well-formedness as it
+//! goes. `skip`, `validate_well_formed` and [`diagnostic`] are built on it.
+
+use std::fmt::Write as _;
+
+use crate::{f16_bits_to_f32, DecodeError, Reader};
+
+/// One step through CBOR input. Definite-length arrays and maps end after
+/// their items without an event; indefinite-length ones, and the chunks of
+/// indefinite-length strings, end with `Break`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Event<'a> {
+    UInt(u64),
+    /// A negative integer, `-1 - n`.
+    NInt(u64),
+    /// A byte string, or one chunk of an indefinite-length byte string.
+    Bytes(&'a [u8]),
+    /// A text string, or one chunk of an indefinite-length text string.
+    Text(&'a str),
+    /// The start of an indefinite-length byte string; its chunks follow.
+    BytesStart,
+    /// The start of an indefinite-length text string; its chunks follow.
+    TextStart,
+    /// An array of this many items, or an indefinite-length one.
+    ArrayStart(Option<u64>),
+    /// A map of this many key/value pairs, or an indefinite-length one.
+    MapStart(Option<u64>),
+    /// A tag; the tagged item follows.
+    Tag(u64),
+    /// A simple value: 20 and 21 are false and true, 22 null, 23 undefined.
+    Simple(u8),
+    /// A float of any width.
+    Float(f64),
+    /// The end of an indefinite-length item.
+    Break,
+}
+
+/// An item whose contents are still being read.
+#[derive(Debug, Clone)]
+pub(crate) enum Open {
+    /// Items left in a definite-length array or map (keys and values
+    /// counted separately), or a tag's content.
+    Items(u64),
+    /// An indefinite-length array or map and the items read so far.
+    Indefinite { map: bool, items: u64 },
+    /// An indefinite-length string or byte string of this major type.
+    Chunks(u8),
+}
+
+impl<'a> Reader<'a> {
+    /// Read the next event. The reader tracks open arrays, maps, tags and
+    /// indefinite-length strings, so breaks must close an indefinite-length
+    /// item and nesting counts against the depth limit. `skip` and
+    /// `read_raw_item` can pass over whole items in between; other reads
+    /// aren't counted against the open items.
+    pub fn next_event(&mut self) -> Result<Event<'a>, DecodeError> {
+        let mut open = std::mem::take(&mut self.open);
+        let before = open.len();
+        let event = next_head(self, &mut open, true);
+        let after = open.len();
+        self.open = open;
+        let event = event?;
+        if after > before {
+            self.enter()?;
+        }
+        for _ in after..before {
+            self.leave();
+        }
+        Ok(event)
+    }
+
+    /// Current nesting depth, counting items opened by `next_event` and
+    /// levels entered by decoders of recursive types.
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// Count an item read some other way against the items `next_event` has
+    /// open.
+    pub(crate) fn item_read(&mut self) {
+        let before = self.open.len();
+        if before > 0 {
+            complete(&mut self.open);
+            for _ in self.open.len()..before {
+                self.leave();
+            }
+        }
+    }
+}
+
+/// Read one head and its payload, updating `open`. Text is checked as UTF-8
+/// only if `utf8` is set; otherwise it comes back as `Text("")`, since
+/// well-formedness doesn't cover it.
+pub(crate) fn next_head<'a>(r: &mut Reader<'a>, open: &mut Vec<Open>, utf8: bool) -> Result<Event<'a>, DecodeError> {
+    let b = r.read_byte()?;
+    let (maj, ai) = (b >> 5, b & 0x1f);
+
+    // Inside an indefinite-length item, a break closes it
+    match open.last() {
+        Some(Open::Chunks(m)) => {
+            if b != 0xff {
+                if maj != *m || ai == 31 {
+                    return Err(DecodeError::InvalidData(format!(
+                        "chunk of an indefinite-length string must be a definite-length string of major type {m}"
+                    )));
+                }
+                return read_string(r, maj, ai, utf8);
+            }
+            open.pop();
+            complete(open);
+            return Ok(Event::Break);
+        }
+        Some(Open::Indefinite { map, items }) if b == 0xff => {
+            if *map && *items % 2 == 1 {
+                return Err(DecodeError::InvalidData("map key without a value".into()));
+            }
+            open.pop();
+            complete(open);
+            return Ok(Event::Break);
+        }
+        _ => {}
+    }
+
+    let (event, opened) = match (maj, ai) {
+        (_, 28..=30) => return Err(DecodeError::InvalidData(format!("reserved additional info {ai}"))),
+        (2, 31) => (Event::BytesStart, Some(Open::Chunks(2))),
+        (3, 31) => (Event::TextStart, Some(Open::Chunks(3))),
+        (4, 31) => (Event::ArrayStart(None), Some(Open::Indefinite { map: false, items: 0 })),
+        (5, 31) => (Event::MapStart(None), Some(Open::Indefinite { map: true, items: 0 })),
+        (7, 31) => return Err(DecodeError::InvalidData("break code outside an indefinite-length item".into())),
+        (_, 31) => return Err(DecodeError::InvalidData(format!("indefinite length for major type {maj}"))),
+        // Fixed-width integers are deliberately not minimal
+        (0, _) => (Event::UInt(r.read_arg(ai)?), None),
+        (1, _) => (Event::NInt(r.read_arg(ai)?), None),
+        (2 | 3, _) => (read_string(r, maj, ai, utf8)?, None),
+        (4 | 5, _) => {
+            let n = r.read_canonical_arg(ai)?;
+            let items = if maj == 5 { n.checked_mul(2) } else { Some(n) };
+            // Every item takes at least a byte
+            let items = items.filter(|&items| items <= (r.data.len() - r.pos) as u64).ok_or(DecodeError::UnexpectedEnd)?;
+            let event = if maj == 5 { Event::MapStart(Some(n)) } else { Event::ArrayStart(Some(n)) };
+            (event, (items > 0).then_some(Open::Items(items)))
+        }
+        (6, _) => (Event::Tag(r.read_canonical_arg(ai)?), Some(Open::Items(1))),
+        (_, 24) => {
+            let v = r.read_byte()?;
+            if v < 32 {
+                return Err(DecodeError::InvalidData(format!("two-byte encoding of simple value {v}")));
+            }
+            (Event::Simple(v), None)
+        }
+        (_, 25) => (Event::Float(f16_bits_to_f32(r.read_u16_raw()?) as f64), None),
+        (_, 26) => (Event::Float(f32::from_bits(r.read_u32_raw()?) as f64), None),
+        (_, 27) => (Event::Float(f64::from_bits(r.read_u64_raw()?)), None),
+        _ => (Event::Simple(ai), None),
+    };
+    match opened {
+        Some(o) => open.push(o),
+        None => complete(open),
+    }
+    Ok(event)
+}
+
+fn read_string<'a>(r: &mut Reader<'a>, maj: u8, ai: u8, utf8: bool) -> Result<Event<'a>, DecodeError> {
+    let len = r.read_canonical_arg(ai)?;
+    let b = r.take(usize::try_from(len).map_err(|_| DecodeError::Overflow("usize"))?)?;
+    if maj == 2 {
+        Ok(Event::Bytes(b))
+    } else if utf8 {
+        std::str::from_utf8(b)
+            .map(Event::Text)
+            .map_err(|e| DecodeError::InvalidData(format!("invalid UTF-8 in CBOR string: {e}")))
+    } else {
+        Ok(Event::Text(""))
+    }
+}
+
+/// An item is complete; count it against the items it ends.
+fn complete(open: &mut Vec<Open>) {
+    while let Some(o) = open.last_mut() {
+        match o {
+            Open::Items(n) => {
+                *n -= 1;
+                if *n > 0 {
+                    return;
+                }
+                open.pop();
+            }
+            Open::Indefinite { items, .. } => {
+                *items += 1;
+                return;
+            }
+            // Chunks are not items
+            Open::Chunks(_) => return,
+        }
+    }
+}
+
+/// Render `data`, which must be exactly one item, in CBOR diagnostic
+/// notation (RFC 8949, section 8), e.g. `{"a": [1, h'ff'], "b": 1(-2.5)}`.
+pub fn diagnostic(data: &[u8]) -> Result<String, DecodeError> {
+    // An item being printed: its closing text, its length if definite, and
+    // the items printed so far
+    struct Frame {
+        close: &'static str,
+        len: Option<u64>,
+        map: bool,
+        items: u64,
+    }
+
+    let mut r = Reader::new(data);
+    r.set_max_depth(u32::MAX);
+    let mut out = String::new();
+    let mut open: Vec<Frame> = Vec::new();
+    loop {
+        let event = r.next_event()?;
+        if event == Event::Break {
+            // Only indefinite-length frames take a break
+            let frame = open.pop().ok_or(DecodeError::UnexpectedEnd)?;
+            out.push_str(frame.close);
+        } else {
+            if let Some(f) = open.last() {
+                if f.items > 0 {
+                    out.push_str(if f.map && f.items % 2 == 1 { ": " } else { ", " });
+                }
+            }
+            let frame = |close, len: Option<u64>, map| Frame { close, len: len.map(|n| if map { n * 2 } else { n }), map, items: 0 };
+            let opened = match event {
+                Event::UInt(n) => {
+                    let _ = write!(out, "{n}");
+                    None
+                }
+                Event::NInt(n) => {
+                    let _ = write!(out, "{}", -1 - n as i128);
+                    None
+                }
+                Event::Bytes(b) => {
+                    out.push_str("h'");
+                    for byte in b {
+                        let _ = write!(out, "{byte:02x}");
+                    }
+                    out.push('\'');
+                    None
+                }
+                Event::Text(s) => {
+                    write_text(&mut out, s);
+                    None
+                }
+                Event::BytesStart | Event::TextStart => {
+                    out.push_str("(_ ");
+                    Some(frame(")", None, false))
+                }
+                Event::ArrayStart(Some(0)) => {
+                    out.push_str("[]");
+                    None
+                }
+                Event::MapStart(Some(0)) => {
+                    out.push_str("{}");
+                    None
+                }
+                Event::ArrayStart(len) => {
+                    out.push_str(if len.is_some() { "[" } else { "[_ " });
+                    Some(frame("]", len, false))
+                }
+                Event::MapStart(len) => {
+                    out.push_str(if len.is_some() { "{" } else { "{_ " });
+                    Some(frame("}", len, true))
+                }
+                Event::Tag(n) => {
+                    let _ = write!(out, "{n}(");
+                    Some(frame(")", Some(1), false))
+                }
+                Event::Simple(20) => {
+                    out.push_str("false");
+                    None
+                }
+                Event::Simple(21) => {
+                    out.push_str("true");
+                    None
+                }
+                Event::Simple(22) => {
+                    out.push_str("null");
+                    None
+                }
+                Event::Simple(23) => {
+                    out.push_str("undefined");
+                    None
+                }
+                Event::Simple(n) => {
+                    let _ = write!(out, "simple({n})");
+                    None
+                }
+                Event::Float(f) => {
+                    if f.is_nan() {
+                        out.push_str("NaN");
+                    } else if f.is_infinite() {
+                        out.push_str(if f > 0.0 { "Infinity" } else { "-Infinity" });
+                    } else {
+                        let _ = write!(out, "{f:?}");
+                    }
+                    None
+                }
+                Event::Break => unreachable!(),
+            };
+            if let Some(frame) = opened {
+                open.push(frame);
+                continue;
+            }
+        }
+
+        // An item is complete; close the definite-length items it ends
+        loop {
+            let Some(f) = open.last_mut() else {
+                r.finish()?;
+                return Ok(out);
+            };
+            f.items += 1;
+            if f.len != Some(f.items) {
+                break;
+            }
+            out.push_str(f.close);
+            open.pop();
+        }
+    }
+}
+
+fn write_text(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    #[test]
+    fn events() -> Result<(), DecodeError> {
+        // [1, {"a": -2}, 5(h'ff'), [_ 1.5, null], (_ "ab", "c")]
+        let data = bytes("8501a1616121c541ff9ff93e00f6ff7f6261626163ff");
+        let mut r = Reader::new(&data);
+        let mut events = Vec::new();
+        let mut depths = Vec::new();
+        while r.position() < data.len() {
+            events.push(r.next_event()?);
+            depths.push(r.depth());
+        }
+        assert_eq!(events, [
+            Event::ArrayStart(Some(5)),
+            Event::UInt(1),
+            Event::MapStart(Some(1)),
+            Event::Text("a"),
+            Event::NInt(1),
+            Event::Tag(5),
+            Event::Bytes(&[0xff]),
+            Event::ArrayStart(None),
+            Event::Float(1.5),
+            Event::Simple(22),
+            Event::Break,
+            Event::TextStart,
+            Event::Text("ab"),
+            Event::Text("c"),
+            Event::Break,
+        ]);
+        assert_eq!(depths, [1, 1, 2, 2, 1, 2, 1, 2, 2, 2, 1, 2, 2, 2, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn events_check_nesting() -> Result<(), DecodeError> {
+        assert!(Reader::new(&[0xff]).next_event().is_err());
+        let mut r = Reader::new(&[0x81, 0xff]);
+        r.next_event()?;
+        assert!(r.next_event().is_err());
+        let mut r = Reader::new(&[0x9f, 0x9f, 0xff, 0xff]);
+        r.set_max_depth(1);
+        r.next_event()?;
+        assert!(matches!(r.next_event(), Err(DecodeError::DepthExceeded(1))));
+        assert!(Reader::new(&[0x62, 0xff, 0xfe]).next_event().is_err());
+
+        // Typed reads count as items
+        let mut r = Reader::new(&[0x82, 0x81, 0x01, 0x02, 0x03]);
+        assert_eq!(r.next_event()?, Event::ArrayStart(Some(2)));
+        r.skip()?;
+        assert_eq!(r.depth(), 1);
+        r.skip()?;
+        assert_eq!(r.depth(), 0);
+        assert_eq!(r.next_event()?, Event::UInt(3));
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostic_notation() -> Result<(), DecodeError> {
+        let cases = [
+            ("00", "0"),
+            ("3903e7", "-1000"),
+            ("3bffffffffffffffff", "-18446744073709551616"),
+            ("f93e00", "1.5"),
+            ("fa7f800000", "Infinity"),
+            ("f9fc00", "-Infinity"),
+            ("f97e00", "NaN"),
+            ("f4", "false"),
+            ("f7", "undefined"),
+            ("f0", "simple(16)"),
+            ("4401020304", "h'01020304'"),
+            ("62225c", r#""\"\\""#),
+            ("6161", r#""a""#),
+            ("80", "[]"),
+            ("a0", "{}"),
+            ("8301820203820405", "[1, [2, 3], [4, 5]]"),
+            ("a26161016162820203", r#"{"a": 1, "b": [2, 3]}"#),
+            ("c11a514b67b0", "1(1363896240)"),
+            ("9f018202039f0405ffff", "[_ 1, [2, 3], [_ 4, 5]]"),
+            ("bf61610161629f0203ffff", r#"{_ "a": 1, "b": [_ 2, 3]}"#),
+            ("5f42010243030405ff", "(_ h'0102', h'030405')"),
+            ("7f657374726561646d696e67ff", r#"(_ "strea", "ming")"#),
+            ("5fff", "(_ )"),
+        ];
+        for (hex, diag) in cases {
+            assert_eq!(diagnostic(&bytes(hex))?, diag, "{hex}");
+        }
+        assert!(diagnostic(&bytes("0000")).is_err());
+        assert!(diagnostic(&bytes("81")).is_err());
+        Ok(())
+    }
+}
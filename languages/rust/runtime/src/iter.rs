@@ -0,0 +1,143 @@
+//! Element-by-element decoding of arrays, for arrays too large to collect.
+
+use std::iter::FusedIterator;
+
+use crate::{Decode, DecodeError, Reader};
+
+/// Decodes the elements of an encoded array one at a time. The array may be
+/// definite-length or indefinite-length (`0x9f`, as external-length arrays
+/// are written). Yields `Err` at most once, then stops.
+pub struct ArrayIter<'r, T> {
+    r: Reader<'r>,
+    /// Elements left, or `None` for an indefinite-length array
+    remaining: Option<usize>,
+    done: bool,
+    elem: fn(&mut Reader<'r>) -> Result<T, DecodeError>,
+}
+
+impl<'r, T> ArrayIter<'r, T> {
+    /// Read an array header from `r` and decode each element with `elem`.
+    pub fn with(mut r: Reader<'r>, elem: fn(&mut Reader<'r>) -> Result<T, DecodeError>) -> Result<Self, DecodeError> {
+        let remaining = if r.peek_byte()? == 0x9f {
+            r.read_indefinite_array_start()?;
+            None
+        } else {
+            Some(r.read_array_header()?)
+        };
+        Ok(ArrayIter { r, remaining, done: false, elem })
+    }
+
+    /// An iterator with no elements, for an array that isn't there.
+    pub fn empty() -> Self {
+        ArrayIter { r: Reader::new(&[]), remaining: Some(0), done: true, elem: |_| Err(DecodeError::UnexpectedEnd) }
+    }
+
+    /// Elements left, if the array has a definite length.
+    pub fn remaining(&self) -> Option<usize> {
+        if self.done { Some(0) } else { self.remaining }
+    }
+
+    /// The reader, positioned after the elements read so far.
+    pub fn into_reader(self) -> Reader<'r> {
+        self.r
+    }
+}
+
+impl<'r, T: Decode> ArrayIter<'r, T> {
+    /// Read an array header from `r` and decode each element as a `T`.
+    pub fn new(r: Reader<'r>) -> Result<Self, DecodeError> {
+        Self::with(r, T::decode_with)
+    }
+}
+
+impl<T> Iterator for ArrayIter<'_, T> {
+    type Item = Result<T, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match &mut self.remaining {
+            Some(0) => {
+                self.done = true;
+                return None;
+            }
+            Some(n) => *n -= 1,
+            None => match self.r.try_read_break() {
+                Ok(false) => {}
+                Ok(true) => {
+                    self.done = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            },
+        }
+        let v = (self.elem)(&mut self.r);
+        self.done = v.is_err();
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.remaining() {
+            Some(n) => (n, Some(n)),
+            None => (0, None),
+        }
+    }
+}
+
+impl<T> FusedIterator for ArrayIter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Writer;
+
+    #[test]
+    fn definite_and_indefinite() -> Result<(), DecodeError> {
+        let mut w = Writer::new();
+        w.write_array_header(3);
+        for v in [1u32, 2, 3] {
+            w.write_u32(v);
+        }
+        w.write_indefinite_array_start();
+        for v in [4u32, 5] {
+            w.write_u32(v);
+        }
+        w.write_break();
+        w.write_bool(true);
+        let data = w.finish();
+
+        let mut it = ArrayIter::<u32>::new(Reader::new(&data))?;
+        assert_eq!(it.size_hint(), (3, Some(3)));
+        assert_eq!(it.by_ref().collect::<Result<Vec<_>, _>>()?, [1, 2, 3]);
+        assert_eq!(it.remaining(), Some(0));
+
+        let mut it = ArrayIter::with(it.into_reader(), |r| Ok(r.read_u32()? * 10))?;
+        assert_eq!(it.remaining(), None);
+        assert_eq!(it.by_ref().collect::<Result<Vec<_>, _>>()?, [40, 50]);
+        let mut r = it.into_reader();
+        assert!(r.read_bool()?);
+        r.finish()?;
+
+        assert_eq!(ArrayIter::<u32>::empty().count(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn stops_after_an_error() -> Result<(), DecodeError> {
+        // [1, "x", 3]
+        let data = [0x83, 0x1a, 0, 0, 0, 1, 0x61, b'x', 0x1a, 0, 0, 0, 3];
+        let mut it = ArrayIter::<u32>::new(Reader::new(&data))?;
+        assert_eq!(it.next().transpose()?, Some(1));
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+        // Truncated indefinite-length array
+        let mut it = ArrayIter::<u32>::new(Reader::new(&[0x9f]))?;
+        assert!(it.next().unwrap().is_err());
+        assert!(it.next().is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! Schema-driven CBOR ↔ JSON conversion (`json` feature).
+//!
+//! JSON uses the names from the schema: structs become objects keyed by
+//! field name, enums become variant-name strings, and unions become
+//! `{"variant": payload}` (or a bare `"variant"` string for unit variants).
+//! `[]u8` is base64 text, non-finite floats are the strings `"NaN"`,
+//! `"Infinity"` and `"-Infinity"`, and 64-bit integers are numbers or
+//! strings per [`JsonOptions::int64_as_string`]. Nested optionals (`??T`)
+//! wrap each inner `some` in a one-element array so `some(none)` stays
+//! distinct from `none`.
+//!
+//! Converting JSON back goes through the schema, so every integer and float
+//! regains its declared width. For a message written by this schema's
+//! generated code, the re-encoded bytes are identical to the original. Other
+//! messages come back in that form: fields unknown to the schema and the
+//! contents of gaps are dropped, and a short struct gains its missing
+//! fields at their defaults.
+
+use serde_json::{Map, Number, Value as Json};
+
+use crate::descriptor::{FloatKind, IntKind, Schema, Type};
+use crate::base64::{base64_decode, base64_encode};
+use crate::{DecodeError, EncodeError, Reader, Value};
+
+/// Options controlling the JSON representation.
+#[derive(Debug, Clone, Default)]
+pub struct JsonOptions {
+    /// Render `u64`, `i64`, `uvarint` and `ivarint` as decimal strings, for
+    /// consumers (such as JavaScript) that lose precision above 2^53.
+    /// Parsing accepts both forms regardless of this setting.
+    pub int64_as_string: bool,
+    /// Pretty-print the output of [`to_json`].
+    pub pretty: bool,
+}
+
+/// Error converting between JSON and a schema type.
+#[derive(Debug)]
+pub enum JsonError {
+    /// The input is not valid JSON.
+    Syntax(String),
+    /// The JSON or CBOR does not match the schema type.
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for JsonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonError::Syntax(msg) => write!(f, "invalid JSON: {msg}"),
+            JsonError::InvalidValue(msg) => write!(f, "invalid value: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<DecodeError> for JsonError {
+    fn from(e: DecodeError) -> Self {
+        JsonError::InvalidValue(e.to_string())
+    }
+}
+
+impl From<EncodeError> for JsonError {
+    fn from(e: EncodeError) -> Self {
+        JsonError::InvalidValue(e.to_string())
+    }
+}
+
+/// Decode a CBOR message of the named type and render it as JSON text.
+/// Fields the schema doesn't know are left out.
+pub fn to_json(schema: &Schema, type_name: &str, data: &[u8], opts: &JsonOptions) -> Result<String, JsonError> {
+    let ty = named(schema, type_name)?;
+    let mut r = Reader::new(data);
+    let value = schema.read_value(&ty, &mut r)?;
+    let json = schema.value_to_json(&ty, &value, opts)?;
+    let text = if opts.pretty { serde_json::to_string_pretty(&json) } else { serde_json::to_string(&json) };
+    text.map_err(|e| JsonError::InvalidValue(e.to_string()))
+}
+
+/// Parse JSON text as the named type and encode it as CBOR.
+pub fn from_json(schema: &Schema, type_name: &str, json: &str) -> Result<Vec<u8>, JsonError> {
+    let ty = named(schema, type_name)?;
+    let json: Json = serde_json::from_str(json).map_err(|e| JsonError::Syntax(e.to_string()))?;
+    let value = schema.value_from_json(&ty, &json)?;
+    let mut w = crate::Writer::new();
+    schema.write_value(&ty, &value, &mut w)?;
+    Ok(w.finish())
+}
+
+fn named(schema: &Schema, type_name: &str) -> Result<Type, JsonError> {
+    match schema.get(type_name) {
+        Some(_) => Ok(Type::Named(type_name.to_string())),
+        None => Err(JsonError::InvalidValue(format!("unknown type {type_name}"))),
+    }
+}
+
+impl Schema {
+    /// Convert a value of type `ty` to its JSON representation.
+    pub fn value_to_json(&self, ty: &Type, value: &Value, opts: &JsonOptions) -> Result<Json, JsonError> {
+        let ty = self.resolve(ty)?;
+        Ok(match (ty, value) {
+            (Type::Bool, Value::Bool(b)) => Json::Bool(*b),
+            (Type::String, Value::Text(s)) => Json::String(s.clone()),
+            (Type::Int(kind), Value::UInt(n)) => {
+                if opts.int64_as_string && is_wide(*kind) { Json::String(n.to_string()) } else { Json::from(*n) }
+            }
+            (Type::Int(kind), Value::Int(n)) => {
+                if opts.int64_as_string && is_wide(*kind) { Json::String(n.to_string()) } else { Json::from(*n) }
+            }
+            (Type::Float(kind), Value::Float(f)) => float_to_json(*kind, *f),
+            (Type::Struct(fields), Value::Struct(values)) => {
+                let mut obj = Map::new();
+                for (name, v) in values {
+                    let field = fields
+                        .iter()
+                        .find(|f| &f.name == name)
+                        .ok_or_else(|| JsonError::InvalidValue(format!("unknown struct field {name}")))?;
+                    obj.insert(name.clone(), self.value_to_json(&field.ty, v, opts)?);
+                }
+                Json::Object(obj)
+            }
+            (Type::Enum(_), Value::Enum(name)) => Json::String(name.clone()),
+            (Type::Union(variants), Value::Union(name, payload)) => {
+                let variant = variants
+                    .iter()
+                    .find(|v| &v.name == name)
+                    .ok_or_else(|| JsonError::InvalidValue(format!("unknown union variant {name}")))?;
+                match (&variant.payload, payload) {
+                    (Some(pty), Some(p)) => {
+                        let mut obj = Map::new();
+                        obj.insert(name.clone(), self.value_to_json(pty, p, opts)?);
+                        Json::Object(obj)
+                    }
+                    (None, None) => Json::String(name.clone()),
+                    _ => return Err(JsonError::InvalidValue(format!("payload mismatch for union variant {name}"))),
+                }
+            }
+            (Type::Option(child), Value::Option(opt)) => match opt {
+                None => Json::Null,
+                Some(v) => {
+                    let inner = self.value_to_json(child, v, opts)?;
+                    if matches!(self.resolve(child)?, Type::Option(_)) { Json::Array(vec![inner]) } else { inner }
+                }
+            },
+            (Type::Array(elem) | Type::FixedArray(_, elem) | Type::ExternalArray(_, elem), v) => match v {
+                Value::Bytes(b) if is_u8(elem) => Json::String(base64_encode(b)),
+                Value::Array(items) => {
+                    let mut out = Vec::with_capacity(items.len());
+                    for item in items {
+                        out.push(self.value_to_json(elem, item, opts)?);
+                    }
+                    Json::Array(out)
+                }
+                _ => return Err(JsonError::InvalidValue(format!("expected array, got {v:?}"))),
+            },
+            (ty, v) => return Err(JsonError::InvalidValue(format!("value {v:?} does not match type {ty:?}"))),
+        })
+    }
+
+    /// Convert JSON to a value of type `ty`.
+    pub fn value_from_json(&self, ty: &Type, json: &Json) -> Result<Value, JsonError> {
+        let ty = self.resolve(ty)?;
+        let mismatch = || JsonError::InvalidValue(format!("expected {}, got {json}", type_label(ty)));
+        Ok(match ty {
+            Type::Bool => Value::Bool(json.as_bool().ok_or_else(mismatch)?),
+            Type::String => Value::Text(json.as_str().ok_or_else(mismatch)?.to_string()),
+            Type::Int(kind) => match kind {
+                IntKind::I8 | IntKind::I16 | IntKind::I32 | IntKind::I64 | IntKind::IVarint => {
+                    let n = match json {
+                        Json::Number(n) => n.as_i64(),
+                        Json::String(s) => s.parse().ok(),
+                        _ => None,
+                    };
+                    Value::Int(n.ok_or_else(mismatch)?)
+                }
+                _ => {
+                    let n = match json {
+                        Json::Number(n) => n.as_u64(),
+                        Json::String(s) => s.parse().ok(),
+                        _ => None,
+                    };
+                    Value::UInt(n.ok_or_else(mismatch)?)
+                }
+            },
+            Type::Float(_) => {
+                let f = match json {
+                    Json::Number(n) => n.as_f64(),
+                    Json::String(s) => match s.as_str() {
+                        "NaN" => Some(f64::NAN),
+                        "Infinity" => Some(f64::INFINITY),
+                        "-Infinity" => Some(f64::NEG_INFINITY),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                Value::Float(f.ok_or_else(mismatch)?)
+            }
+            Type::Struct(fields) => {
+                let obj = json.as_object().ok_or_else(mismatch)?;
+                let mut values = Vec::with_capacity(obj.len());
+                for (name, v) in obj {
+                    let field = fields
+                        .iter()
+                        .find(|f| &f.name == name)
+                        .ok_or_else(|| JsonError::InvalidValue(format!("unknown struct field {name}")))?;
+                    values.push((name.clone(), self.value_from_json(&field.ty, v)?));
+                }
+                Value::Struct(values)
+            }
+            Type::Enum(variants) => {
+                let name = json.as_str().ok_or_else(mismatch)?;
+                if !variants.iter().any(|v| v.name == name) {
+                    return Err(JsonError::InvalidValue(format!("unknown enum variant {name}")));
+                }
+                Value::Enum(name.to_string())
+            }
+            Type::Union(variants) => {
+                let (name, payload) = match json {
+                    Json::String(name) => (name, None),
+                    Json::Object(obj) if obj.len() == 1 => {
+                        let (name, p) = obj.iter().next().ok_or_else(mismatch)?;
+                        (name, Some(p))
+                    }
+                    _ => return Err(mismatch()),
+                };
+                let variant = variants
+                    .iter()
+                    .find(|v| &v.name == name)
+                    .ok_or_else(|| JsonError::InvalidValue(format!("unknown union variant {name}")))?;
+                match (&variant.payload, payload) {
+                    (Some(pty), Some(p)) => Value::Union(name.clone(), Some(Box::new(self.value_from_json(pty, p)?))),
+                    (None, None) => Value::Union(name.clone(), None),
+                    _ => return Err(JsonError::InvalidValue(format!("payload mismatch for union variant {name}"))),
+                }
+            }
+            Type::Option(child) => match json {
+                Json::Null => Value::Option(None),
+                _ if matches!(self.resolve(child)?, Type::Option(_)) => match json {
+                    Json::Array(items) if items.len() == 1 => {
+                        Value::Option(Some(Box::new(self.value_from_json(child, &items[0])?)))
+                    }
+                    _ => return Err(mismatch()),
+                },
+                _ => Value::Option(Some(Box::new(self.value_from_json(child, json)?))),
+            },
+            Type::Array(elem) | Type::FixedArray(_, elem) | Type::ExternalArray(_, elem) => {
+                if is_u8(elem) {
+                    let text = json.as_str().ok_or_else(mismatch)?;
+                    Value::Bytes(base64_decode(text).ok_or_else(|| JsonError::InvalidValue(format!("invalid base64 {text:?}")))?)
+                } else {
+                    let items = json.as_array().ok_or_else(mismatch)?;
+                    let mut out = Vec::with_capacity(items.len());
+                    for item in items {
+                        out.push(self.value_from_json(elem, item)?);
+                    }
+                    Value::Array(out)
+                }
+            }
+            Type::Named(_) => unreachable!("resolve() never returns Named"),
+        })
+    }
+}
+
+fn is_wide(kind: IntKind) -> bool {
+    matches!(kind, IntKind::U64 | IntKind::I64 | IntKind::UVarint | IntKind::IVarint)
+}
+
+fn is_u8(elem: &Type) -> bool {
+    matches!(elem, Type::Int(IntKind::U8))
+}
+
+fn type_label(ty: &Type) -> &'static str {
+    match ty {
+        Type::Bool => "boolean",
+        Type::String => "string",
+        Type::Int(_) => "integer",
+        Type::Float(_) => "number",
+        Type::Struct(_) => "object",
+        Type::Enum(_) => "enum variant name",
+        Type::Union(_) => "union variant",
+        Type::Option(_) => "optional",
+        Type::Array(_) | Type::FixedArray(..) | Type::ExternalArray(..) => "array",
+        Type::Named(_) => "named type",
+    }
+}
+
+/// Floats narrower than f64 print their shortest round-tripping decimal,
+/// so an `f32` of 0.1 reads as `0.1` rather than `0.10000000149011612`.
+fn float_to_json(kind: FloatKind, f: f64) -> Json {
+    if f.is_nan() {
+        return Json::String("NaN".into());
+    }
+    if f.is_infinite() {
+        return Json::String(if f > 0.0 { "Infinity" } else { "-Infinity" }.into());
+    }
+    let shortest = match kind {
+        FloatKind::F16 | FloatKind::F32 => (f as f32).to_string().parse().unwrap_or(f),
+        FloatKind::F64 => f,
+    };
+    Number::from_f64(shortest).map_or(Json::Null, Json::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dynamic::tests::{item_bytes, schema};
+
+    #[test]
+    fn to_json_uses_schema_names() -> Result<(), JsonError> {
+        let json = to_json(&schema(), "Item", &item_bytes(), &JsonOptions::default())?;
+        assert_eq!(
+            json,
+            r#"{"id":7,"color":"Green","shape":{"circle":1.5},"note":"hi","count":2,"samples":[-1,300],"xyz":[1.0,2.0,3.0],"blob":"AQI=","delta":-100,"maybe":[null]}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn int64_as_string() -> Result<(), JsonError> {
+        let opts = JsonOptions { int64_as_string: true, ..Default::default() };
+        let json = to_json(&schema(), "Item", &item_bytes(), &opts)?;
+        assert!(json.contains(r#""id":"7""#));
+        assert_eq!(from_json(&schema(), "Item", &json)?, item_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn json_roundtrip_is_byte_identical() -> Result<(), JsonError> {
+        let opts = JsonOptions { pretty: true, ..Default::default() };
+        let json = to_json(&schema(), "Item", &item_bytes(), &opts)?;
+        assert_eq!(from_json(&schema(), "Item", &json)?, item_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn newer_peer_fields_are_dropped() -> Result<(), JsonError> {
+        // A newer schema appended a field at rank 11
+        let mut newer = item_bytes();
+        newer[0] += 1;
+        newer.push(0xf5);
+        let json = to_json(&schema(), "Item", &newer, &JsonOptions::default())?;
+        assert_eq!(json, to_json(&schema(), "Item", &item_bytes(), &JsonOptions::default())?);
+        assert_eq!(from_json(&schema(), "Item", &json)?, item_bytes());
+        Ok(())
+    }
+
+    #[test]
+    fn hand_edited_json() -> Result<(), JsonError> {
+        let bytes = from_json(&schema(), "Item", r#"{"id": "5", "color": "Red", "note": null}"#)?;
+        let value = schema().decode_value("Item", &bytes)?;
+        assert_eq!(value.field("id"), Some(&Value::UInt(5)));
+        assert_eq!(value.field("note"), Some(&Value::Option(None)));
+        assert!(from_json(&schema(), "Item", r#"{"bogus": 1}"#).is_err());
+        assert!(from_json(&schema(), "Item", r#"{"color": "Blue"}"#).is_err());
+        assert!(matches!(
+            from_json(&schema(), "Item", "{"),
+            Err(JsonError::Syntax(_))
+        ));
+        Ok(())
+    }
+}
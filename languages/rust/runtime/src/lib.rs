@@ -1,11 +1,47 @@
+#[cfg(any(feature = "json", feature = "serde"))]
+mod base64;
+pub mod codec;
+pub mod convert;
+pub mod descriptor;
+#[cfg(feature = "digest")]
+pub mod digest;
+pub mod dynamic;
+pub mod events;
+pub mod iter;
+#[cfg(feature = "json")]
+pub mod json;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod wellformed;
+
+pub use codec::{Decode, Encode, Encoded};
+pub use convert::CbgConvert;
+#[cfg(feature = "derive")]
+pub use cboragen_derive::{Decode, Encode};
+pub use dynamic::Value;
+pub use events::{diagnostic, Event};
+pub use iter::ArrayIter;
+#[cfg(feature = "half")]
+pub use half;
+pub use wellformed::{validate_well_formed, ItemInfo};
+
 /// CBOR Writer — growable byte buffer for encoding.
 pub struct Writer {
     buf: Vec<u8>,
+    deterministic: bool,
 }
 
 impl Writer {
     pub fn new() -> Self {
-        Writer { buf: Vec::with_capacity(256) }
+        Writer { buf: Vec::with_capacity(256), deterministic: false }
+    }
+
+    /// A writer that follows the deterministic encoding profile in SPEC.md,
+    /// so equal values always produce identical bytes. Generated encoders
+    /// already meet the rest of the profile; the writer additionally
+    /// replaces every NaN with the canonical quiet NaN.
+    pub fn deterministic() -> Self {
+        Writer { deterministic: true, ..Self::new() }
     }
 
     pub fn finish(self) -> Vec<u8> {
@@ -97,20 +133,31 @@ impl Writer {
 
     // Floats
     pub fn write_f16(&mut self, v: f32) {
+        let bits = if self.deterministic && v.is_nan() { 0x7e00 } else { f32_to_f16_bits(v) };
         self.buf.push(0xf9);
-        self.buf.extend_from_slice(&f32_to_f16_bits(v).to_be_bytes());
+        self.buf.extend_from_slice(&bits.to_be_bytes());
     }
 
     pub fn write_f32(&mut self, v: f32) {
+        let v = if self.deterministic && v.is_nan() { f32::NAN } else { v };
         self.buf.push(0xfa);
         self.buf.extend_from_slice(&v.to_bits().to_be_bytes());
     }
 
     pub fn write_f64(&mut self, v: f64) {
+        let v = if self.deterministic && v.is_nan() { f64::NAN } else { v };
         self.buf.push(0xfb);
         self.buf.extend_from_slice(&v.to_bits().to_be_bytes());
     }
 
+    /// Write an `f16` from a `half::f16`, bit for bit.
+    #[cfg(feature = "half")]
+    pub fn write_half(&mut self, v: half::f16) {
+        let bits = if self.deterministic && v.is_nan() { 0x7e00 } else { v.to_bits() };
+        self.buf.push(0xf9);
+        self.buf.extend_from_slice(&bits.to_be_bytes());
+    }
+
     // String and bytes
     pub fn write_string(&mut self, v: &str) {
         self.write_maj_len(0x60, v.len() as u64);
@@ -127,14 +174,73 @@ impl Writer {
         self.write_maj_len(0x80, len as u64);
     }
 
+    pub fn write_map_header(&mut self, len: usize) {
+        self.write_maj_len(0xa0, len as u64);
+    }
+
     pub fn write_tag_header(&mut self, tag: u64) {
         self.write_maj_len(0xc0, tag);
     }
 
+    /// Start an indefinite-length array; end it with `write_break`.
+    pub fn write_indefinite_array_start(&mut self) {
+        self.buf.push(0x9f);
+    }
+
+    /// Start an indefinite-length map; end it with `write_break`.
+    pub fn write_indefinite_map_start(&mut self) {
+        self.buf.push(0xbf);
+    }
+
+    /// Start an indefinite-length string. Write each chunk with
+    /// `write_string`, then `write_break`.
+    pub fn write_indefinite_string_start(&mut self) {
+        self.buf.push(0x7f);
+    }
+
+    /// Start an indefinite-length byte string. Write each chunk with
+    /// `write_bytes`, then `write_break`.
+    pub fn write_indefinite_bytes_start(&mut self) {
+        self.buf.push(0x5f);
+    }
+
+    pub fn write_break(&mut self) {
+        self.buf.push(0xff);
+    }
+
+    /// Write simple value `v` (major type 7).
+    ///
+    /// # Panics
+    ///
+    /// If `v` is 24 through 31, which CBOR reserves.
+    pub fn write_simple(&mut self, v: u8) {
+        match v {
+            0..=23 => self.buf.push(0xe0 | v),
+            24..=31 => panic!("simple value {v} is reserved"),
+            _ => {
+                self.buf.push(0xf8);
+                self.buf.push(v);
+            }
+        }
+    }
+
     pub fn write_byte(&mut self, b: u8) {
         self.buf.push(b);
     }
 
+    /// Append an already-encoded item as is, even on a deterministic writer.
+    pub fn write_raw_item(&mut self, item: &[u8]) {
+        self.buf.extend_from_slice(item);
+    }
+
+    /// `write_raw_item`, after checking that `item` is exactly one
+    /// well-formed CBOR item.
+    pub fn write_raw_item_checked(&mut self, item: &[u8]) -> Result<(), DecodeError> {
+        validate_well_formed(item)?;
+        self.write_raw_item(item);
+        Ok(())
+    }
+
     fn write_maj_len(&mut self, base: u8, n: u64) {
         if n <= 23 {
             self.buf.push(base | n as u8);
@@ -165,6 +271,12 @@ impl Default for Writer {
 pub enum DecodeError {
     UnexpectedEnd,
     InvalidData(String),
+    /// Recursive types nested deeper than the reader's limit.
+    DepthExceeded(u32),
+    /// An integer on the wire that doesn't fit the type being read.
+    Overflow(&'static str),
+    /// Bytes left over after the value (strict decoding only).
+    TrailingBytes(usize),
 }
 
 impl std::fmt::Display for DecodeError {
@@ -172,21 +284,133 @@ impl std::fmt::Display for DecodeError {
         match self {
             DecodeError::UnexpectedEnd => write!(f, "unexpected end of CBOR data"),
             DecodeError::InvalidData(msg) => write!(f, "invalid CBOR data: {msg}"),
+            DecodeError::DepthExceeded(max) => write!(f, "nesting exceeds the depth limit of {max}"),
+            DecodeError::Overflow(ty) => write!(f, "integer out of range for {ty}"),
+            DecodeError::TrailingBytes(n) => write!(f, "{n} trailing bytes after the value"),
         }
     }
 }
 
 impl std::error::Error for DecodeError {}
 
+/// Encode error returned when a dynamic value does not match its schema.
+#[derive(Debug)]
+pub enum EncodeError {
+    InvalidValue(String),
+}
+
+impl std::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::InvalidValue(msg) => write!(f, "invalid value: {msg}"),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// Error returned by a generated builder's `build()` when array lengths
+/// don't match the schema. Fields are named `Type.field`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    /// A fixed-length `[N]T` field has the wrong number of elements.
+    FixedLength { field: &'static str, expected: usize, actual: usize },
+    /// An external-length `[.len]T` field disagrees with its length field.
+    LengthMismatch { field: &'static str, len_field: &'static str, len: u64, actual: usize },
+}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::FixedLength { field, expected, actual } => {
+                write!(f, "{field} needs {expected} elements, got {actual}")
+            }
+            BuildError::LengthMismatch { field, len_field, len, actual } => {
+                write!(f, "{field} has {actual} elements but {len_field} is {len}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Default limit on how deeply recursive types may nest while decoding.
+pub const DEFAULT_MAX_DEPTH: u32 = 128;
+
+/// Which encodings a `Reader` accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReaderMode {
+    /// Exactly the encodings cboragen writers produce: fixed-width integers
+    /// with their full-width header and floats at their declared width.
+    #[default]
+    Exact,
+    /// Also accept data from generic CBOR encoders: integers in any width
+    /// whose value fits the target type, floats (or integers) whose value
+    /// the target float type represents exactly, and indefinite-length
+    /// strings and byte strings.
+    Lenient,
+    /// Only the canonical encoding of each value: varints and lengths must
+    /// be minimal, structs must have exactly the schema's fields, and struct
+    /// field gaps must be exactly null (`0xf6`).
+    /// Pair with `finish` to reject trailing bytes.
+    Strict,
+}
+
 /// CBOR Reader — reads from a byte slice.
+#[derive(Clone)]
 pub struct Reader<'a> {
     data: &'a [u8],
     pos: usize,
+    depth: u32,
+    max_depth: u32,
+    mode: ReaderMode,
+    /// Items opened by `next_event`.
+    open: Vec<events::Open>,
 }
 
 impl<'a> Reader<'a> {
     pub fn new(data: &'a [u8]) -> Self {
-        Reader { data, pos: 0 }
+        Self::with_mode(data, ReaderMode::Exact)
+    }
+
+    pub fn with_mode(data: &'a [u8], mode: ReaderMode) -> Self {
+        Reader { data, pos: 0, depth: 0, max_depth: DEFAULT_MAX_DEPTH, mode, open: Vec::new() }
+    }
+
+    pub fn mode(&self) -> ReaderMode {
+        self.mode
+    }
+
+    /// Byte offset of the next item in the input.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Limit how deeply recursive types may nest (default `DEFAULT_MAX_DEPTH`).
+    pub fn set_max_depth(&mut self, max_depth: u32) {
+        self.max_depth = max_depth;
+    }
+
+    /// Enter one level of a recursive type; generated decoders pair this
+    /// with `leave` so hostile input can't overflow the stack.
+    pub fn enter(&mut self) -> Result<(), DecodeError> {
+        if self.depth >= self.max_depth {
+            return Err(DecodeError::DepthExceeded(self.max_depth));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    /// Check that the whole input has been read.
+    pub fn finish(&self) -> Result<(), DecodeError> {
+        match self.data.len() - self.pos {
+            0 => Ok(()),
+            n => Err(DecodeError::TrailingBytes(n)),
+        }
     }
 
     pub fn read_bool(&mut self) -> Result<bool, DecodeError> {
@@ -200,6 +424,9 @@ impl<'a> Reader<'a> {
 
     // Fixed-width unsigned
     pub fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("u8");
+        }
         let b = self.read_byte()?;
         if b != 0x18 {
             return Err(DecodeError::InvalidData(format!("expected u8 header 0x18, got 0x{b:02x}")));
@@ -208,6 +435,9 @@ impl<'a> Reader<'a> {
     }
 
     pub fn read_u16(&mut self) -> Result<u16, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("u16");
+        }
         let b = self.read_byte()?;
         if b != 0x19 {
             return Err(DecodeError::InvalidData(format!("expected u16 header 0x19, got 0x{b:02x}")));
@@ -216,6 +446,9 @@ impl<'a> Reader<'a> {
     }
 
     pub fn read_u32(&mut self) -> Result<u32, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("u32");
+        }
         let b = self.read_byte()?;
         if b != 0x1a {
             return Err(DecodeError::InvalidData(format!("expected u32 header 0x1a, got 0x{b:02x}")));
@@ -224,6 +457,9 @@ impl<'a> Reader<'a> {
     }
 
     pub fn read_u64(&mut self) -> Result<u64, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("u64");
+        }
         let b = self.read_byte()?;
         if b != 0x1b {
             return Err(DecodeError::InvalidData(format!("expected u64 header 0x1b, got 0x{b:02x}")));
@@ -233,37 +469,49 @@ impl<'a> Reader<'a> {
 
     // Fixed-width signed
     pub fn read_i8(&mut self) -> Result<i8, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("i8");
+        }
         let b = self.read_byte()?;
         match b {
-            0x18 => Ok(self.read_byte()? as i8),
-            0x38 => Ok(-1 - self.read_byte()? as i8),
+            0x18 => signed(false, self.read_byte()? as u64, "i8"),
+            0x38 => signed(true, self.read_byte()? as u64, "i8"),
             _ => Err(DecodeError::InvalidData(format!("expected i8, got 0x{b:02x}"))),
         }
     }
 
     pub fn read_i16(&mut self) -> Result<i16, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("i16");
+        }
         let b = self.read_byte()?;
         match b {
-            0x19 => Ok(self.read_u16_raw()? as i16),
-            0x39 => Ok(-1 - self.read_u16_raw()? as i16),
+            0x19 => signed(false, self.read_u16_raw()? as u64, "i16"),
+            0x39 => signed(true, self.read_u16_raw()? as u64, "i16"),
             _ => Err(DecodeError::InvalidData(format!("expected i16, got 0x{b:02x}"))),
         }
     }
 
     pub fn read_i32(&mut self) -> Result<i32, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("i32");
+        }
         let b = self.read_byte()?;
         match b {
-            0x1a => Ok(self.read_u32_raw()? as i32),
-            0x3a => Ok(-1 - self.read_u32_raw()? as i32),
+            0x1a => signed(false, self.read_u32_raw()? as u64, "i32"),
+            0x3a => signed(true, self.read_u32_raw()? as u64, "i32"),
             _ => Err(DecodeError::InvalidData(format!("expected i32, got 0x{b:02x}"))),
         }
     }
 
     pub fn read_i64(&mut self) -> Result<i64, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_int("i64");
+        }
         let b = self.read_byte()?;
         match b {
-            0x1b => Ok(self.read_u64_raw()? as i64),
-            0x3b => Ok(-1 - self.read_u64_raw()? as i64),
+            0x1b => signed(false, self.read_u64_raw()?, "i64"),
+            0x3b => signed(true, self.read_u64_raw()?, "i64"),
             _ => Err(DecodeError::InvalidData(format!("expected i64, got 0x{b:02x}"))),
         }
     }
@@ -271,38 +519,34 @@ impl<'a> Reader<'a> {
     // Varints
     pub fn read_uvarint(&mut self) -> Result<u64, DecodeError> {
         let b = self.read_byte()?;
-        let ai = b & 0x1f;
-        match ai {
-            0..=23 => Ok(ai as u64),
-            24 => Ok(self.read_byte()? as u64),
-            25 => Ok(self.read_u16_raw()? as u64),
-            26 => Ok(self.read_u32_raw()? as u64),
-            27 => self.read_u64_raw(),
-            _ => Err(DecodeError::InvalidData("expected uvarint".into())),
+        let maj = b >> 5;
+        if maj != 0 {
+            return Err(DecodeError::InvalidData(format!("expected uvarint, got major type {maj}")));
         }
+        self.read_canonical_arg(b & 0x1f)
     }
 
     pub fn read_ivarint(&mut self) -> Result<i64, DecodeError> {
         let b = self.read_byte()?;
         let maj = b >> 5;
-        let ai = b & 0x1f;
-        let v: u64 = match ai {
-            0..=23 => ai as u64,
-            24 => self.read_byte()? as u64,
-            25 => self.read_u16_raw()? as u64,
-            26 => self.read_u32_raw()? as u64,
-            27 => self.read_u64_raw()?,
-            _ => return Err(DecodeError::InvalidData("expected ivarint".into())),
-        };
+        let v = self.read_canonical_arg(b & 0x1f)?;
         match maj {
-            0 => Ok(v as i64),
-            1 => Ok(-1 - v as i64),
+            0 | 1 => signed(maj == 1, v, "ivarint"),
             _ => Err(DecodeError::InvalidData(format!("expected ivarint, got major type {maj}"))),
         }
     }
 
     // Floats
     pub fn read_f16(&mut self) -> Result<f32, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            let v = self.read_any_float("f16")?;
+            let f = v as f32;
+            return if v.is_nan() || (f as f64 == v && f16_bits_to_f32(f32_to_f16_bits(f)) == f) {
+                Ok(f)
+            } else {
+                Err(DecodeError::Overflow("f16"))
+            };
+        }
         let b = self.read_byte()?;
         if b != 0xf9 {
             return Err(DecodeError::InvalidData(format!("expected f16 header 0xf9, got 0x{b:02x}")));
@@ -312,6 +556,11 @@ impl<'a> Reader<'a> {
     }
 
     pub fn read_f32(&mut self) -> Result<f32, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            let v = self.read_any_float("f32")?;
+            let f = v as f32;
+            return if v.is_nan() || f as f64 == v { Ok(f) } else { Err(DecodeError::Overflow("f32")) };
+        }
         let b = self.read_byte()?;
         if b != 0xfa {
             return Err(DecodeError::InvalidData(format!("expected f32 header 0xfa, got 0x{b:02x}")));
@@ -321,6 +570,9 @@ impl<'a> Reader<'a> {
     }
 
     pub fn read_f64(&mut self) -> Result<f64, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            return self.read_any_float("f64");
+        }
         let b = self.read_byte()?;
         if b != 0xfb {
             return Err(DecodeError::InvalidData(format!("expected f64 header 0xfb, got 0x{b:02x}")));
@@ -329,33 +581,200 @@ impl<'a> Reader<'a> {
         Ok(f64::from_bits(bits))
     }
 
+    /// Read an `f16` as a `half::f16`, bit for bit.
+    #[cfg(feature = "half")]
+    pub fn read_half(&mut self) -> Result<half::f16, DecodeError> {
+        if self.mode == ReaderMode::Lenient {
+            // read_f16 only returns values f16 represents exactly
+            return Ok(half::f16::from_f32(self.read_f16()?));
+        }
+        let b = self.read_byte()?;
+        if b != 0xf9 {
+            return Err(DecodeError::InvalidData(format!("expected f16 header 0xf9, got 0x{b:02x}")));
+        }
+        Ok(half::f16::from_bits(self.read_u16_raw()?))
+    }
+
     // String and bytes
     pub fn read_string(&mut self) -> Result<String, DecodeError> {
-        let len = self.read_maj_len(3)?;
-        if self.pos + len > self.data.len() {
-            return Err(DecodeError::UnexpectedEnd);
+        let mut s = String::new();
+        self.read_string_into(&mut s)?;
+        Ok(s)
+    }
+
+    /// Read a string borrowed from the input. Indefinite-length strings
+    /// can't be borrowed; use `read_string` for lenient input.
+    pub fn read_str(&mut self) -> Result<&'a str, DecodeError> {
+        let b = self.read_slice(3)?;
+        std::str::from_utf8(b).map_err(|e| DecodeError::InvalidData(format!("invalid UTF-8 in CBOR string: {e}")))
+    }
+
+    /// Read a string into `out`, reusing its capacity.
+    pub fn read_string_into(&mut self, out: &mut String) -> Result<(), DecodeError> {
+        if self.at_indefinite(0x7f)? {
+            // On error `out` is left empty but keeps its capacity
+            let mut buf = std::mem::take(out).into_bytes();
+            if let Err(e) = self.read_chunks(3, &mut buf) {
+                *out = empty_string(buf);
+                return Err(e);
+            }
+            return match String::from_utf8(buf) {
+                Ok(s) => {
+                    *out = s;
+                    Ok(())
+                }
+                Err(e) => {
+                    let err = DecodeError::InvalidData(format!("invalid UTF-8 in CBOR string: {}", e.utf8_error()));
+                    *out = empty_string(e.into_bytes());
+                    Err(err)
+                }
+            };
         }
-        let s = std::str::from_utf8(&self.data[self.pos..self.pos + len])
-            .map_err(|e| DecodeError::InvalidData(format!("invalid UTF-8 in CBOR string: {e}")))?;
-        self.pos += len;
-        Ok(s.to_string())
+        let s = self.read_str()?;
+        out.clear();
+        out.push_str(s);
+        Ok(())
     }
 
     pub fn read_bytes(&mut self) -> Result<Vec<u8>, DecodeError> {
-        let len = self.read_maj_len(2)?;
-        if self.pos + len > self.data.len() {
-            return Err(DecodeError::UnexpectedEnd);
-        }
-        let b = self.data[self.pos..self.pos + len].to_vec();
-        self.pos += len;
+        let mut b = Vec::new();
+        self.read_bytes_into(&mut b)?;
         Ok(b)
     }
 
+    /// Read a byte string borrowed from the input. Indefinite-length byte
+    /// strings can't be borrowed; use `read_bytes` for lenient input.
+    pub fn read_byte_slice(&mut self) -> Result<&'a [u8], DecodeError> {
+        self.read_slice(2)
+    }
+
+    /// Read a byte string into `out`, reusing its capacity.
+    pub fn read_bytes_into(&mut self, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        if self.at_indefinite(0x5f)? {
+            return self.read_chunks(2, out);
+        }
+        let b = self.read_byte_slice()?;
+        out.clear();
+        out.extend_from_slice(b);
+        Ok(())
+    }
+
     // Structural
     pub fn read_array_header(&mut self) -> Result<usize, DecodeError> {
         self.read_maj_len(4)
     }
 
+    pub fn read_map_header(&mut self) -> Result<usize, DecodeError> {
+        self.read_maj_len(5)
+    }
+
+    /// Read a tag header (major type 6) and return the tag number.
+    pub fn read_tag_header(&mut self) -> Result<u64, DecodeError> {
+        let b = self.read_byte()?;
+        let maj = b >> 5;
+        if maj != 6 {
+            return Err(DecodeError::InvalidData(format!("unexpected major type {maj}, expected 6")));
+        }
+        self.read_canonical_arg(b & 0x1f)
+    }
+
+    /// Read the array header of a struct whose highest field rank is
+    /// `fields - 1`. Strict mode rejects arrays with fields missing or with
+    /// fields the schema doesn't know.
+    pub fn read_struct_header(&mut self, fields: usize) -> Result<usize, DecodeError> {
+        let len = self.read_array_header()?;
+        if self.mode == ReaderMode::Strict && len != fields {
+            return Err(DecodeError::InvalidData(format!("expected struct of {fields} fields, got {len}")));
+        }
+        Ok(len)
+    }
+
+    /// Read the marker of an optional value: `false` for absent (`0x00`),
+    /// `true` for present (tag 1, followed by the value).
+    pub fn read_option_tag(&mut self) -> Result<bool, DecodeError> {
+        if self.try_read_exact(0x00)? {
+            return Ok(false);
+        }
+        match self.read_tag_header()? {
+            1 => Ok(true),
+            t => Err(DecodeError::InvalidData(format!("expected optional (0x00 or tag 1), got tag {t}"))),
+        }
+    }
+
+    /// The tag number of the next item, if it is a tag, without consuming it.
+    pub fn peek_tag(&self) -> Result<Option<u64>, DecodeError> {
+        if self.peek_major_type()? != 6 {
+            return Ok(None);
+        }
+        // A reader over the rest of the input, so the open-item stack isn't copied
+        Reader::with_mode(&self.data[self.pos..], self.mode).read_tag_header().map(Some)
+    }
+
+    /// Major type (0–7) of the next item, without consuming it.
+    pub fn peek_major_type(&self) -> Result<u8, DecodeError> {
+        Ok(self.peek_byte()? >> 5)
+    }
+
+    pub fn read_null(&mut self) -> Result<(), DecodeError> {
+        let b = self.read_byte()?;
+        if b != 0xf6 {
+            return Err(DecodeError::InvalidData(format!("expected null, got 0x{b:02x}")));
+        }
+        Ok(())
+    }
+
+    /// Consume the next item if it is null.
+    pub fn try_read_null(&mut self) -> Result<bool, DecodeError> {
+        self.try_read_exact(0xf6)
+    }
+
+    /// Read a simple value (major type 7, other than floats and the break code).
+    pub fn read_simple(&mut self) -> Result<u8, DecodeError> {
+        let b = self.read_byte()?;
+        match b {
+            0xe0..=0xf7 => Ok(b & 0x1f),
+            0xf8 => match self.read_byte()? {
+                v @ 32.. => Ok(v),
+                v => Err(DecodeError::InvalidData(format!("simple value {v} must use the one-byte form"))),
+            },
+            _ => Err(DecodeError::InvalidData(format!("expected simple value, got 0x{b:02x}"))),
+        }
+    }
+
+    /// Read the start of an indefinite-length array; its items follow until
+    /// `try_read_break` returns true.
+    pub fn read_indefinite_array_start(&mut self) -> Result<(), DecodeError> {
+        self.read_initial(0x9f, "indefinite array")
+    }
+
+    /// Read the start of an indefinite-length map; its keys and values
+    /// follow until `try_read_break` returns true.
+    pub fn read_indefinite_map_start(&mut self) -> Result<(), DecodeError> {
+        self.read_initial(0xbf, "indefinite map")
+    }
+
+    /// Read the start of an indefinite-length string; its chunks follow as
+    /// definite-length strings until `try_read_break` returns true.
+    pub fn read_indefinite_string_start(&mut self) -> Result<(), DecodeError> {
+        self.read_initial(0x7f, "indefinite string")
+    }
+
+    /// Read the start of an indefinite-length byte string; its chunks follow
+    /// as definite-length byte strings until `try_read_break` returns true.
+    pub fn read_indefinite_bytes_start(&mut self) -> Result<(), DecodeError> {
+        self.read_initial(0x5f, "indefinite byte string")
+    }
+
+    pub fn read_break(&mut self) -> Result<(), DecodeError> {
+        self.read_initial(0xff, "break")
+    }
+
+    /// Consume the next byte if it is the break code that ends an
+    /// indefinite-length item.
+    pub fn try_read_break(&mut self) -> Result<bool, DecodeError> {
+        self.try_read_exact(0xff)
+    }
+
     pub fn read_byte(&mut self) -> Result<u8, DecodeError> {
         let b = *self.data.get(self.pos).ok_or(DecodeError::UnexpectedEnd)?;
         self.pos += 1;
@@ -366,69 +785,50 @@ impl<'a> Reader<'a> {
         self.data.get(self.pos).copied().ok_or(DecodeError::UnexpectedEnd)
     }
 
+    /// Skip the next item and return its encoded bytes.
+    pub fn read_raw_item(&mut self) -> Result<&'a [u8], DecodeError> {
+        let start = self.pos;
+        self.skip()?;
+        Ok(&self.data[start..self.pos])
+    }
+
+    /// Skip the next item, checking that it is well-formed (see
+    /// [`validate_well_formed`]). Nesting depth is not limited.
     pub fn skip(&mut self) -> Result<(), DecodeError> {
+        wellformed::scan(self)?;
+        self.item_read();
+        Ok(())
+    }
+
+    /// Skip a struct field the schema has no field for. Writers fill these
+    /// gaps with null; strict mode accepts nothing else.
+    pub fn skip_gap(&mut self) -> Result<(), DecodeError> {
+        if self.mode != ReaderMode::Strict {
+            return self.skip();
+        }
         let b = self.read_byte()?;
-        let maj = b >> 5;
-        let ai = b & 0x1f;
-
-        if maj == 7 {
-            // simple/float
-            let skip_len = match ai {
-                0..=23 => 0,
-                24 => 1,
-                25 => 2,
-                26 => 4,
-                27 => 8,
-                _ => 0,
-            };
-            if self.pos + skip_len > self.data.len() {
-                return Err(DecodeError::UnexpectedEnd);
-            }
-            self.pos += skip_len;
-            return Ok(());
-        }
-
-        let len: usize = if ai <= 23 {
-            ai as usize
-        } else if ai == 24 {
-            self.read_byte()? as usize
-        } else if ai == 25 {
-            self.read_u16_raw()? as usize
-        } else if ai == 26 {
-            self.read_u32_raw()? as usize
-        } else if ai == 27 {
-            self.read_u64_raw()? as usize
-        } else if ai == 31 {
-            // indefinite length
-            loop {
-                let pb = *self.data.get(self.pos).ok_or(DecodeError::UnexpectedEnd)?;
-                if pb == 0xff {
-                    break;
-                }
-                self.skip()?;
-            }
-            self.pos += 1; // consume break
-            return Ok(());
-        } else {
-            return Err(DecodeError::InvalidData(format!("unsupported additional info {ai} in skip")));
-        };
+        if b != 0xf6 {
+            return Err(DecodeError::InvalidData(format!("expected null in field gap, got 0x{b:02x}")));
+        }
+        Ok(())
+    }
 
-        match maj {
-            0 | 1 => {} // integer, value already consumed
-            2 | 3 => {
-                if self.pos + len > self.data.len() {
-                    return Err(DecodeError::UnexpectedEnd);
-                }
-                self.pos += len;
-            }
-            4 => { for _ in 0..len { self.skip()?; } }
-            5 => { for _ in 0..len * 2 { self.skip()?; } }
-            6 => { self.skip()?; }
-            _ => return Err(DecodeError::InvalidData(format!("unexpected major type {maj} in skip"))),
+    fn read_initial(&mut self, expected: u8, what: &str) -> Result<(), DecodeError> {
+        let b = self.read_byte()?;
+        if b != expected {
+            return Err(DecodeError::InvalidData(format!("expected {what} (0x{expected:02x}), got 0x{b:02x}")));
         }
         Ok(())
     }
 
+    fn try_read_exact(&mut self, b: u8) -> Result<bool, DecodeError> {
+        let found = self.peek_byte()? == b;
+        if found {
+            self.pos += 1;
+        }
+        Ok(found)
+    }
+
     fn read_u16_raw(&mut self) -> Result<u16, DecodeError> {
         if self.pos + 2 > self.data.len() {
             return Err(DecodeError::UnexpectedEnd);
@@ -464,22 +864,122 @@ impl<'a> Reader<'a> {
         Ok(v)
     }
 
+    /// Read a string or byte string header and borrow its contents.
+    fn read_slice(&mut self, major: u8) -> Result<&'a [u8], DecodeError> {
+        let len = self.read_maj_len(major)?;
+        self.take(len)
+    }
+
+    /// Borrow the next `len` bytes of input.
+    fn take(&mut self, len: usize) -> Result<&'a [u8], DecodeError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len()).ok_or(DecodeError::UnexpectedEnd)?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
     fn read_maj_len(&mut self, expected_major: u8) -> Result<usize, DecodeError> {
         let b = self.read_byte()?;
         let maj = b >> 5;
         if maj != expected_major {
             return Err(DecodeError::InvalidData(format!("unexpected major type {maj}, expected {expected_major}")));
         }
-        let ai = b & 0x1f;
+        let len = self.read_canonical_arg(b & 0x1f)?;
+        usize::try_from(len).map_err(|_| DecodeError::Overflow("usize"))
+    }
+
+    /// Read the argument of a head whose initial byte had additional info `ai`.
+    fn read_arg(&mut self, ai: u8) -> Result<u64, DecodeError> {
         match ai {
-            0..=23 => Ok(ai as usize),
-            24 => Ok(self.read_byte()? as usize),
-            25 => Ok(self.read_u16_raw()? as usize),
-            26 => Ok(self.read_u32_raw()? as usize),
-            27 => Ok(self.read_u64_raw()? as usize),
+            0..=23 => Ok(ai as u64),
+            24 => Ok(self.read_byte()? as u64),
+            25 => Ok(self.read_u16_raw()? as u64),
+            26 => Ok(self.read_u32_raw()? as u64),
+            27 => self.read_u64_raw(),
             _ => Err(DecodeError::InvalidData(format!("unsupported additional info {ai}"))),
         }
     }
+
+    /// `read_arg`, rejecting a non-minimal argument in strict mode.
+    fn read_canonical_arg(&mut self, ai: u8) -> Result<u64, DecodeError> {
+        let v = self.read_arg(ai)?;
+        let min = match ai {
+            24 => 24,
+            25 => 0x100,
+            26 => 0x1_0000,
+            27 => 0x1_0000_0000,
+            _ => 0,
+        };
+        if self.mode == ReaderMode::Strict && v < min {
+            return Err(DecodeError::InvalidData(format!("non-minimal encoding of {v}")));
+        }
+        Ok(v)
+    }
+
+    /// Read an integer of any width (lenient mode) if its value fits in `T`.
+    fn read_int<T: TryFrom<i128>>(&mut self, ty: &'static str) -> Result<T, DecodeError> {
+        let b = self.read_byte()?;
+        let maj = b >> 5;
+        if maj > 1 {
+            return Err(DecodeError::InvalidData(format!("expected {ty}, got 0x{b:02x}")));
+        }
+        let arg = self.read_arg(b & 0x1f)?;
+        signed(maj == 1, arg, ty)
+    }
+
+    /// Read a float of any width, or an integer with an exact f64 value,
+    /// as an f64 (lenient mode).
+    fn read_any_float(&mut self, ty: &'static str) -> Result<f64, DecodeError> {
+        match self.peek_byte()? {
+            0xf9 => {
+                self.pos += 1;
+                Ok(f16_bits_to_f32(self.read_u16_raw()?) as f64)
+            }
+            0xfa => {
+                self.pos += 1;
+                Ok(f32::from_bits(self.read_u32_raw()?) as f64)
+            }
+            0xfb => {
+                self.pos += 1;
+                Ok(f64::from_bits(self.read_u64_raw()?))
+            }
+            _ => {
+                let v: i128 = self.read_int(ty)?;
+                let f = v as f64;
+                if f as i128 == v { Ok(f) } else { Err(DecodeError::Overflow(ty)) }
+            }
+        }
+    }
+
+    /// Whether the next item is an indefinite-length string (`initial`
+    /// 0x7f) or byte string (0x5f) that lenient mode should accept.
+    fn at_indefinite(&self, initial: u8) -> Result<bool, DecodeError> {
+        Ok(self.mode == ReaderMode::Lenient && self.peek_byte()? == initial)
+    }
+
+    /// Concatenate the definite-length chunks of an indefinite-length
+    /// string or byte string into `out`.
+    fn read_chunks(&mut self, major: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        out.clear();
+        self.pos += 1;
+        while !self.try_read_break()? {
+            out.extend_from_slice(self.read_slice(major)?);
+        }
+        Ok(())
+    }
+}
+
+/// An empty string that keeps the capacity of `buf`.
+fn empty_string(mut buf: Vec<u8>) -> String {
+    buf.clear();
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+/// The integer with major type 0 (`negative` false) or 1 argument `arg`, if
+/// it fits in `T`.
+fn signed<T: TryFrom<i128>>(negative: bool, arg: u64, ty: &'static str) -> Result<T, DecodeError> {
+    let v = if negative { -1 - arg as i128 } else { arg as i128 };
+    T::try_from(v).map_err(|_| DecodeError::Overflow(ty))
 }
 
 // === IEEE 754 half-precision (f16) conversion ===
@@ -504,20 +1004,28 @@ fn f32_to_f16_bits(v: f32) -> u16 {
         // Overflow → Inf
         return sign | 0x7c00;
     }
-    if unbiased < -24 {
-        // Underflow → zero
-        return sign;
-    }
-    if unbiased < -14 {
-        // Subnormal
-        let shift = -1 - unbiased + 10;
-        let frac_with_hidden = frac | 0x0080_0000;
-        return sign | (frac_with_hidden >> shift) as u16;
-    }
 
-    let h_exp = ((unbiased + 15) as u16) << 10;
-    let h_frac = (frac >> 13) as u16;
-    sign | h_exp | h_frac
+    // Split into the f16 bits and the low `shift` bits rounded away
+    let (h, mant, shift) = if unbiased >= -14 {
+        ((((unbiased + 15) as u32) << 10) | (frac >> 13), frac, 13)
+    } else {
+        // Subnormal: the hidden bit becomes explicit
+        let shift = -1 - unbiased;
+        if shift > 24 {
+            // Below half the smallest subnormal → zero
+            return sign;
+        }
+        let mant = frac | 0x0080_0000;
+        (mant >> shift, mant, shift)
+    };
+
+    // Round to nearest, ties to even. A carry out of the fraction bumps the
+    // exponent, which is also right for the largest subnormal and for
+    // rounding up to Inf.
+    let rest = mant & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    let h = h + (rest > half || (rest == half && h & 1 == 1)) as u32;
+    sign | h as u16
 }
 
 fn f16_bits_to_f32(bits: u16) -> f32 {
@@ -557,6 +1065,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn roundtrip_bool() -> Result<(), DecodeError> {
         let mut w = Writer::new();
         w.write_bool(true);
@@ -623,6 +1132,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn roundtrip_floats() -> Result<(), DecodeError> {
         let mut w = Writer::new();
         w.write_f32(3.14);
@@ -648,6 +1158,30 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn read_into_reuses_buffers() -> Result<(), DecodeError> {
+        let mut w = Writer::new();
+        w.write_string("hi");
+        w.write_bytes(&[4, 5]);
+        let data = w.finish();
+        let mut r = Reader::new(&data);
+        let mut s = String::with_capacity(64);
+        s.push_str("previous");
+        let mut b = vec![9; 10];
+        r.read_string_into(&mut s)?;
+        r.read_bytes_into(&mut b)?;
+        assert_eq!((s.as_str(), s.capacity() >= 64), ("hi", true));
+        assert_eq!(b, [4, 5]);
+
+        // A failed read keeps the capacity too
+        let lenient = |data: &'static [u8]| Reader::with_mode(data, ReaderMode::Lenient);
+        for bad in [&[0x7f, 0x61, 0xff, 0xff][..], &[0x7f, 0x41, b'a', 0xff], &[0x7f, 0x61]] {
+            assert!(lenient(bad).read_string_into(&mut s).is_err());
+            assert_eq!((s.as_str(), s.capacity() >= 64), ("", true));
+        }
+        Ok(())
+    }
+
     #[test]
     fn roundtrip_array() -> Result<(), DecodeError> {
         let mut w = Writer::new();
@@ -665,6 +1199,85 @@ mod tests {
     }
 
     #[test]
+    fn low_level_api() -> Result<(), DecodeError> {
+        let mut w = Writer::new();
+        w.write_tag_header(u64::MAX);
+        w.write_map_header(1);
+        w.write_null();
+        w.write_simple(16);
+        w.write_simple(255);
+        w.write_indefinite_array_start();
+        w.write_indefinite_map_start();
+        w.write_break();
+        w.write_indefinite_string_start();
+        w.write_string("ab");
+        w.write_break();
+        w.write_indefinite_bytes_start();
+        w.write_break();
+        w.write_break();
+        let data = w.finish();
+        assert_eq!(data[..9], [0xdb, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+
+        let mut r = Reader::new(&data);
+        assert_eq!(r.peek_major_type()?, 6);
+        assert_eq!(r.peek_tag()?, Some(u64::MAX));
+        assert_eq!(r.read_tag_header()?, u64::MAX);
+        assert_eq!(r.peek_tag()?, None);
+        assert_eq!(r.read_map_header()?, 1);
+        assert!(!r.try_read_break()?);
+        assert!(r.try_read_null()?);
+        assert!(!r.try_read_null()?);
+        assert_eq!(r.read_simple()?, 16);
+        assert_eq!(r.read_simple()?, 255);
+        r.read_indefinite_array_start()?;
+        r.read_indefinite_map_start()?;
+        assert!(r.try_read_break()?);
+        r.read_indefinite_string_start()?;
+        assert_eq!(r.read_str()?, "ab");
+        r.read_break()?;
+        r.read_indefinite_bytes_start()?;
+        r.read_break()?;
+        assert!(r.try_read_break()?);
+        r.finish()?;
+
+        assert!(Reader::new(&[0xf8, 0x10]).read_simple().is_err());
+        assert!(Reader::new(&[0xf9, 0, 0]).read_simple().is_err());
+        assert!(Reader::new(&[0xc1]).read_null().is_err());
+        assert!(Reader::with_mode(&[0xd8, 0x01], ReaderMode::Strict).read_tag_header().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn raw_items() -> Result<(), DecodeError> {
+        let mut w = Writer::new();
+        w.write_array_header(2);
+        w.write_string("a");
+        w.write_u32(7);
+        w.write_bool(true);
+        let data = w.finish();
+
+        let mut r = Reader::new(&data);
+        let item = r.read_raw_item()?;
+        assert_eq!(item, [0x82, 0x61, b'a', 0x1a, 0, 0, 0, 7]);
+        assert!(r.read_bool()?);
+
+        let mut w = Writer::new();
+        w.write_raw_item_checked(item)?;
+        assert!(w.write_raw_item_checked(&item[..4]).is_err());
+        assert!(w.write_raw_item_checked(&data).is_err());
+        assert_eq!(w.finish(), item);
+
+        let e = Encoded::<String>::new(&"hi".to_string());
+        assert_eq!(e.as_bytes(), [0x62, b'h', b'i']);
+        assert_eq!(e.decode()?, "hi");
+        assert_eq!(Encoded::<String>::from_raw(vec![0x62, b'h', b'i'])?, e);
+        assert!(Encoded::<String>::from_raw(vec![0x62, b'h']).is_err());
+        assert_eq!(Option::<Encoded<String>>::decode(&Some(e.clone()).encode())?, Some(e));
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_skip() -> Result<(), DecodeError> {
         let mut w = Writer::new();
         w.write_u32(42);
@@ -689,6 +1302,86 @@ mod tests {
         }
     }
 
+    /// The value of f16 `bits` computed from its definition.
+    fn f16_value(bits: u16) -> f64 {
+        let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+        let exp = ((bits >> 10) & 0x1f) as i32;
+        let frac = (bits & 0x03ff) as f64;
+        match exp {
+            0 => sign * frac * 2f64.powi(-24),
+            31 if frac == 0.0 => sign * f64::INFINITY,
+            31 => f64::NAN,
+            _ => sign * (1.0 + frac / 1024.0) * 2f64.powi(exp - 15),
+        }
+    }
+
+    #[test]
+    fn f16_all_bit_patterns() {
+        for bits in 0..=u16::MAX {
+            let v = f16_bits_to_f32(bits);
+            let expected = f16_value(bits);
+            if expected.is_nan() {
+                assert!(v.is_nan(), "{bits:#06x}");
+                assert!(f32_to_f16_bits(v) & 0x7fff > 0x7c00, "{bits:#06x}");
+                continue;
+            }
+            assert_eq!(v as f64, expected, "{bits:#06x}");
+            assert_eq!(v.is_sign_negative(), bits & 0x8000 != 0, "{bits:#06x}");
+            assert_eq!(f32_to_f16_bits(v), bits, "{bits:#06x}");
+        }
+    }
+
+    #[test]
+    fn f16_rounds_to_nearest_even() {
+        // Every gap between adjacent finite f16 values, including the one
+        // above 65504 whose upper end is Inf
+        for bits in 0..0x7c00u16 {
+            let lo = f16_bits_to_f32(bits);
+            let hi = f16_value(bits + 1);
+            let hi = if hi.is_infinite() { 65536.0 } else { hi };
+            let mid = ((lo as f64 + hi) / 2.0) as f32;
+            let up = f32::from_bits(mid.to_bits() + 1);
+            let down = f32::from_bits(mid.to_bits() - 1);
+            let even = if bits & 1 == 0 { bits } else { bits + 1 };
+            for sign in [0, 0x8000] {
+                let neg = |v: f32| if sign == 0 { v } else { -v };
+                assert_eq!(f32_to_f16_bits(neg(mid)), sign | even, "{bits:#06x} tie");
+                assert_eq!(f32_to_f16_bits(neg(up)), sign | (bits + 1), "{bits:#06x} above");
+                if down > lo {
+                    assert_eq!(f32_to_f16_bits(neg(down)), sign | bits, "{bits:#06x} below");
+                }
+            }
+        }
+        assert_eq!(f32_to_f16_bits(2f32.powi(-25)), 0);
+        assert_eq!(f32_to_f16_bits(f32::from_bits(2f32.powi(-25).to_bits() + 1)), 1);
+        assert_eq!(f32_to_f16_bits(f32::MIN_POSITIVE / 2.0), 0);
+        assert_eq!(f32_to_f16_bits(1e10), 0x7c00);
+        assert_eq!(f32_to_f16_bits(-1e10), 0xfc00);
+    }
+
+    #[cfg(feature = "half")]
+    #[test]
+    fn f16_matches_half_crate() -> Result<(), DecodeError> {
+        // A spread of f32 bit patterns covering every exponent
+        for bits in (0..=u32::MAX).step_by(4099) {
+            let v = f32::from_bits(bits);
+            let expected = half::f16::from_f32(v);
+            if v.is_nan() {
+                assert!(f16_bits_to_f32(f32_to_f16_bits(v)).is_nan());
+            } else {
+                assert_eq!(f32_to_f16_bits(v), expected.to_bits(), "{v:e}");
+            }
+        }
+        let mut w = Writer::new();
+        w.write_half(half::f16::from_f32(1.5));
+        w.write_f16(-0.25);
+        let data = w.finish();
+        let mut r = Reader::new(&data);
+        assert_eq!(r.read_f16()?, 1.5);
+        assert_eq!(r.read_half()?, half::f16::from_f32(-0.25));
+        Ok(())
+    }
+
     #[test]
     fn decode_error_on_empty() {
         let mut r = Reader::new(&[]);
@@ -703,4 +1396,226 @@ mod tests {
         let err = r.read_bool().unwrap_err();
         assert!(matches!(err, DecodeError::InvalidData(_)));
     }
+
+    #[test]
+    fn depth_limit() -> Result<(), DecodeError> {
+        let mut r = Reader::new(&[]);
+        r.set_max_depth(2);
+        r.enter()?;
+        r.enter()?;
+        assert!(matches!(r.enter(), Err(DecodeError::DepthExceeded(2))));
+        r.leave();
+        r.enter()?;
+        Ok(())
+    }
+
+    fn overflows<T: std::fmt::Debug>(res: Result<T, DecodeError>) -> bool {
+        matches!(res, Err(DecodeError::Overflow(_)))
+    }
+
+    #[test]
+    fn signed_ints_exhaustive() -> Result<(), DecodeError> {
+        for v in i8::MIN..=i8::MAX {
+            let mut w = Writer::new();
+            w.write_i8(v);
+            assert_eq!(Reader::new(&w.finish()).read_i8()?, v);
+        }
+        for v in i16::MIN..=i16::MAX {
+            let mut w = Writer::new();
+            w.write_i16(v);
+            assert_eq!(Reader::new(&w.finish()).read_i16()?, v);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn signed_int_bounds() -> Result<(), DecodeError> {
+        for v in [i32::MIN, i32::MIN + 1, -1, 0, i32::MAX] {
+            let mut w = Writer::new();
+            w.write_i32(v);
+            assert_eq!(Reader::new(&w.finish()).read_i32()?, v);
+        }
+        for v in [i64::MIN, i64::MIN + 1, -1, 0, i64::MAX] {
+            let mut w = Writer::new();
+            w.write_i64(v);
+            assert_eq!(Reader::new(&w.finish()).read_i64()?, v);
+        }
+
+        // One past each end: 128 / -129, 32768 / -32769, ...
+        assert!(overflows(Reader::new(&[0x18, 0x80]).read_i8()));
+        assert!(overflows(Reader::new(&[0x38, 0x80]).read_i8()));
+        assert!(overflows(Reader::new(&[0x18, 0xff]).read_i8()));
+        assert!(overflows(Reader::new(&[0x38, 0xff]).read_i8()));
+        assert!(overflows(Reader::new(&[0x19, 0x80, 0x00]).read_i16()));
+        assert!(overflows(Reader::new(&[0x39, 0x80, 0x00]).read_i16()));
+        assert!(overflows(Reader::new(&[0x1a, 0x80, 0, 0, 0]).read_i32()));
+        assert!(overflows(Reader::new(&[0x3a, 0x80, 0, 0, 0]).read_i32()));
+        assert!(overflows(Reader::new(&[0x1b, 0x80, 0, 0, 0, 0, 0, 0, 0]).read_i64()));
+        assert!(overflows(Reader::new(&[0x3b, 0x80, 0, 0, 0, 0, 0, 0, 0]).read_i64()));
+        assert!(overflows(Reader::new(&[0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).read_i64()));
+        Ok(())
+    }
+
+    #[test]
+    fn varint_bounds() -> Result<(), DecodeError> {
+        for v in [0, 23, 24, 255, 256, 65535, 65536, u32::MAX as u64, u32::MAX as u64 + 1, u64::MAX] {
+            let mut w = Writer::new();
+            w.write_uvarint(v);
+            assert_eq!(Reader::new(&w.finish()).read_uvarint()?, v);
+        }
+        for v in [i64::MIN, i64::MIN + 1, -25, -24, -1, 0, 23, 24, i64::MAX] {
+            let mut w = Writer::new();
+            w.write_ivarint(v);
+            assert_eq!(Reader::new(&w.finish()).read_ivarint()?, v);
+        }
+
+        // Major 0 or 1 arguments of 2^63 and above don't fit an i64
+        let mut w = Writer::new();
+        w.write_uvarint(1 << 63);
+        w.write_uvarint(u64::MAX);
+        let data = w.finish();
+        let mut r = Reader::new(&data);
+        assert!(overflows(r.read_ivarint()));
+        assert!(overflows(r.read_ivarint()));
+        assert!(overflows(Reader::new(&[0x3b, 0x80, 0, 0, 0, 0, 0, 0, 0]).read_ivarint()));
+        assert!(overflows(Reader::new(&[0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).read_ivarint()));
+
+        // A negative integer is not a uvarint
+        assert!(matches!(Reader::new(&[0x20]).read_uvarint(), Err(DecodeError::InvalidData(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn huge_lengths_are_errors() {
+        let bytes = [0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert!(Reader::new(&bytes).read_bytes().is_err());
+        let text = [0x7b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, b'a'];
+        assert!(Reader::new(&text).read_string().is_err());
+    }
+
+    #[test]
+    fn lenient_integers() -> Result<(), DecodeError> {
+        let lenient = |data: &'static [u8]| Reader::with_mode(data, ReaderMode::Lenient);
+        assert!(Reader::new(&[0x05]).read_u32().is_err());
+        assert_eq!(lenient(&[0x05]).read_u32()?, 5);
+        assert_eq!(lenient(&[0x18, 0xff]).read_u64()?, 255);
+        assert_eq!(lenient(&[0x1b, 0, 0, 0, 0, 0, 0, 0, 7]).read_u8()?, 7);
+        assert_eq!(lenient(&[0x20]).read_i64()?, -1);
+        assert_eq!(lenient(&[0x38, 0x7f]).read_i8()?, -128);
+        assert_eq!(lenient(&[0x19, 0x7f, 0xff]).read_i16()?, i16::MAX);
+        assert!(overflows(lenient(&[0x19, 0x01, 0x00]).read_u8()));
+        assert!(overflows(lenient(&[0x38, 0x80]).read_i8()));
+        assert!(overflows(lenient(&[0x20]).read_u32()));
+        assert!(lenient(&[0xf5]).read_u8().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_floats() -> Result<(), DecodeError> {
+        let lenient = |data: &'static [u8]| Reader::with_mode(data, ReaderMode::Lenient);
+        // 1.5 as f16, f32 and f64
+        let f16 = &[0xf9, 0x3e, 0x00];
+        let f32 = &[0xfa, 0x3f, 0xc0, 0x00, 0x00];
+        let f64 = &[0xfb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0];
+        assert!(Reader::new(f16).read_f64().is_err());
+        for data in [&f16[..], &f32[..], &f64[..]] {
+            assert_eq!(lenient(data).read_f16()?, 1.5);
+            assert_eq!(lenient(data).read_f32()?, 1.5);
+            assert_eq!(lenient(data).read_f64()?, 1.5);
+        }
+        assert_eq!(lenient(&[0x22]).read_f32()?, -3.0);
+        assert!(lenient(&[0xf9, 0x7e, 0x00]).read_f64()?.is_nan());
+        // 0.1 is not exact in f32, 2^53 + 1 is not exact in f64
+        assert!(overflows(lenient(&[0xfb, 0x3f, 0xb9, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a]).read_f32()));
+        assert!(overflows(lenient(&[0x1b, 0, 0x20, 0, 0, 0, 0, 0, 1]).read_f64()));
+        // 65504 is the largest f16; 65536 is not representable
+        assert_eq!(lenient(&[0x19, 0xff, 0xe0]).read_f16()?, 65504.0);
+        assert!(overflows(lenient(&[0x1a, 0, 1, 0, 0]).read_f16()));
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_indefinite_strings() -> Result<(), DecodeError> {
+        let text = [0x7f, 0x62, b'h', b'e', 0x60, 0x63, b'l', b'l', b'o', 0xff, 0x01];
+        assert!(Reader::new(&text).read_string().is_err());
+        let mut r = Reader::with_mode(&text, ReaderMode::Lenient);
+        assert_eq!(r.read_string()?, "hello");
+        assert_eq!(r.read_uvarint()?, 1);
+
+        let bytes = [0x5f, 0x41, 1, 0x42, 2, 3, 0xff];
+        let mut out = vec![9, 9, 9, 9];
+        Reader::with_mode(&bytes, ReaderMode::Lenient).read_bytes_into(&mut out)?;
+        assert_eq!(out, [1, 2, 3]);
+
+        // Chunks must be definite-length and of the same major type
+        let nested = [0x7f, 0x7f, 0xff, 0xff];
+        assert!(Reader::with_mode(&nested, ReaderMode::Lenient).read_string().is_err());
+        let mixed = [0x7f, 0x41, b'a', 0xff];
+        assert!(Reader::with_mode(&mixed, ReaderMode::Lenient).read_string().is_err());
+        let unterminated = [0x5f, 0x41, 1];
+        assert!(Reader::with_mode(&unterminated, ReaderMode::Lenient).read_bytes().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn strict_requires_minimal_lengths() -> Result<(), DecodeError> {
+        let strict = |data: &'static [u8]| Reader::with_mode(data, ReaderMode::Strict);
+        assert_eq!(Reader::new(&[0x18, 0x05]).read_uvarint()?, 5);
+        assert!(strict(&[0x18, 0x05]).read_uvarint().is_err());
+        assert!(strict(&[0x39, 0x00, 0x10]).read_ivarint().is_err());
+        assert_eq!(strict(&[0x18, 0x18]).read_uvarint()?, 24);
+        assert_eq!(strict(&[0x1b, 0, 0, 0, 1, 0, 0, 0, 0]).read_uvarint()?, 1 << 32);
+        assert!(strict(&[0x78, 0x01, b'a']).read_string().is_err());
+        assert!(strict(&[0x98, 0x00]).read_array_header().is_err());
+        // Fixed-width integers keep their full width
+        assert_eq!(strict(&[0x1a, 0, 0, 0, 5]).read_u32()?, 5);
+        let mut r = strict(&[0x82, 0x1a, 0, 0, 0, 5, 0xf6]);
+        r.skip()?;
+        r.finish()?;
+        assert!(strict(&[0x81, 0x79, 0x00, 0x01, b'a']).skip().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn strict_gaps_and_trailing_bytes() -> Result<(), DecodeError> {
+        let gap = [0x80];
+        Reader::new(&gap).skip_gap()?;
+        assert!(Reader::with_mode(&gap, ReaderMode::Strict).skip_gap().is_err());
+        Reader::with_mode(&[0xf6], ReaderMode::Strict).skip_gap()?;
+
+        let mut r = Reader::with_mode(&[0x01, 0x02], ReaderMode::Strict);
+        r.read_uvarint()?;
+        assert!(matches!(r.finish(), Err(DecodeError::TrailingBytes(1))));
+        r.read_uvarint()?;
+        r.finish()
+    }
+
+    #[test]
+    fn option_tags_and_short_structs() -> Result<(), DecodeError> {
+        assert!(!Reader::new(&[0x00]).read_option_tag()?);
+        assert!(Reader::new(&[0xc1]).read_option_tag()?);
+        assert!(Reader::new(&[0xc2]).read_option_tag().is_err());
+        assert!(Reader::new(&[0x01]).read_option_tag().is_err());
+        assert_eq!(Option::<u8>::decode(&[0xc1, 0x18, 0x07])?, Some(7));
+        assert!(Option::<u8>::decode(&[0xc5, 0x18, 0x07]).is_err());
+
+        assert_eq!(Reader::new(&[0x81]).read_struct_header(2)?, 1);
+        assert!(Reader::with_mode(&[0x81], ReaderMode::Strict).read_struct_header(2).is_err());
+        assert_eq!(Reader::with_mode(&[0x82], ReaderMode::Strict).read_struct_header(2)?, 2);
+        assert!(Reader::with_mode(&[0x83], ReaderMode::Strict).read_struct_header(2).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn skip_validates_break_codes() -> Result<(), DecodeError> {
+        assert!(Reader::new(&[0xff]).skip().is_err());
+        assert!(Reader::new(&[0x9f, 0x01]).skip().is_err());
+        assert!(Reader::new(&[0x3f, 0xff]).skip().is_err());
+        assert!(Reader::new(&[0x7f, 0x41, b'a', 0xff]).skip().is_err());
+        assert!(Reader::new(&[0xbf, 0x01, 0xff]).skip().is_err());
+        let mut r = Reader::new(&[0x9f, 0x01, 0x7f, 0x61, b'a', 0xff, 0xff, 0xbf, 0x01, 0x02, 0xff]);
+        r.skip()?;
+        r.skip()?;
+        r.finish()
+    }
 }
@@ -0,0 +1,1135 @@
+//! serde support (`serde` feature).
+//!
+//! Two halves:
+//!
+//! - Code generated with `cboragen-rs --serde` derives `Serialize` and
+//!   `Deserialize` using the schema's field and variant names, and routes
+//!   `[]u8` fields through [`bytes`] and [`option_bytes`] so they come out as
+//!   base64 strings in human-readable formats (matching the `json` feature)
+//!   and as native byte strings everywhere else.
+//! - [`to_vec`] and [`from_slice`] are a serde data format for the cboragen
+//!   wire format itself, so hand-written serde types can talk to cboragen
+//!   peers without a `.cbg` file.
+//!
+//! The data format maps Rust types onto the wire rules in SPEC.md:
+//!
+//! | Rust                          | Wire                                         |
+//! |-------------------------------|----------------------------------------------|
+//! | `bool`, `u8`…`u64`, `i8`…`i64` | full-width integer for the Rust type         |
+//! | `f32`, `f64`                  | `f32` / `f64`                                |
+//! | `String`, `&str`, `char`      | `string`                                     |
+//! | [`bytes`] fields, `serde_bytes` | byte string (`[]u8`)                       |
+//! | `Option<T>`                   | `0x00`, or tag 1 wrapping `T`                |
+//! | struct, tuple, `[T; N]`       | definite-length array, fields in declaration order |
+//! | `Vec<T>`                      | definite-length array                        |
+//! | unit enum variant             | varint of the variant index                  |
+//! | enum variant with data        | tag = variant index wrapping the payload     |
+//!
+//! Struct fields take ranks `0, 1, 2…` in declaration order and enum variants
+//! take tags `0, 1, 2…` in declaration order, so declare them to match the
+//! schema. A field skipped with `skip_serializing_if` is written as a null
+//! gap, and gaps and missing trailing fields read back as absent (give such
+//! fields `#[serde(default)]` unless they are `Option`s). Use [`uvarint`],
+//! [`ivarint`] and [`f16`] for fields with those schema types. Maps have no
+//! cboragen encoding and are rejected.
+
+use std::fmt;
+
+use ::serde::de::{self, DeserializeSeed, IntoDeserializer, SeqAccess, Visitor};
+use ::serde::ser::{self, Serialize};
+use ::serde::Deserialize;
+
+use crate::base64::{base64_decode, base64_encode};
+use crate::{DecodeError, EncodeError, Reader, Writer};
+
+/// Encode a value in the cboragen wire format.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, EncodeError> {
+    let mut w = Writer::new();
+    to_writer(value, &mut w)?;
+    Ok(w.finish())
+}
+
+/// Encode a value onto an existing [`Writer`].
+pub fn to_writer<T: Serialize + ?Sized>(value: &T, w: &mut Writer) -> Result<(), EncodeError> {
+    value.serialize(&mut Serializer::new(w))
+}
+
+/// Decode a value from cboragen wire bytes.
+pub fn from_slice<'de, T: Deserialize<'de>>(data: &'de [u8]) -> Result<T, DecodeError> {
+    let mut r = Reader::new(data);
+    from_reader(&mut r)
+}
+
+/// Decode a value from an existing [`Reader`], leaving it after the value.
+pub fn from_reader<'de, T: Deserialize<'de>>(r: &mut Reader<'de>) -> Result<T, DecodeError> {
+    T::deserialize(&mut Deserializer::new(r))
+}
+
+impl ser::Error for EncodeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        EncodeError::InvalidValue(msg.to_string())
+    }
+}
+
+impl de::Error for DecodeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        DecodeError::InvalidData(msg.to_string())
+    }
+}
+
+// Newtype names the `uvarint`/`ivarint`/`f16` helpers use to ask this
+// format for a non-default encoding. Other formats see a plain newtype.
+const UVARINT: &str = "$cboragen::uvarint";
+const IVARINT: &str = "$cboragen::ivarint";
+const F16: &str = "$cboragen::f16";
+
+// === Serializer ===
+
+/// Encoding requested by the enclosing marker newtype, consumed by the next
+/// integer or float.
+#[derive(Clone, Copy, Default, PartialEq)]
+enum Width {
+    #[default]
+    Fixed,
+    Varint,
+    Half,
+}
+
+/// serde `Serializer` writing the cboragen wire format to a [`Writer`].
+pub struct Serializer<'w> {
+    w: &'w mut Writer,
+    width: Width,
+}
+
+impl<'w> Serializer<'w> {
+    pub fn new(w: &'w mut Writer) -> Self {
+        Serializer { w, width: Width::Fixed }
+    }
+
+    fn array(&mut self, len: Option<usize>) -> Compound<'_, 'w> {
+        let start = self.w.buf.len();
+        self.w.write_array_header(len.unwrap_or(0));
+        let body = self.w.buf.len();
+        Compound { ser: self, start, body, declared: len, count: 0 }
+    }
+}
+
+impl<'a, 'w> ser::Serializer for &'a mut Serializer<'w> {
+    type Ok = ();
+    type Error = EncodeError;
+    type SerializeSeq = Compound<'a, 'w>;
+    type SerializeTuple = Compound<'a, 'w>;
+    type SerializeTupleStruct = Compound<'a, 'w>;
+    type SerializeTupleVariant = Compound<'a, 'w>;
+    type SerializeMap = ser::Impossible<(), EncodeError>;
+    type SerializeStruct = Compound<'a, 'w>;
+    type SerializeStructVariant = Compound<'a, 'w>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<(), EncodeError> {
+        self.w.write_bool(v);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), EncodeError> {
+        self.w.write_i8(v);
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), EncodeError> {
+        self.w.write_i16(v);
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), EncodeError> {
+        self.w.write_i32(v);
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), EncodeError> {
+        match std::mem::take(&mut self.width) {
+            Width::Varint => self.w.write_ivarint(v),
+            _ => self.w.write_i64(v),
+        }
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), EncodeError> {
+        self.w.write_u8(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), EncodeError> {
+        self.w.write_u16(v);
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), EncodeError> {
+        self.w.write_u32(v);
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), EncodeError> {
+        match std::mem::take(&mut self.width) {
+            Width::Varint => self.w.write_uvarint(v),
+            _ => self.w.write_u64(v),
+        }
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), EncodeError> {
+        match std::mem::take(&mut self.width) {
+            Width::Half => self.w.write_f16(v),
+            _ => self.w.write_f32(v),
+        }
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), EncodeError> {
+        self.w.write_f64(v);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), EncodeError> {
+        self.w.write_string(v.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), EncodeError> {
+        self.w.write_string(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), EncodeError> {
+        self.w.write_bytes(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), EncodeError> {
+        self.w.write_byte(0x00);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), EncodeError> {
+        self.w.write_tag_header(1);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), EncodeError> {
+        self.w.write_null();
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), EncodeError> {
+        self.w.write_array_header(0);
+        Ok(())
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, index: u32, _variant: &'static str) -> Result<(), EncodeError> {
+        self.w.write_uvarint(index as u64);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<(), EncodeError> {
+        self.width = match name {
+            UVARINT | IVARINT => Width::Varint,
+            F16 => Width::Half,
+            _ => Width::Fixed,
+        };
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), EncodeError> {
+        self.w.write_tag_header(index as u64);
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a, 'w>, EncodeError> {
+        Ok(self.array(len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, 'w>, EncodeError> {
+        Ok(self.array(Some(len)))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, 'w>, EncodeError> {
+        Ok(self.array(Some(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, 'w>, EncodeError> {
+        self.w.write_tag_header(index as u64);
+        Ok(self.array(Some(len)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, EncodeError> {
+        Err(EncodeError::InvalidValue("maps have no cboragen wire encoding".into()))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, 'w>, EncodeError> {
+        Ok(self.array(Some(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, 'w>, EncodeError> {
+        self.w.write_tag_header(index as u64);
+        Ok(self.array(Some(len)))
+    }
+}
+
+/// An array in progress. The header is written up front with the declared
+/// length and rewritten in [`Compound::finish`] if the element count differs
+/// (unknown-length sequences, fields skipped as null gaps).
+pub struct Compound<'a, 'w> {
+    ser: &'a mut Serializer<'w>,
+    start: usize,
+    body: usize,
+    declared: Option<usize>,
+    count: usize,
+}
+
+impl Compound<'_, '_> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.count += 1;
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) -> Result<(), EncodeError> {
+        if self.declared != Some(self.count) {
+            let mut header = Writer { buf: Vec::with_capacity(9), deterministic: false };
+            header.write_array_header(self.count);
+            self.ser.w.buf.splice(self.start..self.body, header.buf);
+        }
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Compound<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for Compound<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), EncodeError> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for Compound<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), EncodeError> {
+        self.element(value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), EncodeError> {
+        self.count += 1;
+        self.ser.w.write_null();
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for Compound<'_, '_> {
+    type Ok = ();
+    type Error = EncodeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), EncodeError> {
+        self.element(value)
+    }
+
+    fn skip_field(&mut self, _key: &'static str) -> Result<(), EncodeError> {
+        self.count += 1;
+        self.ser.w.write_null();
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), EncodeError> {
+        self.finish()
+    }
+}
+
+// === Deserializer ===
+
+/// serde `Deserializer` reading the cboragen wire format from a [`Reader`].
+pub struct Deserializer<'r, 'de> {
+    r: &'r mut Reader<'de>,
+}
+
+impl<'r, 'de> Deserializer<'r, 'de> {
+    pub fn new(r: &'r mut Reader<'de>) -> Self {
+        Deserializer { r }
+    }
+
+    /// Read an array header; `None` for an indefinite-length array (`0x9f`).
+    fn read_array_start(&mut self) -> Result<Option<usize>, DecodeError> {
+        if self.r.peek_byte()? == 0x9f {
+            self.r.read_byte()?;
+            return Ok(None);
+        }
+        Ok(Some(self.r.read_array_header()?))
+    }
+
+    /// Run `f` one level deeper, so nested input is bounded by the
+    /// reader's depth limit rather than the call stack.
+    fn nested<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, DecodeError>) -> Result<T, DecodeError> {
+        self.r.enter()?;
+        let v = f(self);
+        self.r.leave();
+        v
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut Deserializer<'_, 'de> {
+    type Error = DecodeError;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    /// The wire format only partly describes itself (a `u8` and a `u64`
+    /// differ only in width), so this is for self-describing consumers such
+    /// as `serde_json::Value`: integers widen to 64 bits and tags are rejected.
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let b = self.r.peek_byte()?;
+        match b >> 5 {
+            0 => visitor.visit_u64(self.r.read_uvarint()?),
+            1 => visitor.visit_i64(self.r.read_ivarint()?),
+            2 => self.deserialize_bytes(visitor),
+            3 => self.deserialize_str(visitor),
+            4 => self.deserialize_seq(visitor),
+            7 => match b {
+                0xf4 | 0xf5 => visitor.visit_bool(self.r.read_bool()?),
+                0xf6 => self.deserialize_unit(visitor),
+                0xf9 => visitor.visit_f32(self.r.read_f16()?),
+                0xfa => visitor.visit_f32(self.r.read_f32()?),
+                0xfb => visitor.visit_f64(self.r.read_f64()?),
+                _ => Err(DecodeError::InvalidData(format!("unsupported simple value 0x{b:02x}"))),
+            },
+            maj => Err(DecodeError::InvalidData(format!("cannot decode major type {maj} without a type"))),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_bool(self.r.read_bool()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_i8(self.r.read_i8()?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_i16(self.r.read_i16()?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_i32(self.r.read_i32()?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_i64(self.r.read_i64()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_u8(self.r.read_u8()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_u16(self.r.read_u16()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_u32(self.r.read_u32()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_u64(self.r.read_u64()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_f32(self.r.read_f32()?)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_f64(self.r.read_f64()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let s = self.r.read_str()?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(DecodeError::InvalidData(format!("expected a single character, got {s:?}"))),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_borrowed_str(self.r.read_str()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        visitor.visit_borrowed_bytes(self.r.read_slice(2)?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        // A null gap where an optional field is expected reads as absent.
+        if self.r.try_read_null()? {
+            return visitor.visit_none();
+        }
+        if self.r.read_option_tag()? {
+            self.nested(|de| visitor.visit_some(de))
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        match self.r.read_byte()? {
+            0xf6 => visitor.visit_unit(),
+            b => Err(DecodeError::InvalidData(format!("expected null (0xf6), got 0x{b:02x}"))),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, DecodeError> {
+        let len = self.r.read_array_header()?;
+        for _ in 0..len { self.r.skip()?; }
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, name: &'static str, visitor: V) -> Result<V::Value, DecodeError> {
+        match name {
+            UVARINT => visitor.visit_u64(self.r.read_uvarint()?),
+            IVARINT => visitor.visit_i64(self.r.read_ivarint()?),
+            F16 => visitor.visit_f32(self.r.read_f16()?),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let len = self.read_array_start()?;
+        self.nested(|de| {
+            let mut seq = Elements { de, left: len };
+            let value = visitor.visit_seq(&mut seq)?;
+            seq.finish()?;
+            Ok(value)
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, DecodeError> {
+        let n = self.r.read_array_header()?;
+        if n != len {
+            return Err(DecodeError::InvalidData(format!("expected array of {len}, got {n}")));
+        }
+        self.nested(|de| {
+            let mut seq = Elements { de, left: Some(n) };
+            let value = visitor.visit_seq(&mut seq)?;
+            seq.finish()?;
+            Ok(value)
+        })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, DecodeError> {
+        Err(DecodeError::InvalidData("maps have no cboragen wire encoding".into()))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        let len = self.r.read_struct_header(fields.len())?;
+        self.nested(|de| visitor.visit_map(Fields { de, fields, rank: 0, len }))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        let b = self.r.peek_byte()?;
+        match b >> 5 {
+            0 => {
+                let index = self.r.read_uvarint()?;
+                visitor.visit_enum(Variant { de: self, index, payload: false })
+            }
+            6 => {
+                let index = self.r.read_tag_header()?;
+                self.nested(|de| visitor.visit_enum(Variant { de, index, payload: true }))
+            }
+            maj => Err(DecodeError::InvalidData(format!("expected enum or union, got major type {maj}"))),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        self.r.skip()?;
+        visitor.visit_unit()
+    }
+}
+
+/// Array elements; `left` is `None` for an indefinite-length array.
+struct Elements<'a, 'r, 'de> {
+    de: &'a mut Deserializer<'r, 'de>,
+    left: Option<usize>,
+}
+
+impl Elements<'_, '_, '_> {
+    /// Skip elements the visitor didn't consume and any break code.
+    fn finish(self) -> Result<(), DecodeError> {
+        match self.left {
+            Some(n) => {
+                for _ in 0..n { self.de.r.skip()?; }
+            }
+            None => {
+                while self.de.r.peek_byte()? != 0xff { self.de.r.skip()?; }
+                self.de.r.read_byte()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de> SeqAccess<'de> for &mut Elements<'_, '_, 'de> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, DecodeError> {
+        match &mut self.left {
+            Some(0) => return Ok(None),
+            Some(n) => *n -= 1,
+            None => {
+                if self.de.r.peek_byte()? == 0xff {
+                    self.de.r.read_byte()?;
+                    self.left = Some(0);
+                    return Ok(None);
+                }
+            }
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        self.left
+    }
+}
+
+/// Struct fields by rank, presented as a map keyed by field name so that
+/// null gaps and missing trailing fields are simply absent.
+struct Fields<'a, 'r, 'de> {
+    de: &'a mut Deserializer<'r, 'de>,
+    fields: &'static [&'static str],
+    rank: usize,
+    len: usize,
+}
+
+impl<'de> de::MapAccess<'de> for Fields<'_, '_, 'de> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, DecodeError> {
+        while self.rank < self.len {
+            let rank = self.rank;
+            self.rank += 1;
+            let Some(&name) = self.fields.get(rank) else {
+                // Fields beyond what this type knows about are skipped.
+                self.de.r.skip()?;
+                continue;
+            };
+            if self.de.r.peek_byte()? == 0xf6 {
+                self.de.r.read_byte()?;
+                continue;
+            }
+            return seed.deserialize(name.into_deserializer()).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, DecodeError> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+struct Variant<'a, 'r, 'de> {
+    de: &'a mut Deserializer<'r, 'de>,
+    index: u64,
+    payload: bool,
+}
+
+impl<'a, 'r, 'de> de::EnumAccess<'de> for Variant<'a, 'r, 'de> {
+    type Error = DecodeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), DecodeError> {
+        let index = u32::try_from(self.index)
+            .map_err(|_| DecodeError::InvalidData(format!("variant tag {} out of range", self.index)))?;
+        let value = seed.deserialize(index.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Variant<'_, '_, 'de> {
+    type Error = DecodeError;
+
+    fn unit_variant(self) -> Result<(), DecodeError> {
+        if self.payload {
+            return Err(DecodeError::InvalidData(format!("variant {} has no payload, got a tag", self.index)));
+        }
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, DecodeError> {
+        self.expect_payload()?;
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, DecodeError> {
+        self.expect_payload()?;
+        de::Deserializer::deserialize_tuple(&mut *self.de, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        self.expect_payload()?;
+        de::Deserializer::deserialize_struct(&mut *self.de, "", fields, visitor)
+    }
+}
+
+impl Variant<'_, '_, '_> {
+    fn expect_payload(&self) -> Result<(), DecodeError> {
+        if !self.payload {
+            return Err(DecodeError::InvalidData(format!("variant {} expects a payload", self.index)));
+        }
+        Ok(())
+    }
+}
+
+// === Field helpers ===
+
+/// `#[serde(with = "cboragen_runtime::serde::uvarint")]` for `u64` fields
+/// of schema type `uvarint`.
+pub mod uvarint {
+    use super::*;
+
+    pub fn serialize<S: ser::Serializer>(v: &u64, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_newtype_struct(UVARINT, v)
+    }
+
+    pub fn deserialize<'de, D: de::Deserializer<'de>>(d: D) -> Result<u64, D::Error> {
+        d.deserialize_newtype_struct(UVARINT, Marked::<u64>::new())
+    }
+}
+
+/// `#[serde(with = "cboragen_runtime::serde::ivarint")]` for `i64` fields
+/// of schema type `ivarint`.
+pub mod ivarint {
+    use super::*;
+
+    pub fn serialize<S: ser::Serializer>(v: &i64, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_newtype_struct(IVARINT, v)
+    }
+
+    pub fn deserialize<'de, D: de::Deserializer<'de>>(d: D) -> Result<i64, D::Error> {
+        d.deserialize_newtype_struct(IVARINT, Marked::<i64>::new())
+    }
+}
+
+/// `#[serde(with = "cboragen_runtime::serde::f16")]` for `f32` fields of
+/// schema type `f16`.
+pub mod f16 {
+    use super::*;
+
+    pub fn serialize<S: ser::Serializer>(v: &f32, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_newtype_struct(F16, v)
+    }
+
+    pub fn deserialize<'de, D: de::Deserializer<'de>>(d: D) -> Result<f32, D::Error> {
+        d.deserialize_newtype_struct(F16, Marked::<f32>::new())
+    }
+}
+
+/// Visitor for a marker newtype: this format hands over the number
+/// directly, other formats hand over the inner value as a newtype.
+struct Marked<T>(std::marker::PhantomData<T>);
+
+impl<T> Marked<T> {
+    fn new() -> Self {
+        Marked(std::marker::PhantomData)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for Marked<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a number")
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<T, D::Error> {
+        T::deserialize(d)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        T::deserialize(v.into_deserializer())
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        T::deserialize(v.into_deserializer())
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<T, E> {
+        T::deserialize(v.into_deserializer())
+    }
+}
+
+/// `#[serde(with = "cboragen_runtime::serde::bytes")]` for `Vec<u8>` fields.
+pub mod bytes {
+    use super::*;
+
+    pub fn serialize<S: ser::Serializer>(v: &[u8], s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            s.serialize_str(&base64_encode(v))
+        } else {
+            s.serialize_bytes(v)
+        }
+    }
+
+    pub fn deserialize<'de, D: de::Deserializer<'de>>(d: D) -> Result<Vec<u8>, D::Error> {
+        if d.is_human_readable() {
+            d.deserialize_any(BytesVisitor)
+        } else {
+            d.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+}
+
+/// `#[serde(with = "cboragen_runtime::serde::option_bytes")]` for
+/// `Option<Vec<u8>>` fields.
+pub mod option_bytes {
+    use super::*;
+
+    pub fn serialize<S: ser::Serializer>(v: &Option<Vec<u8>>, s: S) -> Result<S::Ok, S::Error> {
+        match v {
+            Some(b) => s.serialize_some(&Bytes(b)),
+            None => s.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: de::Deserializer<'de>>(d: D) -> Result<Option<Vec<u8>>, D::Error> {
+        Ok(Option::<ByteBuf>::deserialize(d)?.map(|b| b.0))
+    }
+}
+
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        bytes::serialize(self.0, s)
+    }
+}
+
+struct ByteBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for ByteBuf {
+    fn deserialize<D: de::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        bytes::deserialize(d).map(ByteBuf)
+    }
+}
+
+/// Accepts byte strings, base64 text, or a sequence of `u8`.
+struct BytesVisitor;
+
+impl<'de> Visitor<'de> for BytesVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a byte string, base64 text, or a sequence of bytes")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Vec<u8>, E> {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Vec<u8>, E> {
+        Ok(v)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Vec<u8>, E> {
+        base64_decode(v).ok_or_else(|| E::custom(format!("invalid base64 {v:?}")))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<u8>, A::Error> {
+        let mut out = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(b) = seq.next_element::<u8>()? {
+            out.push(b);
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::serde::{Deserialize, Serialize};
+
+    use super::{from_slice, to_vec};
+    use crate::{DecodeError, Writer};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Status {
+        Active,
+        Inactive,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rect { w: u16, h: u16 },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message<'a> {
+        id: u32,
+        name: &'a str,
+        #[serde(with = "crate::serde::uvarint")]
+        seq: u64,
+        #[serde(with = "crate::serde::ivarint")]
+        delta: i64,
+        #[serde(with = "crate::serde::f16")]
+        scale: f32,
+        #[serde(with = "crate::serde::bytes")]
+        payload: Vec<u8>,
+        status: Status,
+        shapes: Vec<Shape>,
+        parent: Option<Option<i8>>,
+        point: [u8; 2],
+    }
+
+    /// The bytes generated code writes for the equivalent schema struct.
+    fn expected_message() -> Vec<u8> {
+        let mut w = Writer::new();
+        w.write_array_header(10);
+        w.write_u32(7);
+        w.write_string("hello");
+        w.write_uvarint(300);
+        w.write_ivarint(-2);
+        w.write_f16(1.5);
+        w.write_bytes(&[1, 2, 3]);
+        w.write_uvarint(1);
+        w.write_array_header(3);
+        w.write_uvarint(0);
+        w.write_tag_header(1);
+        w.write_f64(2.5);
+        w.write_tag_header(2);
+        w.write_array_header(2);
+        w.write_u16(3);
+        w.write_u16(4);
+        w.write_tag_header(1);
+        w.write_byte(0x00);
+        w.write_array_header(2);
+        w.write_u8(9);
+        w.write_u8(10);
+        w.finish()
+    }
+
+    fn message() -> Message<'static> {
+        Message {
+            id: 7,
+            name: "hello",
+            seq: 300,
+            delta: -2,
+            scale: 1.5,
+            payload: vec![1, 2, 3],
+            status: Status::Inactive,
+            shapes: vec![Shape::Empty, Shape::Circle(2.5), Shape::Rect { w: 3, h: 4 }],
+            parent: Some(None),
+            point: [9, 10],
+        }
+    }
+
+    #[test]
+    fn serialize_matches_generated_bytes() -> Result<(), crate::EncodeError> {
+        assert_eq!(to_vec(&message())?, expected_message());
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_generated_bytes() -> Result<(), DecodeError> {
+        let bytes = expected_message();
+        assert_eq!(from_slice::<Message>(&bytes)?, message());
+        Ok(())
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Versioned {
+        a: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        b: Option<u8>,
+        #[serde(default)]
+        c: bool,
+    }
+
+    #[test]
+    fn gaps_and_compatibility() -> Result<(), DecodeError> {
+        let v = Versioned { a: 1, b: None, c: true };
+        let bytes = to_vec(&v).map_err(|e| DecodeError::InvalidData(e.to_string()))?;
+        assert_eq!(bytes, [0x83, 0x18, 0x01, 0xf6, 0xf5]);
+        assert_eq!(from_slice::<Versioned>(&bytes)?, v);
+
+        // Older peer: missing trailing fields take their defaults
+        assert_eq!(from_slice::<Versioned>(&[0x81, 0x18, 0x05])?, Versioned { a: 5, b: None, c: false });
+        // Newer peer: unknown trailing fields are skipped
+        let newer = [0x84, 0x18, 0x01, 0xc1, 0x18, 0x02, 0xf4, 0x63, b'x', b'y', b'z'];
+        assert_eq!(from_slice::<Versioned>(&newer)?, Versioned { a: 1, b: Some(2), c: false });
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_mismatched_input() {
+        // u8 field encoded as a 1-byte varint
+        assert!(from_slice::<Versioned>(&[0x81, 0x01]).is_err());
+        // Unit variant given a payload
+        assert!(from_slice::<Shape>(&[0xc0, 0x00]).is_err());
+        // Fixed array of the wrong length
+        assert!(from_slice::<[u8; 2]>(&[0x81, 0x18, 0x01]).is_err());
+        assert!(to_vec(&std::collections::BTreeMap::from([(1u8, 2u8)])).is_err());
+    }
+
+    #[test]
+    fn external_length_arrays() -> Result<(), DecodeError> {
+        let bytes = [0x9f, 0x18, 0x01, 0x18, 0x02, 0xff];
+        assert_eq!(from_slice::<Vec<u8>>(&bytes)?, vec![1, 2]);
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    enum Tree {
+        Leaf,
+        Node(Vec<Tree>),
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error() {
+        let data = [0xc1, 0x81].repeat(200_000);
+        assert!(matches!(from_slice::<Tree>(&data), Err(DecodeError::DepthExceeded(_))));
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Attachment {
+        #[serde(rename = "mimeType")]
+        mime_type: String,
+        #[serde(with = "crate::serde::bytes")]
+        data: Vec<u8>,
+        #[serde(with = "crate::serde::option_bytes")]
+        thumbnail: Option<Vec<u8>>,
+    }
+
+    #[test]
+    fn bytes_are_base64_in_json() -> Result<(), serde_json::Error> {
+        let a = Attachment {
+            mime_type: "text/plain".into(),
+            data: b"foobar".to_vec(),
+            thumbnail: Some(b"fo".to_vec()),
+        };
+        let json = serde_json::to_string(&a)?;
+        assert_eq!(json, r#"{"mimeType":"text/plain","data":"Zm9vYmFy","thumbnail":"Zm8="}"#);
+        assert_eq!(serde_json::from_str::<Attachment>(&json)?, a);
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_accept_sequences() -> Result<(), serde_json::Error> {
+        let a: Attachment = serde_json::from_str(r#"{"mimeType":"","data":[1,2,3],"thumbnail":null}"#)?;
+        assert_eq!(a.data, vec![1, 2, 3]);
+        assert_eq!(a.thumbnail, None);
+        assert!(serde_json::from_str::<Attachment>(r#"{"mimeType":"","data":"Zm8","thumbnail":null}"#).is_err());
+        Ok(())
+    }
+}
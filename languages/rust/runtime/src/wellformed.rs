@@ -0,0 +1,159 @@
+//! Well-formedness checking (RFC 8949, Appendix C) without recursion.
+//!
+//! Nesting is tracked on a heap stack, so arbitrarily deep input costs
+//! memory in proportion to its size instead of overflowing the call stack.
+//! The scan reads the same heads as `Reader::next_event`, and `Reader::skip`
+//! is built on it. Well-formed only means the item can be parsed; whether
+//! strings are valid UTF-8 or tags fit their content is not checked.
+
+use crate::events::{next_head, Event};
+use crate::{DecodeError, Reader};
+
+/// What validating an item found out about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemInfo {
+    /// Major type of the item's head.
+    pub major: u8,
+    /// Length of the encoded item in bytes.
+    pub len: usize,
+    /// Deepest nesting of arrays, maps and tags; 0 for scalars and strings.
+    pub depth: usize,
+}
+
+/// Check that `data` is exactly one well-formed CBOR item.
+pub fn validate_well_formed(data: &[u8]) -> Result<ItemInfo, DecodeError> {
+    let mut r = Reader::new(data);
+    let info = scan(&mut r)?;
+    r.finish()?;
+    Ok(info)
+}
+
+/// Read past the next item, checking that it is well-formed. Strict readers
+/// also require minimal lengths and tag numbers.
+pub(crate) fn scan(r: &mut Reader) -> Result<ItemInfo, DecodeError> {
+    let start = r.pos;
+    let mut open = Vec::new();
+    let mut depth = 0;
+    loop {
+        let event = next_head(r, &mut open, false)?;
+        if matches!(event, Event::ArrayStart(_) | Event::MapStart(_) | Event::Tag(_)) {
+            depth = depth.max(open.len());
+        }
+        if open.is_empty() {
+            return Ok(ItemInfo { major: r.data[start] >> 5, len: r.pos - start, depth });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReaderMode;
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        let hex: String = hex.split_whitespace().collect();
+        (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect()
+    }
+
+    // RFC 8949, Appendix A
+    #[test]
+    fn appendix_a_is_well_formed() {
+        let examples = [
+            "00", "17", "1818", "1903e8", "1a000f4240", "1b000000e8d4a51000", "1bffffffffffffffff",
+            "c249010000000000000000", "3bffffffffffffffff", "20", "3863", "3903e7",
+            "f90000", "f98000", "f93c00", "fb3ff199999999999a", "f97bff", "fa47c35000",
+            "fa7f7fffff", "fb7e37e43c8800759c", "f90001", "f9c400", "f97c00", "f97e00",
+            "fa7f800000", "fbfff0000000000000", "f4", "f5", "f6", "f7", "f0", "f8ff",
+            "c074323031332d30332d32315432303a30343a30305a", "c11a514b67b0", "c1fb41d452d9ec200000",
+            "d74401020304", "d818456449455446",
+            "d82076687474703a2f2f7777772e6578616d706c652e636f6d",
+            "40", "4401020304", "60", "6161", "6449455446", "62225c", "62c3bc", "63e6b0b4", "64f0908591",
+            "80", "83010203", "8301820203820405",
+            "98190102030405060708090a0b0c0d0e0f101112131415161718181819",
+            "a0", "a201020304", "a26161016162820203", "826161a161626163",
+            "a56161614161626142616361436164614461656145",
+            "5f42010243030405ff", "7f657374726561646d696e67ff", "9fff", "9f018202039f0405ffff",
+            "9f01820203820405ff", "83018202039f0405ff", "83019f0203ff820405",
+            "9f0102030405060708090a0b0c0d0e0f101112131415161718181819ff",
+            "bf61610161629f0203ffff", "826161bf61626163ff", "bf6346756ef563416d7421ff",
+        ];
+        for hex in examples {
+            let data = bytes(hex);
+            let info = validate_well_formed(&data).unwrap_or_else(|e| panic!("{hex}: {e}"));
+            assert_eq!(info.len, data.len());
+        }
+    }
+
+    // RFC 8949, Appendix F.1
+    #[test]
+    fn appendix_f_is_malformed() {
+        let examples = [
+            // End of input in a head
+            "18", "19", "1a", "1b", "1901", "1a0102", "1b01020304050607", "38", "58", "78", "98",
+            "9a01ff00", "b8", "d8", "f8", "f900", "fa0000", "fb000000",
+            // Definite-length strings with short data
+            "41", "61", "5affffffff00", "5bffffffffffffffff010203", "7affffffff00", "7b7fffffffffffffff010203",
+            // Definite-length maps and arrays not closed with enough items
+            "81", "818181818181818181", "8200", "a1", "a20102", "a100", "a2000000",
+            // Tag number not followed by tag content
+            "c0",
+            // Indefinite-length strings not closed by a break
+            "5f4100", "7f6100",
+            // Indefinite-length maps and arrays not closed by a break
+            "9f", "9f0102", "bf", "bf01020102", "819f", "9f8000", "9f9f9f9f9fffffffff", "9f819f819f9fffffff",
+            // Reserved additional information values
+            "1c", "1d", "1e", "3c", "3d", "3e", "5c", "5d", "5e", "7c", "7d", "7e",
+            "9c", "9d", "9e", "bc", "bd", "be", "dc", "dd", "de", "fc", "fd", "fe",
+            // Reserved two-byte encodings of simple values
+            "f800", "f801", "f818", "f81f",
+            // Indefinite-length string chunks not of the correct type
+            "5f00ff", "5f21ff", "5f6100ff", "5f80ff", "5fa0ff", "5fc000ff", "5fe0ff", "7f4100ff",
+            // Indefinite-length string chunks not definite length
+            "5f5f4100ffff", "7f7f6100ffff",
+            // Break outside an indefinite-length item
+            "ff",
+            // Break in a definite-length array or map or a tag
+            "81ff", "8200ff", "a1ff", "a1ff00", "a100ff", "a20000ff", "9f81ff", "9f829f819f9fffffffff",
+            // Break in a map value position
+            "bf00ff", "bf000000ff",
+            // Major type 0, 1 or 6 with additional information 31
+            "1f", "3f", "df",
+        ];
+        for hex in examples {
+            assert!(validate_well_formed(&bytes(hex)).is_err(), "{hex} should be malformed");
+        }
+    }
+
+    #[test]
+    fn item_info() -> Result<(), DecodeError> {
+        assert_eq!(validate_well_formed(&bytes("00"))?, ItemInfo { major: 0, len: 1, depth: 0 });
+        assert_eq!(validate_well_formed(&bytes("5f42010243030405ff"))?, ItemInfo { major: 2, len: 9, depth: 0 });
+        assert_eq!(validate_well_formed(&bytes("83018202039f0405ff"))?, ItemInfo { major: 4, len: 9, depth: 2 });
+        assert_eq!(validate_well_formed(&bytes("d818456449455446"))?, ItemInfo { major: 6, len: 8, depth: 1 });
+        assert!(matches!(validate_well_formed(&bytes("0000")), Err(DecodeError::TrailingBytes(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn deep_nesting_does_not_recurse() -> Result<(), DecodeError> {
+        let mut data = vec![0x81; 1_000_000];
+        data.push(0x00);
+        assert_eq!(validate_well_formed(&data)?.depth, 1_000_000);
+        let mut data = vec![0x9f; 1_000_000];
+        data.resize(2_000_000, 0xff);
+        let mut r = Reader::new(&data);
+        r.skip()?;
+        r.finish()
+    }
+
+    #[test]
+    fn strict_scan_requires_minimal_lengths() {
+        for hex in ["5800", "9800", "d80100", "9f5800ff"] {
+            let data = bytes(hex);
+            assert!(validate_well_formed(&data).is_ok());
+            assert!(scan(&mut Reader::with_mode(&data, ReaderMode::Strict)).is_err(), "{hex}");
+        }
+        // Fixed-width integers and floats keep their width
+        assert!(scan(&mut Reader::with_mode(&bytes("1a00000001"), ReaderMode::Strict)).is_ok());
+    }
+}
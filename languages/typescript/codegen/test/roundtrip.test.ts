@@ -1,4 +1,19 @@
 import { describe, expect, test } from "bun:test";
+import { createHash } from "node:crypto";
+import {
+  setDeterministic,
+  _reset,
+  _finish,
+  _wb,
+  _eNull,
+  _eU32,
+  _eUvar,
+  _eF16,
+  _eF32,
+  _eF64,
+  _eStr,
+  _eArrHdr,
+} from "@cboragen/runtime";
 import {
   type Primitives,
   encodePrimitives,
@@ -338,6 +353,48 @@ describe("encoding determinism", () => {
   });
 });
 
+// The vectors in SPEC.md, "Deterministic Encoding"
+describe("deterministic profile", () => {
+  const hex = (b: Uint8Array) => Array.from(b, (x) => x.toString(16).padStart(2, "0")).join("");
+  const sha256 = (b: Uint8Array) => createHash("sha256").update(b).digest("hex");
+  const bits = new DataView(new ArrayBuffer(8));
+
+  test("spec vectors", () => {
+    setDeterministic(true);
+    try {
+      // struct { 0 x: f64, 2 n: uvarint, 3 name: string, 4 h: f16, 5 s: f32 }
+      // with every float NaN, the f64 one negative with a payload
+      _reset();
+      _eArrHdr(6);
+      bits.setBigUint64(0, 0xfff0000000000001n);
+      _eF64(bits.getFloat64(0));
+      _eNull();
+      _eUvar(500n);
+      _eStr("cbor");
+      _eF16(-NaN);
+      bits.setUint32(0, 0x7f800001);
+      _eF32(bits.getFloat32(0));
+      let data = _finish();
+      expect(hex(data)).toBe("86fb7ff8000000000000f61901f46463626f72f97e00fa7fc00000");
+      expect(sha256(data)).toBe("2cd0c57e594a832b76a391294227b993d4e4ba9f2b776ba545c81bda6d4b0518");
+
+      // struct { 0 a: ?u32, 2 b: ?[]u32 } with a absent and b = [1, 2, 3]
+      _reset();
+      _eArrHdr(3);
+      _wb(0x00);
+      _eNull();
+      _wb(0xc1);
+      _eArrHdr(3);
+      for (const v of [1, 2, 3]) _eU32(v);
+      data = _finish();
+      expect(hex(data)).toBe("8300f6c1831a000000011a000000021a00000003");
+      expect(sha256(data)).toBe("dbcbd8185cb2b020aef2988080f3be3a4ddd2069423f9984f67c33dc87a75e6a");
+    } finally {
+      setDeterministic(false);
+    }
+  });
+});
+
 describe("unicode strings", () => {
   test("roundtrip with emoji and multi-byte", () => {
     const val: WithOptionals = { required: "hello \u{1F600} world \u00E9", maybe: null, maybeStr: "\u{1F4A9}" };
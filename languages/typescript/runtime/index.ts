@@ -7,6 +7,15 @@ const _td = new TextDecoder();
 let _b = new Uint8Array(256);
 let _v = new DataView(_b.buffer);
 let _p = 0;
+let _det = false;
+
+/**
+ * Follow the deterministic encoding profile in SPEC.md, so equal values
+ * always produce identical bytes. Generated encoders already meet the rest
+ * of the profile; this additionally writes every NaN as the canonical
+ * quiet NaN.
+ */
+export function setDeterministic(on: boolean): void { _det = on; }
 
 function _grow(n: number): void {
   if (_p + n <= _b.length) return;
@@ -63,9 +72,21 @@ export function _eIvar(v: bigint): void {
   else { _eMajLen(0x20, -1n - v); }
 }
 
-export function _eF16(v: number): void { _wb(0xf9); _grow(2); _v.setFloat16(_p, v); _p += 2; }
-export function _eF32(v: number): void { _wb(0xfa); _grow(4); _v.setFloat32(_p, v); _p += 4; }
-export function _eF64(v: number): void { _wb(0xfb); _grow(8); _v.setFloat64(_p, v); _p += 8; }
+export function _eF16(v: number): void {
+  _wb(0xf9);
+  if (_det && v !== v) { _w16(0x7e00); return; }
+  _grow(2); _v.setFloat16(_p, v); _p += 2;
+}
+export function _eF32(v: number): void {
+  _wb(0xfa);
+  if (_det && v !== v) { _w32(0x7fc00000); return; }
+  _grow(4); _v.setFloat32(_p, v); _p += 4;
+}
+export function _eF64(v: number): void {
+  _wb(0xfb);
+  if (_det && v !== v) { _w64(0x7ff8000000000000n); return; }
+  _grow(8); _v.setFloat64(_p, v); _p += 8;
+}
 
 export function _eStr(v: string): void {
   const enc = _te.encode(v);